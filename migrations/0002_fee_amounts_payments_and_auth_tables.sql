@@ -0,0 +1,177 @@
+-- Brings the schema of the former docker init script, kept unchanged in 0001, to the one of the
+-- server. Databases set up by that script hold data in the tables of 0001, which is kept.
+--
+-- Their fees, payments and pre-payments were recorded without a price. Before migrating such a
+-- database, set the price they were charged at, in minor units, e.g. for 150.00 TRY:
+--
+--   ALTER DATABASE agem SET agem.legacy_fee_amount = '15000';
+--   ALTER DATABASE agem SET agem.legacy_fee_currency = 'TRY';
+
+DO $$
+DECLARE
+    invalid TEXT;
+BEGIN
+    IF (EXISTS (SELECT 1 FROM MONTLY_FEES)
+            OR EXISTS (SELECT 1 FROM PAYMENTS)
+            OR EXISTS (SELECT 1 FROM PRE_PAYMENTS))
+        AND (COALESCE(current_setting('agem.legacy_fee_amount', TRUE), '') = ''
+            OR COALESCE(current_setting('agem.legacy_fee_currency', TRUE), '') = '')
+    THEN
+        RAISE EXCEPTION 'Fees or payments exist without a price, set agem.legacy_fee_amount '
+            'and agem.legacy_fee_currency as described in migrations/0002.';
+    END IF;
+
+    SELECT string_agg(DISTINCT format('%s-%s', year, month), ', ') INTO invalid
+    FROM (
+        SELECT year, month FROM MONTLY_FEES
+        WHERE month NOT BETWEEN 1 AND 12
+        UNION ALL
+        SELECT year, month FROM MONTLY_FEES
+        GROUP BY year, month HAVING COUNT(*) > 1
+        UNION ALL
+        SELECT year, month FROM PRE_PAYMENTS
+        WHERE month NOT BETWEEN 1 AND 12
+    ) periods;
+    IF invalid IS NOT NULL THEN
+        RAISE EXCEPTION 'Fee periods with an invalid month or recorded twice: %. '
+            'Correct them before migrating.', invalid;
+    END IF;
+END
+$$;
+
+-- fees of a period get a price
+ALTER TABLE MONTLY_FEES RENAME TO MONTHLY_FEES;
+ALTER SEQUENCE MONTLY_FEES_ID_SEQ RENAME TO MONTHLY_FEES_ID_SEQ;
+ALTER TABLE MONTHLY_FEES
+    ADD COLUMN amount INT8,
+    ADD COLUMN currency CHAR(3);
+UPDATE MONTHLY_FEES SET
+    amount = current_setting('agem.legacy_fee_amount', TRUE)::INT8,
+    currency = current_setting('agem.legacy_fee_currency', TRUE);
+ALTER TABLE MONTHLY_FEES
+    ALTER COLUMN amount SET NOT NULL,
+    ALTER COLUMN currency SET NOT NULL,
+    ADD CONSTRAINT MONTHLY_FEES_MONTH_CHECK CHECK (month BETWEEN 1 AND 12),
+    ADD CONSTRAINT MONTHLY_FEES_AMOUNT_CHECK CHECK (amount > 0),
+    ADD CONSTRAINT MONTHLY_FEES_PERIOD_KEY UNIQUE (year, month);
+
+-- payments get an id and may be partial, a former payment settled its fee in full
+ALTER TABLE PAYMENTS
+    DROP CONSTRAINT PAYMENTS_PKEY,
+    ADD COLUMN id SERIAL PRIMARY KEY,
+    ADD COLUMN amount INT8,
+    ADD COLUMN currency CHAR(3),
+    ADD COLUMN paid_at INT8;
+UPDATE PAYMENTS SET
+    amount = MONTHLY_FEES.amount,
+    currency = MONTHLY_FEES.currency,
+    paid_at = EXTRACT(EPOCH FROM now())::INT8
+FROM MONTHLY_FEES
+WHERE MONTHLY_FEES.id = PAYMENTS.fee_id;
+-- payments of fees that no longer exist are removed by 0003
+UPDATE PAYMENTS SET
+    amount = current_setting('agem.legacy_fee_amount', TRUE)::INT8,
+    currency = current_setting('agem.legacy_fee_currency', TRUE),
+    paid_at = EXTRACT(EPOCH FROM now())::INT8
+WHERE amount IS NULL;
+ALTER TABLE PAYMENTS
+    ALTER COLUMN amount SET NOT NULL,
+    ALTER COLUMN currency SET NOT NULL,
+    ALTER COLUMN paid_at SET NOT NULL,
+    ADD CONSTRAINT PAYMENTS_AMOUNT_CHECK CHECK (amount > 0);
+CREATE INDEX PAYMENTS_ACCOUNT_FEE ON PAYMENTS(account_id, fee_id);
+
+-- a former pre-payment covered the fee of its period in full
+ALTER TABLE PRE_PAYMENTS
+    ADD COLUMN amount INT8,
+    ADD COLUMN currency CHAR(3),
+    ADD COLUMN paid_at INT8;
+UPDATE PRE_PAYMENTS SET
+    amount = current_setting('agem.legacy_fee_amount', TRUE)::INT8,
+    currency = current_setting('agem.legacy_fee_currency', TRUE),
+    paid_at = EXTRACT(EPOCH FROM now())::INT8;
+ALTER TABLE PRE_PAYMENTS
+    ALTER COLUMN amount SET NOT NULL,
+    ALTER COLUMN currency SET NOT NULL,
+    ALTER COLUMN paid_at SET NOT NULL,
+    ADD CONSTRAINT PRE_PAYMENTS_MONTH_CHECK CHECK (month BETWEEN 1 AND 12),
+    ADD CONSTRAINT PRE_PAYMENTS_AMOUNT_CHECK CHECK (amount > 0);
+CREATE INDEX PRE_PAYMENTS_ACCOUNT_PERIOD ON PRE_PAYMENTS(account_id, year, month);
+
+-- create REFRESH_TOKENS table
+CREATE TABLE REFRESH_TOKENS (
+    jti UUID PRIMARY KEY,
+    family_id UUID NOT NULL,
+    account_id INT NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    issued_at INT8 NOT NULL,
+    expires_at INT8 NOT NULL,
+    rotated_at INT8,
+    revoked_at INT8
+);
+
+-- create PASSWORD_RESET_TOKENS table
+CREATE TABLE PASSWORD_RESET_TOKENS (
+    token_hash VARCHAR(64) PRIMARY KEY,
+    account_id INT NOT NULL,
+    expires_at INT8 NOT NULL,
+    used_at INT8
+);
+
+-- create ACCOUNT_TOTP table
+CREATE TABLE ACCOUNT_TOTP (
+    account_id INT PRIMARY KEY,
+    secret VARCHAR(64) NOT NULL,
+    confirmed BOOLEAN NOT NULL,
+    last_used_step INT8,
+    created_at INT8 NOT NULL
+);
+
+-- create ACCOUNT_RECOVERY_CODES table
+CREATE TABLE ACCOUNT_RECOVERY_CODES (
+    id SERIAL PRIMARY KEY,
+    account_id INT NOT NULL,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at INT8
+);
+
+-- create PERMISSIONS table
+CREATE TABLE PERMISSIONS (
+    id SERIAL PRIMARY KEY,
+    permission VARCHAR(100) UNIQUE NOT NULL
+);
+
+-- create ROLE_PERMISSIONS table
+CREATE TABLE ROLE_PERMISSIONS (
+    role_id INT NOT NULL,
+    permission_id INT NOT NULL,
+    PRIMARY KEY(role_id, permission_id)
+);
+
+-- built-in roles, Admin implicitly holds every permission
+INSERT INTO ROLES(role) VALUES
+    ('Admin'),
+    ('Treasurer')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO PERMISSIONS(permission) VALUES
+    ('accounts.read'),
+    ('accounts.write'),
+    ('roles.read'),
+    ('roles.write'),
+    ('fees.read'),
+    ('fees.write'),
+    ('payments.read'),
+    ('payments.write'),
+    ('payments.reverse');
+
+INSERT INTO ROLE_PERMISSIONS(role_id, permission_id)
+SELECT ROLES.id, PERMISSIONS.id FROM ROLES, PERMISSIONS
+WHERE ROLES.role = 'Treasurer'
+    AND PERMISSIONS.permission IN (
+        'accounts.read',
+        'fees.read',
+        'payments.read',
+        'payments.write',
+        'payments.reverse'
+    );
@@ -0,0 +1,95 @@
+-- rows of the link tables pointing at deleted accounts, roles or permissions
+DELETE FROM ACCOUNT_ROLES
+WHERE account_id NOT IN (SELECT id FROM ACCOUNTS)
+    OR role_id NOT IN (SELECT id FROM ROLES);
+
+DELETE FROM ROLE_PERMISSIONS
+WHERE role_id NOT IN (SELECT id FROM ROLES)
+    OR permission_id NOT IN (SELECT id FROM PERMISSIONS);
+
+-- credentials of deleted accounts
+DELETE FROM REFRESH_TOKENS WHERE account_id NOT IN (SELECT id FROM ACCOUNTS);
+DELETE FROM PASSWORD_RESET_TOKENS WHERE account_id NOT IN (SELECT id FROM ACCOUNTS);
+DELETE FROM ACCOUNT_TOTP WHERE account_id NOT IN (SELECT id FROM ACCOUNTS);
+DELETE FROM ACCOUNT_RECOVERY_CODES WHERE account_id NOT IN (SELECT id FROM ACCOUNTS);
+
+-- payments record money received and accounts can not be merged automatically,
+-- so those are reported to be corrected by hand
+DO $$
+DECLARE
+    invalid TEXT;
+BEGIN
+    SELECT string_agg(format('%s (account %s, fee %s)', id, account_id, fee_id), ', ')
+    INTO invalid
+    FROM PAYMENTS
+    WHERE account_id NOT IN (SELECT id FROM ACCOUNTS)
+        OR fee_id NOT IN (SELECT id FROM MONTHLY_FEES);
+    IF invalid IS NOT NULL THEN
+        RAISE EXCEPTION 'Payments of deleted accounts or fees: %. '
+            'Remove or reassign them before migrating.', invalid;
+    END IF;
+
+    SELECT string_agg(format('%s (account %s)', id, account_id), ', ')
+    INTO invalid
+    FROM PRE_PAYMENTS
+    WHERE account_id NOT IN (SELECT id FROM ACCOUNTS);
+    IF invalid IS NOT NULL THEN
+        RAISE EXCEPTION 'Pre-payments of deleted accounts: %. '
+            'Remove or reassign them before migrating.', invalid;
+    END IF;
+
+    SELECT string_agg(format('%s (accounts %s)', email, ids), ', ')
+    INTO invalid
+    FROM (
+        SELECT email, string_agg(id::TEXT, ', ' ORDER BY id) AS ids FROM ACCOUNTS
+        GROUP BY email HAVING COUNT(*) > 1
+    ) duplicates;
+    IF invalid IS NOT NULL THEN
+        RAISE EXCEPTION 'Emails used by more than one account: %. '
+            'Give every account its own email before migrating.', invalid;
+    END IF;
+END
+$$;
+
+-- login looks accounts up by email
+ALTER TABLE ACCOUNTS
+    ADD CONSTRAINT ACCOUNTS_EMAIL_KEY UNIQUE (email);
+
+ALTER TABLE ACCOUNT_ROLES
+    ADD CONSTRAINT ACCOUNT_ROLES_ACCOUNT_FK FOREIGN KEY (account_id)
+        REFERENCES ACCOUNTS(id) ON DELETE CASCADE,
+    ADD CONSTRAINT ACCOUNT_ROLES_ROLE_FK FOREIGN KEY (role_id)
+        REFERENCES ROLES(id) ON DELETE CASCADE;
+
+ALTER TABLE ROLE_PERMISSIONS
+    ADD CONSTRAINT ROLE_PERMISSIONS_ROLE_FK FOREIGN KEY (role_id)
+        REFERENCES ROLES(id) ON DELETE CASCADE,
+    ADD CONSTRAINT ROLE_PERMISSIONS_PERMISSION_FK FOREIGN KEY (permission_id)
+        REFERENCES PERMISSIONS(id) ON DELETE CASCADE;
+
+-- payments are records of money received, they keep their account and fee from being deleted
+ALTER TABLE PAYMENTS
+    ADD CONSTRAINT PAYMENTS_ACCOUNT_FK FOREIGN KEY (account_id)
+        REFERENCES ACCOUNTS(id) ON DELETE RESTRICT,
+    ADD CONSTRAINT PAYMENTS_FEE_FK FOREIGN KEY (fee_id)
+        REFERENCES MONTHLY_FEES(id) ON DELETE RESTRICT;
+
+ALTER TABLE PRE_PAYMENTS
+    ADD CONSTRAINT PRE_PAYMENTS_ACCOUNT_FK FOREIGN KEY (account_id)
+        REFERENCES ACCOUNTS(id) ON DELETE RESTRICT;
+
+ALTER TABLE REFRESH_TOKENS
+    ADD CONSTRAINT REFRESH_TOKENS_ACCOUNT_FK FOREIGN KEY (account_id)
+        REFERENCES ACCOUNTS(id) ON DELETE CASCADE;
+
+ALTER TABLE PASSWORD_RESET_TOKENS
+    ADD CONSTRAINT PASSWORD_RESET_TOKENS_ACCOUNT_FK FOREIGN KEY (account_id)
+        REFERENCES ACCOUNTS(id) ON DELETE CASCADE;
+
+ALTER TABLE ACCOUNT_TOTP
+    ADD CONSTRAINT ACCOUNT_TOTP_ACCOUNT_FK FOREIGN KEY (account_id)
+        REFERENCES ACCOUNTS(id) ON DELETE CASCADE;
+
+ALTER TABLE ACCOUNT_RECOVERY_CODES
+    ADD CONSTRAINT ACCOUNT_RECOVERY_CODES_ACCOUNT_FK FOREIGN KEY (account_id)
+        REFERENCES ACCOUNTS(id) ON DELETE CASCADE;
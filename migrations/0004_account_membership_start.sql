@@ -0,0 +1,10 @@
+-- accounts are charged the fees of the periods from the start of their membership on
+ALTER TABLE ACCOUNTS ADD COLUMN member_since INT8;
+
+-- existing accounts were charged every fee, so their membership starts with the first fee
+UPDATE ACCOUNTS SET member_since = COALESCE(
+    (SELECT EXTRACT(EPOCH FROM MIN(make_date(year, month, 1)))::INT8 FROM MONTHLY_FEES),
+    EXTRACT(EPOCH FROM now())::INT8
+);
+
+ALTER TABLE ACCOUNTS ALTER COLUMN member_since SET NOT NULL;
@@ -0,0 +1,21 @@
+-- emails are stored trimmed and in lowercase, which is also how login looks them up
+DO $$
+DECLARE
+    invalid TEXT;
+BEGIN
+    SELECT string_agg(format('%s (accounts %s)', email, ids), ', ')
+    INTO invalid
+    FROM (
+        SELECT lower(trim(email)) AS email, string_agg(id::TEXT, ', ' ORDER BY id) AS ids
+        FROM ACCOUNTS
+        GROUP BY lower(trim(email)) HAVING COUNT(*) > 1
+    ) duplicates;
+    IF invalid IS NOT NULL THEN
+        RAISE EXCEPTION 'Emails used by more than one account when compared in lowercase: %. '
+            'Give every account its own email before migrating.', invalid;
+    END IF;
+END
+$$;
+
+UPDATE ACCOUNTS SET email = lower(trim(email))
+WHERE email <> lower(trim(email));
@@ -0,0 +1,30 @@
+-- payments keep the amount received, every reversal of a part of one is recorded on its own,
+-- in the currency of the payment
+CREATE TABLE PAYMENT_REVERSALS (
+    id SERIAL PRIMARY KEY,
+    payment_id INT NOT NULL,
+    amount INT8 NOT NULL,
+    reversed_by INT NOT NULL,
+    reversed_at INT8 NOT NULL,
+    CONSTRAINT PAYMENT_REVERSALS_PAYMENT_FK FOREIGN KEY (payment_id)
+        REFERENCES PAYMENTS(id) ON DELETE RESTRICT,
+    CONSTRAINT PAYMENT_REVERSALS_ACTOR_FK FOREIGN KEY (reversed_by)
+        REFERENCES ACCOUNTS(id) ON DELETE RESTRICT,
+    CONSTRAINT PAYMENT_REVERSALS_AMOUNT_CHECK CHECK (amount > 0)
+);
+CREATE INDEX PAYMENT_REVERSALS_PAYMENT ON PAYMENT_REVERSALS(payment_id);
+
+-- what is left of every payment, balances are computed from this
+CREATE VIEW NET_PAYMENTS AS
+SELECT
+    PAYMENTS.id,
+    PAYMENTS.account_id,
+    PAYMENTS.fee_id,
+    (PAYMENTS.amount - COALESCE(reversed.amount, 0))::INT8 AS amount,
+    PAYMENTS.currency,
+    PAYMENTS.paid_at
+FROM PAYMENTS
+LEFT JOIN (
+    SELECT payment_id, SUM(amount) AS amount FROM PAYMENT_REVERSALS
+    GROUP BY payment_id
+) AS reversed ON reversed.payment_id = PAYMENTS.id;
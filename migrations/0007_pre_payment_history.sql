@@ -0,0 +1,36 @@
+-- pre-payments are kept once applied, marked with the payment they became
+ALTER TABLE PRE_PAYMENTS
+    ADD COLUMN applied_payment_id INT,
+    ADD COLUMN applied_at INT8,
+    ADD CONSTRAINT PRE_PAYMENTS_APPLIED_PAYMENT_FK FOREIGN KEY (applied_payment_id)
+        REFERENCES PAYMENTS(id) ON DELETE RESTRICT,
+    ADD CONSTRAINT PRE_PAYMENTS_APPLIED_CHECK
+        CHECK ((applied_payment_id IS NULL) = (applied_at IS NULL));
+
+-- reversals of pre-payments are recorded like those of payments
+ALTER TABLE PAYMENT_REVERSALS
+    ALTER COLUMN payment_id DROP NOT NULL,
+    ADD COLUMN pre_payment_id INT,
+    ADD CONSTRAINT PAYMENT_REVERSALS_PRE_PAYMENT_FK FOREIGN KEY (pre_payment_id)
+        REFERENCES PRE_PAYMENTS(id) ON DELETE RESTRICT,
+    ADD CONSTRAINT PAYMENT_REVERSALS_REVERSED_CHECK
+        CHECK (num_nonnulls(payment_id, pre_payment_id) = 1);
+CREATE INDEX PAYMENT_REVERSALS_PRE_PAYMENT ON PAYMENT_REVERSALS(pre_payment_id);
+
+-- what is left of every pre-payment not applied yet, balances are computed from this
+CREATE VIEW NET_PRE_PAYMENTS AS
+SELECT
+    PRE_PAYMENTS.id,
+    PRE_PAYMENTS.account_id,
+    PRE_PAYMENTS.year,
+    PRE_PAYMENTS.month,
+    (PRE_PAYMENTS.amount - COALESCE(reversed.amount, 0))::INT8 AS amount,
+    PRE_PAYMENTS.currency,
+    PRE_PAYMENTS.paid_at
+FROM PRE_PAYMENTS
+LEFT JOIN (
+    SELECT pre_payment_id, SUM(amount) AS amount FROM PAYMENT_REVERSALS
+    WHERE pre_payment_id IS NOT NULL
+    GROUP BY pre_payment_id
+) AS reversed ON reversed.pre_payment_id = PRE_PAYMENTS.id
+WHERE PRE_PAYMENTS.applied_payment_id IS NULL;
@@ -1,172 +1,507 @@
-use actix_web::{HttpResponse, Responder, get, http::header::ContentType, post, web};
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, get,
+    http::header::{self, ContentType},
+    post, web,
+};
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+use uuid::Uuid;
+use validator::Validate;
 
 use crate::{
-    auth::{ACCESS_TOKEN_EXPIRES_IN, Claims},
-    db::DatabaseConnection,
-    password::verify_password,
-    response::HttpErrorBody,
-    service::{self, Account, Role},
-    util::BearerToken,
+    api::validation::{Valid, email, validate_password},
+    auth::{
+        Claims, TokenLifetimes, extract::Authenticated, generate_token, hash_token, keys::JwtKeys,
+        throttle::LoginThrottle,
+    },
+    db::{
+        DatabaseConnection,
+        types::{DB_RefreshToken, ID},
+    },
+    error::{AppError, AppResult},
+    mail::{Mail, MailSender},
+    metrics::{LoginFailure, Metrics, TokenGrant},
+    password::{PasswordHasher, verify_password},
+    response::{HttpErrorBody, HttpJsonMessageBody},
+    service::{self, Account, RefreshToken, Role},
+    util::unix_timestamp,
 };
 
-const JWT_ENCODING_KEY_SECRET: &'static str = "c284f7ac-b1cd-4f14-bdeb-cd4736974e3b";
+use super::mfa::{is_mfa_mandatory, mail_totp_enrollment_token};
 
-#[derive(Deserialize)]
+// Credentials are only checked for presence, the password policy applies to new passwords.
+#[derive(Deserialize, Validate)]
 struct AccessTokenRequest {
+    #[serde(deserialize_with = "email")]
+    #[validate(length(min = 1, message = "Email is required."))]
     email: String,
+    #[validate(length(min = 1, message = "Password is required."))]
     password: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 struct RefreshRequest {
+    #[validate(length(min = 1, message = "Refresh token is required."))]
     refresh_token: String,
 }
 
+#[derive(Deserialize, Validate)]
+struct ChangePasswordRequest {
+    #[validate(length(min = 1, message = "Current password is required."))]
+    old_password: String,
+    #[validate(custom(function = "validate_password"))]
+    new_password: String,
+}
+
+#[derive(Deserialize, Validate)]
+struct ForgotPasswordRequest {
+    #[serde(deserialize_with = "email")]
+    #[validate(email)]
+    email: String,
+}
+
+#[derive(Deserialize, Validate)]
+struct ResetPasswordRequest {
+    #[validate(length(min = 1, message = "Token is required."))]
+    token: String,
+    #[validate(custom(function = "validate_password"))]
+    new_password: String,
+}
+
+#[derive(Serialize)]
+struct MfaRequiredResponse {
+    mfa_required: bool,
+    mfa_token: String,
+    expires_in: usize,
+    /// The account has to enroll before logging in, with the token mailed to it.
+    enrollment_required: bool,
+}
+
 #[derive(Deserialize, Serialize)]
-struct TokenResponse {
+pub(super) struct TokenResponse {
     access_token: String,
     expires_in: usize,
     refresh_token: String,
 }
 
 impl TokenResponse {
-    fn create(account: Account, roles: Vec<Role>) -> TokenResponse {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as usize;
-
+    fn create(
+        keys: &JwtKeys,
+        lifetimes: &TokenLifetimes,
+        now: usize,
+        account: &Account,
+        roles: Vec<Role>,
+        permissions: Vec<String>,
+        refresh_token_id: Uuid,
+    ) -> TokenResponse {
         let access_token_claims = Claims::for_access_token(
             now,
+            lifetimes.access,
             account.email.clone(),
             roles.into_iter().map(|r| r.role).collect(),
+            permissions,
+        );
+        let refresh_token_claims = Claims::for_refresh_token(
+            now,
+            lifetimes.refresh,
+            account.email.clone(),
+            refresh_token_id.to_string(),
         );
-        let refresh_token_claims = Claims::for_refresh_token(now, account.email.clone());
-
-        let access_token = jsonwebtoken::encode(
-            &jsonwebtoken::Header::default(),
-            &access_token_claims,
-            &jsonwebtoken::EncodingKey::from_secret(JWT_ENCODING_KEY_SECRET.as_ref()),
-        )
-        .unwrap();
-
-        let refresh_token = jsonwebtoken::encode(
-            &jsonwebtoken::Header::default(),
-            &refresh_token_claims,
-            &jsonwebtoken::EncodingKey::from_secret(JWT_ENCODING_KEY_SECRET.as_ref()),
-        )
-        .unwrap();
+
+        let access_token = keys.encode(&access_token_claims).unwrap();
+        let refresh_token = keys.encode(&refresh_token_claims).unwrap();
 
         TokenResponse {
             access_token,
-            expires_in: ACCESS_TOKEN_EXPIRES_IN,
+            expires_in: lifetimes.access,
             refresh_token,
         }
     }
 }
 
-#[get("/me")]
-pub async fn get_me_from_access_token(bearer_token: web::Header<BearerToken>) -> impl Responder {
-    let bearer_token = bearer_token.into_inner();
-
-    let claims = match jsonwebtoken::decode::<Claims>(
-        &bearer_token.token,
-        &jsonwebtoken::DecodingKey::from_secret(JWT_ENCODING_KEY_SECRET.as_ref()),
-        &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256),
-    ) {
-        Ok(c) => c.claims,
+/// Issues a new token pair and persists its refresh token as a member of `family_id`.
+///
+/// Every login starts a new family, every refresh continues the family of the presented token.
+pub(super) async fn issue_tokens(
+    db: DatabaseConnection,
+    keys: &JwtKeys,
+    lifetimes: &TokenLifetimes,
+    account: &Account,
+    roles: Vec<Role>,
+    family_id: Uuid,
+) -> AppResult<TokenResponse> {
+    let now = unix_timestamp();
+    let jti = Uuid::new_v4();
+
+    let permissions = service::role::fetch_permissions_of_roles(db.clone(), &roles).await?;
+    let token_response = TokenResponse::create(
+        keys,
+        lifetimes,
+        now as usize,
+        account,
+        roles,
+        permissions,
+        jti,
+    );
+
+    service::token::store_refresh_token(
+        db,
+        DB_RefreshToken {
+            jti,
+            family_id,
+            account_id: account.id,
+            token_hash: hash_token(&token_response.refresh_token),
+            issued_at: now as i64,
+            expires_at: (now as usize + lifetimes.refresh) as i64,
+            rotated_at: None,
+            revoked_at: None,
+        },
+    )
+    .await?;
+
+    Ok(token_response)
+}
+
+/// Decodes a refresh token and looks up its server side record.
+async fn validate_refresh_token(
+    db: DatabaseConnection,
+    keys: &JwtKeys,
+    refresh_token: &str,
+) -> AppResult<(Claims, RefreshToken)> {
+    let claims = match keys.decode::<Claims>(refresh_token) {
+        Ok(c) if c.is_refresh_token() => c,
+        Ok(_) => {
+            return Err(AppError::unauthorized("Invalid token."));
+        }
         Err(err) => {
-            return HttpResponse::BadRequest().error_body(err);
+            return Err(AppError::unauthorized(err));
         }
     };
 
-    HttpResponse::Ok().json(claims)
+    let Some(jti) = claims
+        .jti
+        .as_deref()
+        .and_then(|jti| Uuid::parse_str(jti).ok())
+    else {
+        return Err(AppError::unauthorized("Invalid token."));
+    };
+
+    let record = service::token::fetch_refresh_token(db, jti).await?;
+    if record.token_hash != hash_token(refresh_token) {
+        return Err(AppError::unauthorized("Invalid token."));
+    }
+
+    Ok((claims, record))
+}
+
+#[get("/jwks.json")]
+pub async fn get_jwks(keys: web::Data<JwtKeys>) -> impl Responder {
+    HttpResponse::Ok().json(keys.jwks())
+}
+
+#[get("/me")]
+pub async fn get_me_from_access_token(claims: Authenticated) -> impl Responder {
+    HttpResponse::Ok().json(claims.into_inner())
 }
 
 #[post("/connect")]
+#[allow(clippy::too_many_arguments)]
 pub async fn create_access_token(
+    req: HttpRequest,
     db: web::Data<DatabaseConnection>,
-    request: web::Form<AccessTokenRequest>,
-) -> impl Responder {
+    keys: web::Data<JwtKeys>,
+    lifetimes: web::Data<TokenLifetimes>,
+    hasher: web::Data<PasswordHasher>,
+    throttle: web::Data<LoginThrottle>,
+    metrics: web::Data<Metrics>,
+    mail_sender: web::Data<dyn MailSender>,
+    request: Valid<web::Form<AccessTokenRequest>>,
+) -> Result<HttpResponse, AppError> {
     let db = (*db.into_inner()).clone();
-    let request = request.into_inner();
+    let request = request.into_inner().into_inner();
+    let ip = req.peer_addr().map(|addr| addr.ip());
 
-    let account = match service::account::fetch_account_by_email(db.clone(), request.email).await {
-        Ok(Some(a)) => a,
-        Ok(None) => {
-            return HttpResponse::NotFound().error_body("Account not found.");
-        }
-        Err(err) => {
-            return HttpResponse::InternalServerError().error_body(err);
+    if let Some(retry_after) = throttle.locked_for(&request.email, ip) {
+        metrics.record_failed_login(LoginFailure::Throttled);
+        return Ok(HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after))
+            .take()
+            .error_body("Too many failed login attempts. Try again later."));
+    }
+
+    let account =
+        service::account::fetch_account_by_email(db.clone(), request.email.clone()).await?;
+
+    // Unknown account and wrong password are indistinguishable to the client.
+    let account = match account {
+        Some(a) if verify_password(&request.password, &a.hashed_password) => a,
+        account => {
+            if account.is_none() {
+                hasher.verify_dummy_password(&request.password);
+            }
+            throttle.record_failure(&request.email, ip);
+            metrics.record_failed_login(LoginFailure::InvalidCredentials);
+            return Err(AppError::unauthorized("Invalid credentials."));
         }
     };
 
-    if !verify_password(&request.password, &account.hashed_password) {
-        return HttpResponse::NotFound().error_body("Incorrect password.");
-    }
+    let mfa_enabled = service::mfa::fetch_totp(db.clone(), account.id)
+        .await?
+        .is_some_and(|t| t.confirmed);
+    let roles = service::account::fetch_account_roles(db.clone(), account.id).await?;
 
-    let roles = match service::account::fetch_account_roles(db, account.id).await {
-        Ok(a) => a,
-        Err(err) => {
-            return HttpResponse::InternalServerError().error_body(err);
+    // Accounts with two-factor authentication get a short-lived token to exchange at /mfa/verify,
+    // accounts that must have it are mailed a token to enroll with at /mfa/enroll first. Their
+    // failures are only forgotten once the code is verified.
+    if mfa_enabled || is_mfa_mandatory(&roles) {
+        if !mfa_enabled {
+            mail_totp_enrollment_token(&keys, &lifetimes, &**mail_sender, &account).await?;
         }
-    };
+        let claims = Claims::for_mfa_pending_token(
+            unix_timestamp() as usize,
+            lifetimes.mfa_pending,
+            account.email,
+        );
+        let mfa_token = keys
+            .encode(&claims)
+            .map_err(|err| AppError::Internal(err.into()))?;
+
+        return Ok(HttpResponse::Ok().json(MfaRequiredResponse {
+            mfa_required: true,
+            mfa_token,
+            expires_in: lifetimes.mfa_pending,
+            enrollment_required: !mfa_enabled,
+        }));
+    }
+
+    throttle.record_success(&request.email);
 
-    let token_response = TokenResponse::create(account, roles);
+    service::token::delete_expired_refresh_tokens(db.clone(), account.id, unix_timestamp() as i64)
+        .await?;
 
-    HttpResponse::Ok()
+    let token_response =
+        issue_tokens(db, &keys, &lifetimes, &account, roles, Uuid::new_v4()).await?;
+    metrics.record_tokens_issued(TokenGrant::Password);
+
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::json())
-        .json(token_response)
+        .json(token_response))
 }
 
 #[post("/refresh")]
 pub async fn refresh_access_token(
     db: web::Data<DatabaseConnection>,
-    request: web::Form<RefreshRequest>,
-) -> impl Responder {
+    keys: web::Data<JwtKeys>,
+    lifetimes: web::Data<TokenLifetimes>,
+    metrics: web::Data<Metrics>,
+    request: Valid<web::Form<RefreshRequest>>,
+) -> Result<HttpResponse, AppError> {
     let db = (*db.into_inner()).clone();
-    let request = request.into_inner();
+    let request = request.into_inner().into_inner();
 
     // Validate refresh token
-    let (issued_at, account_email) = {
-        let refresh_token_claims = match jsonwebtoken::decode::<Claims>(
-            &request.refresh_token,
-            &jsonwebtoken::DecodingKey::from_secret(JWT_ENCODING_KEY_SECRET.as_ref()),
-            &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256),
-        ) {
-            Ok(c) => c.claims,
-            Err(err) => {
-                return HttpResponse::BadRequest().error_body(err);
-            }
-        };
+    let (claims, record) =
+        validate_refresh_token(db.clone(), &keys, &request.refresh_token).await?;
+
+    if record.is_revoked() {
+        return Err(AppError::unauthorized("Invalid token."));
+    }
 
-        (refresh_token_claims.iat, refresh_token_claims.sub)
+    // A rotated token is presented again: either the client or an attacker holds a stolen copy,
+    // so the whole family is revoked and both have to log in again.
+    let now = unix_timestamp() as i64;
+    let rotated = match record.is_rotated() {
+        true => false,
+        false => service::token::rotate_refresh_token(db.clone(), record.jti, now).await?,
     };
 
+    if !rotated {
+        metrics.record_refresh_token_reuse();
+        service::token::revoke_refresh_token_family(db, record.family_id, now).await?;
+        return Err(AppError::unauthorized("Refresh token reuse detected."));
+    }
+
     // Make access token
-    let account = match service::account::fetch_account_by_email(db.clone(), account_email).await {
-        Ok(Some(a)) => a,
-        Ok(None) => {
-            return HttpResponse::NotFound().error_body("Account not found.");
-        }
-        Err(err) => {
-            return HttpResponse::InternalServerError().error_body(err);
+    let account = service::account::fetch_account_by_email(db.clone(), claims.sub)
+        .await?
+        .ok_or_else(|| AppError::not_found("Account not found."))?;
+
+    if claims.iat < account.password_set_ts as usize {
+        return Err(AppError::unauthorized("Invalid token."));
+    }
+
+    let roles = service::account::fetch_account_roles(db.clone(), account.id).await?;
+
+    // sessions started before the account had to use a second factor end here
+    if is_mfa_mandatory(&roles) {
+        match service::mfa::fetch_totp(db.clone(), account.id).await? {
+            Some(totp) if totp.confirmed => {}
+            _ => {
+                return Err(AppError::forbidden(
+                    "Two-factor authentication is required, log in again to enroll.",
+                ));
+            }
         }
-    };
+    }
+
+    let token_response =
+        issue_tokens(db, &keys, &lifetimes, &account, roles, record.family_id).await?;
+    metrics.record_tokens_issued(TokenGrant::Refresh);
+
+    Ok(HttpResponse::Ok().json(token_response))
+}
+
+#[post("/logout")]
+pub async fn revoke_refresh_token(
+    db: web::Data<DatabaseConnection>,
+    keys: web::Data<JwtKeys>,
+    request: Valid<web::Form<RefreshRequest>>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner().into_inner();
+
+    let (_, record) = validate_refresh_token(db.clone(), &keys, &request.refresh_token).await?;
+    service::token::revoke_refresh_token_family(db, record.family_id, unix_timestamp() as i64)
+        .await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+#[post("/logout/all")]
+pub async fn revoke_all_refresh_tokens(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let account = service::account::fetch_account_by_email(db.clone(), claims.into_inner().sub)
+        .await?
+        .ok_or_else(|| AppError::not_found("Account not found."))?;
+    service::token::revoke_account_refresh_tokens(db, account.id, unix_timestamp() as i64).await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+/// Replaces the password of an account and logs it out of every device.
+async fn set_password(
+    db: DatabaseConnection,
+    hasher: &PasswordHasher,
+    account_id: ID,
+    new_password: &str,
+) -> AppResult<()> {
+    let now = unix_timestamp() as i64;
 
-    if issued_at <= account.password_set_ts as usize {
-        return HttpResponse::BadRequest().error_body("Invalid token.");
+    service::account::update_password(
+        db.clone(),
+        account_id,
+        hasher.hash_password(new_password),
+        now,
+    )
+    .await?;
+    service::token::revoke_account_refresh_tokens(db, account_id, now).await?;
+
+    Ok(())
+}
+
+#[post("/password")]
+pub async fn change_password(
+    db: web::Data<DatabaseConnection>,
+    hasher: web::Data<PasswordHasher>,
+    claims: Authenticated,
+    request: Valid<web::Form<ChangePasswordRequest>>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner().into_inner();
+
+    let account = service::account::fetch_account_by_email(db.clone(), claims.into_inner().sub)
+        .await?
+        .ok_or_else(|| AppError::not_found("Account not found."))?;
+
+    if !verify_password(&request.old_password, &account.hashed_password) {
+        return Err(AppError::validation("Incorrect password."));
     }
 
-    let roles = match service::account::fetch_account_roles(db, account.id).await {
-        Ok(a) => a,
-        Err(err) => {
-            return HttpResponse::InternalServerError().error_body(err);
+    set_password(db, &hasher, account.id, &request.new_password).await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+#[post("/password/forgot")]
+pub async fn request_password_reset(
+    db: web::Data<DatabaseConnection>,
+    lifetimes: web::Data<TokenLifetimes>,
+    mail_sender: web::Data<dyn MailSender>,
+    request: Valid<web::Form<ForgotPasswordRequest>>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner().into_inner();
+
+    // Neither the response nor the time it takes tell whether the account exists, so they
+    // cannot be used to probe emails. A token is made for unknown emails too, and storing and
+    // mailing it is left to run after the response.
+    let account = service::account::fetch_account_by_email(db.clone(), request.email).await?;
+
+    let token = generate_token();
+    let token_hash = hash_token(&token);
+    let expires_at = unix_timestamp() as usize + lifetimes.password_reset;
+    let lifetime = lifetimes.password_reset;
+
+    actix_web::rt::spawn(
+        async move {
+            let Some(account) = account else {
+                return;
+            };
+            let mail = Mail {
+                to: account.email,
+                subject: "Password reset".to_string(),
+                body: format!(
+                    "Use the following token to reset your password. It expires in {} minutes.\r\n\r\n{}",
+                    lifetime / 60,
+                    token,
+                ),
+            };
+            if let Err(err) =
+                send_password_reset(db, &**mail_sender, token_hash, account.id, expires_at, mail)
+                    .await
+            {
+                tracing::error!(error = format!("{err:#}"), "password reset not sent");
+            }
         }
-    };
+        .in_current_span(),
+    );
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+async fn send_password_reset(
+    db: DatabaseConnection,
+    mail_sender: &dyn MailSender,
+    token_hash: String,
+    account_id: ID,
+    expires_at: usize,
+    mail: Mail,
+) -> anyhow::Result<()> {
+    service::token::store_password_reset_token(db, token_hash, account_id, expires_at as i64)
+        .await?;
+    mail_sender.send(mail).await
+}
+
+#[post("/password/reset")]
+pub async fn reset_password(
+    db: web::Data<DatabaseConnection>,
+    hasher: web::Data<PasswordHasher>,
+    request: Valid<web::Form<ResetPasswordRequest>>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner().into_inner();
 
-    let token_response = TokenResponse::create(account, roles);
+    let account_id = service::token::consume_password_reset_token(
+        db.clone(),
+        hash_token(&request.token),
+        unix_timestamp() as i64,
+    )
+    .await?;
+    set_password(db, &hasher, account_id, &request.new_password).await?;
 
-    HttpResponse::Ok().json(token_response)
+    Ok(HttpResponse::Ok().json_message_body("Success"))
 }
@@ -0,0 +1,61 @@
+//! Probes of the process supervisor, served without authentication.
+
+use actix_web::{HttpResponse, Responder, get, web};
+use serde::Serialize;
+
+use crate::{
+    db::DatabaseConnection,
+    response::HttpErrorBody,
+    service::{self, health::Readiness},
+};
+
+#[derive(Serialize)]
+struct Status {
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+struct Version {
+    version: &'static str,
+    git_commit: &'static str,
+    /// Unix timestamp of the build.
+    built_at: u64,
+}
+
+/// The process is up and serving requests.
+#[get("/healthz")]
+pub async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(Status { status: "ok" })
+}
+
+/// The database answers and every embedded migration is applied.
+#[get("/readyz")]
+pub async fn readyz(db: web::Data<DatabaseConnection>) -> impl Responder {
+    let db = (*db.into_inner()).clone();
+
+    match service::health::check_readiness(db).await {
+        Readiness::Ready => HttpResponse::Ok().json(Status { status: "ready" }),
+        Readiness::DatabaseUnavailable => HttpResponse::ServiceUnavailable()
+            .problem_body("unavailable", "Database is unavailable."),
+        Readiness::PendingMigrations(versions) => HttpResponse::ServiceUnavailable().problem_body(
+            "unavailable",
+            format!(
+                "Database has pending migrations: {}.",
+                versions
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ),
+    }
+}
+
+#[get("/version")]
+pub async fn version() -> impl Responder {
+    HttpResponse::Ok().json(Version {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("AGEM_GIT_COMMIT"),
+        built_at: env!("AGEM_BUILT_AT").parse().unwrap_or_default(),
+    })
+}
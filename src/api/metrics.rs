@@ -0,0 +1,21 @@
+//! Scrape target of Prometheus, served without authentication like the health probes.
+
+use actix_web::{HttpResponse, Responder, ResponseError, get, web};
+
+use crate::{db::DatabaseConnection, error::AppError, metrics::Metrics, service};
+
+#[get("/metrics")]
+pub async fn get_metrics(
+    db: web::Data<DatabaseConnection>,
+    metrics: web::Data<Metrics>,
+) -> impl Responder {
+    let db = (*db.into_inner()).clone();
+
+    let pool = service::health::fetch_pool_stats(db).await;
+    match metrics.render(pool) {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(prometheus::TEXT_FORMAT)
+            .body(body),
+        Err(err) => AppError::Internal(err.into()).error_response(),
+    }
+}
@@ -0,0 +1,368 @@
+use actix_web::{HttpRequest, HttpResponse, http::header, post, web};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        Claims, ROLE_ADMIN, TokenLifetimes, extract::Authenticated, hash_token, keys::JwtKeys,
+        throttle::LoginThrottle, totp,
+    },
+    db::DatabaseConnection,
+    error::{AppError, AppResult},
+    mail::{Mail, MailSender},
+    metrics::{LoginFailure, Metrics, TokenGrant},
+    response::{HttpErrorBody, HttpJsonMessageBody},
+    service::{self, Account, AccountTotp, Role},
+    util::unix_timestamp,
+};
+
+use super::auth::{TokenResponse, issue_tokens};
+
+#[derive(Deserialize)]
+struct CodeRequest {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct EnrollMfaRequest {
+    mfa_token: String,
+    enrollment_token: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyMfaRequest {
+    mfa_token: String,
+    code: String,
+}
+
+#[derive(Serialize)]
+struct TotpEnrollmentResponse {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Serialize)]
+struct VerifyMfaResponse {
+    #[serde(flatten)]
+    tokens: TokenResponse,
+    /// Only when the code confirmed an enrollment started at `/mfa/enroll`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+async fn fetch_authenticated_account(
+    db: DatabaseConnection,
+    claims: Authenticated,
+) -> AppResult<Account> {
+    service::account::fetch_account_by_email(db, claims.into_inner().sub)
+        .await?
+        .ok_or_else(|| AppError::not_found("Account not found."))
+}
+
+/// Claims of a token handed out during login, of the type `is_expected` checks for.
+fn decode_login_token(
+    keys: &JwtKeys,
+    token: &str,
+    is_expected: fn(&Claims) -> bool,
+) -> AppResult<Claims> {
+    match keys.decode::<Claims>(token) {
+        Ok(c) if is_expected(&c) => Ok(c),
+        Ok(_) => Err(AppError::unauthorized("Invalid token.")),
+        Err(err) => Err(AppError::unauthorized(err)),
+    }
+}
+
+/// Accepts either a current TOTP code or an unused recovery code.
+async fn verify_second_factor(
+    db: DatabaseConnection,
+    totp: &AccountTotp,
+    code: &str,
+) -> AppResult<bool> {
+    let now = unix_timestamp();
+
+    if totp::is_totp_code(code) {
+        match totp::verify(&totp.secret, code, now, totp.last_used_step) {
+            Some(step) => service::mfa::use_totp_step(db, totp.account_id, step as i64).await,
+            None => Ok(false),
+        }
+    } else {
+        let code_hash = hash_token(&totp::normalize_recovery_code(code));
+        service::mfa::use_recovery_code(db, totp.account_id, code_hash, now as i64).await
+    }
+}
+
+/// Admin accounts can not log in with a password alone.
+pub(super) fn is_mfa_mandatory(roles: &[Role]) -> bool {
+    roles.iter().any(|r| r.role == ROLE_ADMIN)
+}
+
+/// Mails the account the token it starts its enrollment with at `/mfa/enroll`, so that the
+/// password alone never yields a secret.
+pub(super) async fn mail_totp_enrollment_token(
+    keys: &JwtKeys,
+    lifetimes: &TokenLifetimes,
+    mail_sender: &dyn MailSender,
+    account: &Account,
+) -> AppResult<()> {
+    let claims = Claims::for_totp_enrollment_token(
+        unix_timestamp() as usize,
+        lifetimes.mfa_pending,
+        account.email.clone(),
+    );
+    let token = keys
+        .encode(&claims)
+        .map_err(|err| AppError::Internal(err.into()))?;
+
+    let mail = Mail {
+        to: account.email.clone(),
+        subject: "Two-factor authentication".to_string(),
+        body: format!(
+            "Use the following token to set up two-factor authentication. It expires in {} minutes. If you did not just log in, change your password.\r\n\r\n{}",
+            lifetimes.mfa_pending / 60,
+            token,
+        ),
+    };
+    mail_sender.send(mail).await?;
+
+    Ok(())
+}
+
+/// Stores a new, unconfirmed secret for the account, replacing any previous unconfirmed one.
+async fn start_totp_enrollment(
+    db: DatabaseConnection,
+    account: &Account,
+) -> AppResult<TotpEnrollmentResponse> {
+    let secret = totp::generate_secret();
+
+    service::mfa::store_totp_secret(db, account.id, secret.clone(), unix_timestamp() as i64)
+        .await?;
+
+    Ok(TotpEnrollmentResponse {
+        otpauth_uri: totp::otpauth_uri(&account.email, &secret),
+        secret,
+    })
+}
+
+fn hash_recovery_codes(codes: &[String]) -> Vec<String> {
+    codes
+        .iter()
+        .map(|c| hash_token(&totp::normalize_recovery_code(c)))
+        .collect()
+}
+
+#[post("/mfa/totp/enroll")]
+pub async fn enroll_totp(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let account = fetch_authenticated_account(db.clone(), claims).await?;
+    let enrollment = start_totp_enrollment(db, &account).await?;
+
+    Ok(HttpResponse::Ok().json(enrollment))
+}
+
+#[post("/mfa/totp/confirm")]
+pub async fn confirm_totp(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+    request: web::Form<CodeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+
+    let account = fetch_authenticated_account(db.clone(), claims).await?;
+
+    let totp = match service::mfa::fetch_totp(db.clone(), account.id).await? {
+        Some(totp) if !totp.confirmed => totp,
+        _ => {
+            return Err(AppError::validation("No pending enrollment."));
+        }
+    };
+
+    let Some(step) = totp::verify(&totp.secret, &request.code, unix_timestamp(), None) else {
+        return Err(AppError::validation("Invalid code."));
+    };
+
+    let recovery_codes = totp::generate_recovery_codes();
+    service::mfa::confirm_totp(
+        db,
+        account.id,
+        step as i64,
+        hash_recovery_codes(&recovery_codes),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+}
+
+#[post("/mfa/totp/disable")]
+pub async fn disable_totp(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+    request: web::Form<CodeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+
+    let account = fetch_authenticated_account(db.clone(), claims).await?;
+    let totp = service::mfa::fetch_confirmed_totp(db.clone(), account.id).await?;
+
+    let roles = service::account::fetch_account_roles(db.clone(), account.id).await?;
+    if is_mfa_mandatory(&roles) {
+        return Err(AppError::forbidden(
+            "Two-factor authentication is required for Admin accounts.",
+        ));
+    }
+
+    if !verify_second_factor(db.clone(), &totp, &request.code).await? {
+        return Err(AppError::validation("Invalid code."));
+    }
+
+    service::mfa::delete_totp(db, account.id).await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+#[post("/mfa/recovery_codes")]
+pub async fn regenerate_recovery_codes(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+    request: web::Form<CodeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+
+    let account = fetch_authenticated_account(db.clone(), claims).await?;
+    let totp = service::mfa::fetch_confirmed_totp(db.clone(), account.id).await?;
+
+    if !verify_second_factor(db.clone(), &totp, &request.code).await? {
+        return Err(AppError::validation("Invalid code."));
+    }
+
+    let recovery_codes = totp::generate_recovery_codes();
+    service::mfa::replace_recovery_codes(db, account.id, hash_recovery_codes(&recovery_codes))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Starts the enrollment of an account that has to enroll before logging in. It takes the token
+/// mailed at login along with the `mfa_token`, the first code at `/mfa/verify` confirms it.
+#[post("/mfa/enroll")]
+pub async fn enroll_mfa(
+    db: web::Data<DatabaseConnection>,
+    keys: web::Data<JwtKeys>,
+    request: web::Form<EnrollMfaRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+
+    let claims = decode_login_token(&keys, &request.mfa_token, Claims::is_mfa_pending_token)?;
+    let enrollment_claims = decode_login_token(
+        &keys,
+        &request.enrollment_token,
+        Claims::is_totp_enrollment_token,
+    )?;
+    if enrollment_claims.sub != claims.sub {
+        return Err(AppError::unauthorized("Invalid token."));
+    }
+
+    let Some(account) = service::account::fetch_account_by_email(db.clone(), claims.sub).await?
+    else {
+        return Err(AppError::unauthorized("Invalid token."));
+    };
+
+    if claims.iat.min(enrollment_claims.iat) < account.password_set_ts as usize {
+        return Err(AppError::unauthorized("Invalid token."));
+    }
+
+    let enrollment = start_totp_enrollment(db, &account).await?;
+
+    Ok(HttpResponse::Ok().json(enrollment))
+}
+
+#[post("/mfa/verify")]
+pub async fn verify_mfa(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    keys: web::Data<JwtKeys>,
+    lifetimes: web::Data<TokenLifetimes>,
+    throttle: web::Data<LoginThrottle>,
+    metrics: web::Data<Metrics>,
+    request: web::Form<VerifyMfaRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+    let ip = req.peer_addr().map(|addr| addr.ip());
+
+    let claims = decode_login_token(&keys, &request.mfa_token, Claims::is_mfa_pending_token)?;
+
+    // Codes are guessable in far fewer attempts than passwords, so they share the login throttle.
+    if let Some(retry_after) = throttle.locked_for(&claims.sub, ip) {
+        metrics.record_failed_login(LoginFailure::Throttled);
+        return Ok(HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after))
+            .take()
+            .error_body("Too many failed login attempts. Try again later."));
+    }
+
+    let Some(account) = service::account::fetch_account_by_email(db.clone(), claims.sub).await?
+    else {
+        return Err(AppError::unauthorized("Invalid token."));
+    };
+
+    if claims.iat < account.password_set_ts as usize {
+        return Err(AppError::unauthorized("Invalid token."));
+    }
+
+    let Some(totp) = service::mfa::fetch_totp(db.clone(), account.id).await? else {
+        return Err(AppError::validation(
+            "Two-factor authentication is not enabled.",
+        ));
+    };
+
+    // An account that has to enroll started the enrollment at /mfa/enroll, the first code
+    // confirms it.
+    let verified = match totp.confirmed {
+        true => verify_second_factor(db.clone(), &totp, &request.code)
+            .await?
+            .then_some(None),
+        false => match totp::verify(&totp.secret, &request.code, unix_timestamp(), None) {
+            Some(step) => {
+                let recovery_codes = totp::generate_recovery_codes();
+                service::mfa::confirm_totp(
+                    db.clone(),
+                    account.id,
+                    step as i64,
+                    hash_recovery_codes(&recovery_codes),
+                )
+                .await?;
+                Some(Some(recovery_codes))
+            }
+            None => None,
+        },
+    };
+
+    let Some(recovery_codes) = verified else {
+        throttle.record_failure(&account.email, ip);
+        metrics.record_failed_login(LoginFailure::InvalidCode);
+        return Err(AppError::unauthorized("Invalid code."));
+    };
+    throttle.record_success(&account.email);
+
+    let roles = service::account::fetch_account_roles(db.clone(), account.id).await?;
+    let tokens = issue_tokens(db, &keys, &lifetimes, &account, roles, Uuid::new_v4()).await?;
+    metrics.record_tokens_issued(TokenGrant::Mfa);
+
+    Ok(HttpResponse::Ok().json(VerifyMfaResponse {
+        tokens,
+        recovery_codes,
+    }))
+}
@@ -2,26 +2,36 @@ use actix_web::{HttpRequest, HttpResponse, Responder, get};
 use serde::Deserialize;
 
 pub mod auth;
+pub mod health;
+pub mod metrics;
+pub mod mfa;
 pub mod v1;
+pub mod validation;
 
-#[derive(Deserialize)]
-struct Sort {
-    sort: i32,
-}
+const MAX_PAGE_COUNT: usize = 100;
 
-#[derive(Deserialize)]
-struct Pagination {
-    offset: usize,
-    count: usize,
-}
+const DEFAULT_PAGE_COUNT: usize = 20;
 
+/// Paging of the lists, ascending unless `sort` is negative. Every parameter is optional and
+/// `count` is capped at [`MAX_PAGE_COUNT`].
 #[derive(Deserialize)]
 struct SortPagination {
+    #[serde(default = "default_sort")]
     sort: i32,
+    #[serde(default)]
     offset: usize,
+    #[serde(default = "default_page_count")]
     count: usize,
 }
 
+fn default_sort() -> i32 {
+    1
+}
+
+fn default_page_count() -> usize {
+    DEFAULT_PAGE_COUNT
+}
+
 #[get("/whoami")]
 async fn whoami(req: HttpRequest) -> impl Responder {
     let info = format!(
@@ -1,28 +1,65 @@
-use actix_web::{HttpResponse, Responder, post, web};
-use serde::Deserialize;
+use actix_web::{HttpResponse, Responder, get, post, web};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+
+use validator::Validate;
 
 use crate::{
-    db::{DatabaseConnection, types::DB_Account},
-    password::hash_password,
+    api::{
+        MAX_PAGE_COUNT, SortPagination,
+        validation::{Valid, email, phone_number, trimmed, validate_e164, validate_password},
+    },
+    auth::{
+        PERMISSION_ACCOUNTS_READ, PERMISSION_ACCOUNTS_WRITE, PERMISSION_ROLES_WRITE,
+        extract::Authenticated, guard::RequireRole, throttle::LoginThrottle,
+    },
+    db::{
+        DatabaseConnection,
+        types::{DB_Account, ID},
+    },
+    error::AppError,
+    password::PasswordHasher,
     response::{HttpErrorBody, HttpJsonMessageBody},
-    service,
+    service::{
+        self, AccountCursor,
+        account::{AccountFilter, AccountOrder},
+    },
 };
 
-#[derive(Deserialize)]
+use super::me::fetch_authenticated_account;
+
+#[derive(Deserialize, Validate)]
 struct CreateAccountRequest {
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 100))]
     pub name: String,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 100))]
     pub lastname: String,
+    #[serde(deserialize_with = "phone_number")]
+    #[validate(custom(function = "validate_e164"))]
     pub phone_number: String,
+    #[serde(deserialize_with = "email")]
+    #[validate(email)]
     pub email: String,
+    #[validate(custom(function = "validate_password"))]
     pub password: String,
+    /// Unix timestamp the membership started at, the fees of its period on are charged.
+    /// Defaults to now.
+    pub member_since: Option<u64>,
 }
 
-#[post("/account")]
+#[post(
+    "/account",
+    wrap = "RequireRole::permission(PERMISSION_ACCOUNTS_WRITE)"
+)]
 pub async fn create_account(
     db: web::Data<DatabaseConnection>,
-    account: web::Json<CreateAccountRequest>,
-) -> impl Responder {
+    hasher: web::Data<PasswordHasher>,
+    account: Valid<web::Json<CreateAccountRequest>>,
+) -> Result<HttpResponse, AppError> {
     let db = (*db.into_inner()).clone();
+    let account = account.into_inner();
 
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -35,50 +72,237 @@ pub async fn create_account(
         lastname: account.lastname.clone(),
         email: account.email.clone(),
         phone_number: account.phone_number.clone(),
-        hashed_password: hash_password(&account.password),
+        hashed_password: hasher.hash_password(&account.password),
         password_set_ts: now as i64,
+        member_since: account.member_since.unwrap_or(now) as i64,
     };
 
-    match service::account::create_account(db, db_account).await {
-        Ok(true) => HttpResponse::Created().json_message_body("Success"),
-        Ok(false) => HttpResponse::InternalServerError()
-            .error_body("Account cannot be created because given phone number is already used."),
-        Err(err) => HttpResponse::InternalServerError().error_body(err),
-    }
+    service::account::create_account(db, db_account).await?;
+
+    Ok(HttpResponse::Created().json_message_body("Success"))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 struct AddRolesToAccountRequest {
     account_id: i32,
+    #[validate(length(min = 1, message = "At least one role is required."))]
     role_ids: Vec<i32>,
 }
 
-#[post("/account/roles")]
+#[post(
+    "/account/roles",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_WRITE)"
+)]
 pub async fn add_roles_to_account(
     db: web::Data<DatabaseConnection>,
-    request: web::Json<AddRolesToAccountRequest>,
-) -> impl Responder {
+    claims: Authenticated,
+    request: Valid<web::Json<AddRolesToAccountRequest>>,
+) -> Result<HttpResponse, AppError> {
     let db = (*db.into_inner()).clone();
-    let request = request.into_inner();
+    let request = request.into_inner().into_inner();
 
-    match service::account::add_roles_to_account(db, request.account_id, request.role_ids).await {
-        Ok(true) => HttpResponse::Created().json_message_body("Success"),
-        Ok(false) => HttpResponse::InternalServerError().error_body("Duplicate user role"),
-        Err(err) => HttpResponse::InternalServerError().error_body(err),
-    }
+    let caller = fetch_authenticated_account(db.clone(), claims).await?;
+    let granted_by = service::account::fetch_account_roles(db.clone(), caller.id).await?;
+
+    service::account::add_roles_to_account(db, &granted_by, request.account_id, request.role_ids)
+        .await?;
+
+    Ok(HttpResponse::Created().json_message_body("Success"))
 }
 
-#[post("/roles")]
+#[post("/roles", wrap = "RequireRole::permission(PERMISSION_ROLES_WRITE)")]
 pub async fn create_roles(
     db: web::Data<DatabaseConnection>,
     roles: web::Json<Vec<String>>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let db = (*db.into_inner()).clone();
-    let roles = roles.into_inner();
+    let roles: Vec<String> = roles
+        .into_inner()
+        .iter()
+        .map(|role| role.trim().to_string())
+        .collect();
 
-    match service::account::create_roles(db, roles).await {
-        Ok(true) => HttpResponse::Created().json_message_body("Success"),
-        Ok(false) => HttpResponse::InternalServerError().error_body("Duplicate role"),
-        Err(err) => HttpResponse::InternalServerError().error_body(err),
+    if roles.iter().any(String::is_empty) {
+        return Err(AppError::validation("Role can not be empty."));
     }
+
+    service::account::create_roles(db, roles).await?;
+
+    Ok(HttpResponse::Created().json_message_body("Success"))
+}
+
+#[derive(Deserialize, Validate)]
+struct UnlockAccountRequest {
+    #[serde(deserialize_with = "email")]
+    #[validate(email)]
+    email: String,
+}
+
+#[post(
+    "/account/unlock",
+    wrap = "RequireRole::permission(PERMISSION_ACCOUNTS_WRITE)"
+)]
+pub async fn unlock_account(
+    throttle: web::Data<LoginThrottle>,
+    request: Valid<web::Json<UnlockAccountRequest>>,
+) -> impl Responder {
+    let request = request.into_inner();
+
+    match throttle.unlock_account(&request.email) {
+        true => HttpResponse::Ok().json_message_body("Success"),
+        false => HttpResponse::NotFound().error_body("Account has no failed login attempts."),
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum AccountOrderBy {
+    #[default]
+    Id,
+    Name,
+    Lastname,
+    Email,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PaymentStatus {
+    InArrears,
+    PaidUp,
+}
+
+/// Filters of the account directory, paged by the [`SortPagination`] parameters of the same query.
+#[derive(Deserialize)]
+struct AccountDirectoryQuery {
+    /// Space separated terms searched in name, lastname, email and phone number.
+    q: Option<String>,
+    role: Option<String>,
+    payment_status: Option<PaymentStatus>,
+    #[serde(default)]
+    order_by: AccountOrderBy,
+    /// `next_cursor` of the previous page, takes the place of `offset`.
+    cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort_key: String,
+    id: ID,
+}
+
+impl Cursor {
+    fn encode(cursor: AccountCursor) -> String {
+        let cursor = Cursor {
+            sort_key: cursor.sort_key,
+            id: cursor.id,
+        };
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&cursor).unwrap())
+    }
+
+    fn decode(cursor: &str) -> Option<AccountCursor> {
+        let cursor: Cursor = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        Some(AccountCursor {
+            sort_key: cursor.sort_key,
+            id: cursor.id,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct AccountSummary {
+    id: ID,
+    name: String,
+    lastname: String,
+    email: String,
+    phone_number: String,
+    roles: Vec<String>,
+    in_arrears: bool,
+}
+
+impl From<service::AccountSummary> for AccountSummary {
+    fn from(account: service::AccountSummary) -> Self {
+        AccountSummary {
+            id: account.id,
+            name: account.name,
+            lastname: account.lastname,
+            email: account.email,
+            phone_number: account.phone_number,
+            roles: account.roles,
+            in_arrears: account.in_arrears,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AccountPage {
+    total: u64,
+    accounts: Vec<AccountSummary>,
+    next_cursor: Option<String>,
+}
+
+#[get(
+    "/accounts",
+    wrap = "RequireRole::permission(PERMISSION_ACCOUNTS_READ)"
+)]
+pub async fn get_accounts(
+    db: web::Data<DatabaseConnection>,
+    page: web::Query<SortPagination>,
+    query: web::Query<AccountDirectoryQuery>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let page = page.into_inner();
+    let query = query.into_inner();
+
+    let after = match query.cursor.as_deref().map(Cursor::decode) {
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => {
+            return Err(AppError::validation("Invalid cursor."));
+        }
+        None => None,
+    };
+    let offset = match after {
+        Some(_) => 0,
+        None => page.offset as i64,
+    };
+
+    let filter = AccountFilter {
+        search: query
+            .q
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        role: query.role,
+        in_arrears: query.payment_status.map(|s| match s {
+            PaymentStatus::InArrears => true,
+            PaymentStatus::PaidUp => false,
+        }),
+    };
+    let order = match query.order_by {
+        AccountOrderBy::Id => AccountOrder::Id,
+        AccountOrderBy::Name => AccountOrder::Name,
+        AccountOrderBy::Lastname => AccountOrder::Lastname,
+        AccountOrderBy::Email => AccountOrder::Email,
+    };
+
+    let accounts = service::account::fetch_accounts(
+        db,
+        filter,
+        order,
+        page.sort >= 0,
+        after,
+        offset,
+        page.count.min(MAX_PAGE_COUNT) as i64,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(AccountPage {
+        total: accounts.total,
+        accounts: accounts
+            .accounts
+            .into_iter()
+            .map(AccountSummary::from)
+            .collect(),
+        next_cursor: accounts.next.map(Cursor::encode),
+    }))
 }
@@ -0,0 +1,149 @@
+use actix_web::{HttpResponse, get, web};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{MAX_PAGE_COUNT, SortPagination},
+    auth::{PERMISSION_PAYMENTS_READ, guard::RequireRole},
+    db::{DatabaseConnection, types::ID},
+    error::AppError,
+    money::Money,
+    service::{self, balance::ArrearsOrder},
+};
+
+#[derive(Serialize)]
+struct PeriodStatement {
+    year: u32,
+    month: u32,
+    due: Money,
+    paid: Money,
+    precovered: Money,
+    outstanding: Money,
+    overdue: bool,
+}
+
+impl From<service::PeriodStatement> for PeriodStatement {
+    fn from(period: service::PeriodStatement) -> Self {
+        PeriodStatement {
+            year: period.year,
+            month: period.month,
+            due: period.due,
+            paid: period.paid,
+            precovered: period.precovered,
+            outstanding: period.outstanding,
+            overdue: period.overdue,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct Statement {
+    periods: Vec<PeriodStatement>,
+    balance: Vec<Money>,
+}
+
+impl From<service::Statement> for Statement {
+    fn from(statement: service::Statement) -> Self {
+        Statement {
+            periods: statement
+                .periods
+                .into_iter()
+                .map(PeriodStatement::from)
+                .collect(),
+            balance: statement.balance,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Period {
+    year: u32,
+    month: u32,
+}
+
+#[derive(Serialize)]
+struct Arrears {
+    account_id: ID,
+    name: String,
+    lastname: String,
+    email: String,
+    outstanding: Money,
+    months_overdue: u32,
+    since: Period,
+}
+
+impl From<service::Arrears> for Arrears {
+    fn from(arrears: service::Arrears) -> Self {
+        Arrears {
+            account_id: arrears.account_id,
+            name: arrears.name,
+            lastname: arrears.lastname,
+            email: arrears.email,
+            outstanding: arrears.outstanding,
+            months_overdue: arrears.months_overdue,
+            since: Period {
+                year: arrears.since_year,
+                month: arrears.since_month,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum ArrearsOrderBy {
+    #[default]
+    Amount,
+    Months,
+}
+
+/// Order of the arrears, paged by the [`SortPagination`] parameters of the same query.
+#[derive(Deserialize)]
+struct ArrearsQuery {
+    #[serde(default)]
+    order_by: ArrearsOrderBy,
+}
+
+#[get(
+    "/account/{account_id}/statement",
+    wrap = "RequireRole::permission(PERMISSION_PAYMENTS_READ)"
+)]
+pub async fn get_account_statement(
+    db: web::Data<DatabaseConnection>,
+    account_id: web::Path<ID>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let account_id = account_id.into_inner();
+
+    service::account::fetch_account_by_id(db.clone(), account_id).await?;
+    let statement = service::balance::fetch_account_statement(db, account_id).await?;
+
+    Ok(HttpResponse::Ok().json(Statement::from(statement)))
+}
+
+/// Members owing money, sorted by the outstanding amount or the number of overdue months.
+#[get("/arrears", wrap = "RequireRole::permission(PERMISSION_PAYMENTS_READ)")]
+pub async fn get_arrears(
+    db: web::Data<DatabaseConnection>,
+    page: web::Query<SortPagination>,
+    query: web::Query<ArrearsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let page = page.into_inner();
+    let query = query.into_inner();
+
+    let order = match query.order_by {
+        ArrearsOrderBy::Amount => ArrearsOrder::Amount,
+        ArrearsOrderBy::Months => ArrearsOrder::MonthsOverdue,
+    };
+
+    let arrears = service::balance::fetch_arrears(
+        db,
+        order,
+        page.sort >= 0,
+        page.offset as i64,
+        page.count.min(MAX_PAGE_COUNT) as i64,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(arrears.into_iter().map(Arrears::from).collect::<Vec<_>>()))
+}
@@ -1,30 +1,183 @@
-// use actix_web::{HttpResponse, Responder, get, web};
-// use serde::Serialize;
-
-// use crate::{
-//     api::SortPagination,
-//     db::memorydb::{DB_MonthlyFee, MemoryDB},
-// };
-
-// #[derive(Serialize)]
-// struct MonthlyFee {
-//     month: u32,
-//     year: u32,
-// }
-
-// #[get("/monthly_fees")]
-// async fn get_all_fees(
-//     db: web::Data<MemoryDB>,
-//     query: web::Query<SortPagination>,
-// ) -> impl Responder {
-//     let mut db = db.write().unwrap();
-
-//     // let fees = Vec::with_capacity(query.count);
-//     // if query.sort >= 0 {
-//     // } else {
-//     //     // DESC
-
-//     // }
-
-//     HttpResponse::Ok()
-// }
+use actix_web::{HttpResponse, delete, get, post, put, web};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{MAX_PAGE_COUNT, SortPagination},
+    auth::{PERMISSION_FEES_READ, PERMISSION_FEES_WRITE, guard::RequireRole},
+    db::{DatabaseConnection, types::ID},
+    error::{AppError, AppResult},
+    money::Money,
+    response::HttpJsonMessageBody,
+    service,
+};
+
+use super::payment::PrePaymentReport;
+
+#[derive(Serialize)]
+pub(super) struct MonthlyFee {
+    id: ID,
+    year: u32,
+    month: u32,
+    amount: Money,
+}
+
+impl From<service::MonthlyFee> for MonthlyFee {
+    fn from(fee: service::MonthlyFee) -> Self {
+        MonthlyFee {
+            id: fee.id,
+            year: fee.year,
+            month: fee.month,
+            amount: fee.amount,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CreatedMonthlyFee {
+    #[serde(flatten)]
+    fee: MonthlyFee,
+    pre_payments: PrePaymentReport,
+}
+
+#[derive(Serialize)]
+struct CreatedMonthlyFees {
+    fees: Vec<MonthlyFee>,
+    pre_payments: PrePaymentReport,
+}
+
+#[derive(Deserialize)]
+struct MonthlyFeeRequest {
+    year: i32,
+    month: i32,
+    amount: Money,
+}
+
+#[derive(Deserialize)]
+struct YearlyFeesRequest {
+    year: i32,
+    amount: Money,
+}
+
+pub(super) fn validate_period(year: i32, month: i32) -> AppResult<()> {
+    if year < 1 {
+        return Err(AppError::validation("Invalid year."));
+    }
+    if !(1..=12).contains(&month) {
+        return Err(AppError::validation("Month must be between 1 and 12."));
+    }
+    Ok(())
+}
+
+pub(super) fn validate_amount(amount: &Money) -> AppResult<()> {
+    if !amount.is_positive() {
+        return Err(AppError::validation("Amount must be positive."));
+    }
+    Ok(())
+}
+
+#[get(
+    "/monthly_fees",
+    wrap = "RequireRole::permission(PERMISSION_FEES_READ)"
+)]
+pub async fn get_all_fees(
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<SortPagination>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let query = query.into_inner();
+
+    let fees = service::fee::fetch_monthly_fees(
+        db,
+        query.sort >= 0,
+        query.offset as i64,
+        query.count.min(MAX_PAGE_COUNT) as i64,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(fees.into_iter().map(MonthlyFee::from).collect::<Vec<_>>()))
+}
+
+#[post(
+    "/monthly_fees",
+    wrap = "RequireRole::permission(PERMISSION_FEES_WRITE)"
+)]
+pub async fn create_fee(
+    db: web::Data<DatabaseConnection>,
+    request: web::Json<MonthlyFeeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+
+    validate_period(request.year, request.month)?;
+    validate_amount(&request.amount)?;
+
+    let (fee, report) =
+        service::fee::create_monthly_fee(db, request.year, request.month, request.amount).await?;
+
+    Ok(HttpResponse::Created().json(CreatedMonthlyFee {
+        fee: MonthlyFee::from(fee),
+        pre_payments: PrePaymentReport::from(report),
+    }))
+}
+
+#[post(
+    "/monthly_fees/year",
+    wrap = "RequireRole::permission(PERMISSION_FEES_WRITE)"
+)]
+pub async fn create_fees_for_year(
+    db: web::Data<DatabaseConnection>,
+    request: web::Json<YearlyFeesRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+
+    validate_period(request.year, 1)?;
+    validate_amount(&request.amount)?;
+
+    let (fees, report) =
+        service::fee::create_monthly_fees_for_year(db, request.year, request.amount).await?;
+
+    Ok(HttpResponse::Created().json(CreatedMonthlyFees {
+        fees: fees.into_iter().map(MonthlyFee::from).collect(),
+        pre_payments: PrePaymentReport::from(report),
+    }))
+}
+
+#[put(
+    "/monthly_fees/{fee_id}",
+    wrap = "RequireRole::permission(PERMISSION_FEES_WRITE)"
+)]
+pub async fn update_fee(
+    db: web::Data<DatabaseConnection>,
+    fee_id: web::Path<ID>,
+    request: web::Json<MonthlyFeeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let fee_id = fee_id.into_inner();
+    let request = request.into_inner();
+
+    validate_period(request.year, request.month)?;
+    validate_amount(&request.amount)?;
+
+    let fee =
+        service::fee::update_monthly_fee(db, fee_id, request.year, request.month, request.amount)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(MonthlyFee::from(fee)))
+}
+
+#[delete(
+    "/monthly_fees/{fee_id}",
+    wrap = "RequireRole::permission(PERMISSION_FEES_WRITE)"
+)]
+pub async fn delete_fee(
+    db: web::Data<DatabaseConnection>,
+    fee_id: web::Path<ID>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let fee_id = fee_id.into_inner();
+
+    service::fee::delete_monthly_fee(db, fee_id).await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
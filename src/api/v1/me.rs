@@ -1 +1,197 @@
+use actix_web::{HttpResponse, get, patch, web};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
 
+use crate::{
+    api::{
+        auth::{TokenResponse, issue_tokens},
+        validation::{Valid, email_option, phone_number_option, trimmed_option, validate_e164},
+    },
+    auth::{TokenLifetimes, extract::Authenticated, keys::JwtKeys},
+    db::{DatabaseConnection, types::ID},
+    error::{AppError, AppResult},
+    password::verify_password,
+    service::{self, Account},
+    util::unix_timestamp,
+};
+
+use super::{balance::Statement, payment::Payments};
+
+#[derive(Serialize)]
+struct Profile {
+    id: ID,
+    name: String,
+    lastname: String,
+    email: String,
+    phone_number: String,
+    roles: Vec<String>,
+}
+
+impl Profile {
+    fn new(account: Account, roles: Vec<service::Role>) -> Profile {
+        Profile {
+            id: account.id,
+            name: account.name,
+            lastname: account.lastname,
+            email: account.email,
+            phone_number: account.phone_number,
+            roles: roles.into_iter().map(|r| r.role).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+struct UpdateProfileRequest {
+    #[serde(default, deserialize_with = "trimmed_option")]
+    #[validate(length(min = 1, max = 100))]
+    name: Option<String>,
+    #[serde(default, deserialize_with = "trimmed_option")]
+    #[validate(length(min = 1, max = 100))]
+    lastname: Option<String>,
+    #[serde(default, deserialize_with = "phone_number_option")]
+    #[validate(custom(function = "validate_e164"))]
+    phone_number: Option<String>,
+    #[serde(default, deserialize_with = "email_option")]
+    #[validate(email)]
+    email: Option<String>,
+    /// Required to change the email, which is also the login name.
+    current_password: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UpdateProfileResponse {
+    profile: Profile,
+    /// New tokens, issued when the email changes since the old ones name the old email.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokens: Option<TokenResponse>,
+}
+
+pub(super) async fn fetch_authenticated_account(
+    db: DatabaseConnection,
+    claims: Authenticated,
+) -> AppResult<Account> {
+    service::account::fetch_account_by_email(db, claims.into_inner().sub)
+        .await?
+        .ok_or_else(|| AppError::not_found("Account not found."))
+}
+
+#[get("/me")]
+pub async fn get_profile(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let account = fetch_authenticated_account(db.clone(), claims).await?;
+    let roles = service::account::fetch_account_roles(db, account.id).await?;
+
+    Ok(HttpResponse::Ok().json(Profile::new(account, roles)))
+}
+
+/// Updates the given fields of the profile, leaving the others as they are.
+#[patch("/me")]
+pub async fn update_profile(
+    db: web::Data<DatabaseConnection>,
+    keys: web::Data<JwtKeys>,
+    lifetimes: web::Data<TokenLifetimes>,
+    claims: Authenticated,
+    request: Valid<web::Json<UpdateProfileRequest>>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner().into_inner();
+
+    let account = fetch_authenticated_account(db.clone(), claims).await?;
+
+    let email_changed = request.email.as_ref().is_some_and(|e| *e != account.email);
+    if email_changed {
+        match request.current_password {
+            Some(password) if verify_password(&password, &account.hashed_password) => {}
+            Some(_) => {
+                return Err(AppError::unauthorized("Current password is wrong."));
+            }
+            None => {
+                return Err(AppError::validation(
+                    "Current password is required to change the email.",
+                ));
+            }
+        }
+    }
+
+    let account = Account {
+        name: request.name.unwrap_or(account.name),
+        lastname: request.lastname.unwrap_or(account.lastname),
+        phone_number: request.phone_number.unwrap_or(account.phone_number),
+        email: request.email.unwrap_or(account.email),
+        ..account
+    };
+
+    service::account::update_account_profile(
+        db.clone(),
+        account.id,
+        account.name.clone(),
+        account.lastname.clone(),
+        account.phone_number.clone(),
+        account.email.clone(),
+    )
+    .await?;
+
+    let roles = service::account::fetch_account_roles(db.clone(), account.id).await?;
+
+    // sessions of the old email can no longer be refreshed, replace them with a new one
+    let tokens = match email_changed {
+        true => {
+            service::token::revoke_account_refresh_tokens(
+                db.clone(),
+                account.id,
+                unix_timestamp() as i64,
+            )
+            .await?;
+
+            Some(
+                issue_tokens(
+                    db,
+                    &keys,
+                    &lifetimes,
+                    &account,
+                    roles.clone(),
+                    Uuid::new_v4(),
+                )
+                .await?,
+            )
+        }
+        false => None,
+    };
+
+    Ok(HttpResponse::Ok().json(UpdateProfileResponse {
+        profile: Profile::new(account, roles),
+        tokens,
+    }))
+}
+
+#[get("/me/payments")]
+pub async fn get_my_payments(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let account = fetch_authenticated_account(db.clone(), claims).await?;
+    let payments = service::account::fetch_account_payments(db, account.id).await?;
+
+    Ok(HttpResponse::Ok().json(Payments::from(payments)))
+}
+
+/// Per-month statement and the current balance of the account.
+#[get("/me/balance")]
+pub async fn get_my_balance(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let account = fetch_authenticated_account(db.clone(), claims).await?;
+    let statement = service::balance::fetch_account_statement(db, account.id).await?;
+
+    Ok(HttpResponse::Ok().json(Statement::from(statement)))
+}
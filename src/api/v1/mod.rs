@@ -1,2 +1,6 @@
 pub mod account;
-pub mod fee;
\ No newline at end of file
+pub mod balance;
+pub mod fee;
+pub mod me;
+pub mod payment;
+pub mod role;
@@ -0,0 +1,286 @@
+use std::collections::HashSet;
+
+use actix_web::{HttpResponse, get, post, web};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::{
+        PERMISSION_PAYMENTS_READ, PERMISSION_PAYMENTS_REVERSE, PERMISSION_PAYMENTS_WRITE,
+        extract::Authenticated, guard::RequireRole,
+    },
+    db::{DatabaseConnection, types::ID},
+    error::{AppError, AppResult},
+    money::Money,
+    response::HttpJsonMessageBody,
+    service::{
+        self,
+        payment::{PaymentKind, PeriodPayment},
+    },
+};
+
+use super::{
+    fee::{validate_amount, validate_period},
+    me::fetch_authenticated_account,
+};
+
+#[derive(Deserialize)]
+struct Period {
+    year: i32,
+    month: i32,
+    /// Defaults to the whole outstanding fee of the period.
+    amount: Option<Money>,
+}
+
+#[derive(Deserialize)]
+struct PaymentRequest {
+    account_id: ID,
+    periods: Vec<Period>,
+}
+
+/// Names either a payment or a pre-payment, by the id listed in `made` or `precovered`.
+#[derive(Deserialize)]
+struct ReversePaymentRequest {
+    account_id: ID,
+    payment_id: Option<ID>,
+    pre_payment_id: Option<ID>,
+    /// Defaults to what is left of the payment.
+    amount: Option<Money>,
+}
+
+#[derive(Serialize)]
+struct Reversal {
+    id: ID,
+    amount: Money,
+    reversed_by: ID,
+    reversed_at: u64,
+}
+
+impl From<service::Reversal> for Reversal {
+    fn from(reversal: service::Reversal) -> Self {
+        Reversal {
+            id: reversal.id,
+            amount: reversal.amount,
+            reversed_by: reversal.reversed_by,
+            reversed_at: reversal.reversed_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct Payment {
+    id: ID,
+    year: u32,
+    month: u32,
+    amount: Money,
+    paid_at: u64,
+    reversals: Vec<Reversal>,
+}
+
+impl From<service::Payment> for Payment {
+    fn from(payment: service::Payment) -> Self {
+        Payment {
+            id: payment.id,
+            year: payment.year,
+            month: payment.month,
+            amount: payment.amount,
+            paid_at: payment.paid_at,
+            reversals: payment.reversals.into_iter().map(Reversal::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PrePayment {
+    #[serde(flatten)]
+    payment: Payment,
+    applied_payment_id: Option<ID>,
+    applied_at: Option<u64>,
+}
+
+impl From<service::PrePayment> for PrePayment {
+    fn from(pre_payment: service::PrePayment) -> Self {
+        PrePayment {
+            payment: Payment::from(pre_payment.payment),
+            applied_payment_id: pre_payment.applied_payment_id,
+            applied_at: pre_payment.applied_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct Payments {
+    made: Vec<Payment>,
+    precovered: Vec<PrePayment>,
+}
+
+impl From<service::Payments> for Payments {
+    fn from(payments: service::Payments) -> Self {
+        Payments {
+            made: payments.made.into_iter().map(Payment::from).collect(),
+            precovered: payments
+                .precovered
+                .into_iter()
+                .map(PrePayment::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AccountPayment {
+    account_id: ID,
+    #[serde(flatten)]
+    payment: Payment,
+}
+
+impl From<service::AccountPayment> for AccountPayment {
+    fn from(payment: service::AccountPayment) -> Self {
+        AccountPayment {
+            account_id: payment.account_id,
+            payment: Payment::from(payment.payment),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct PrePaymentReport {
+    applied: Vec<AccountPayment>,
+    orphaned: Vec<AccountPayment>,
+}
+
+impl From<service::PrePaymentReport> for PrePaymentReport {
+    fn from(report: service::PrePaymentReport) -> Self {
+        PrePaymentReport {
+            applied: report
+                .applied
+                .into_iter()
+                .map(AccountPayment::from)
+                .collect(),
+            orphaned: report
+                .orphaned
+                .into_iter()
+                .map(AccountPayment::from)
+                .collect(),
+        }
+    }
+}
+
+fn validate_periods(periods: &[Period]) -> AppResult<()> {
+    if periods.is_empty() {
+        return Err(AppError::validation("At least one period is required."));
+    }
+
+    let mut unique = HashSet::new();
+    for period in periods {
+        validate_period(period.year, period.month)?;
+        if let Some(amount) = &period.amount {
+            validate_amount(amount)?;
+        }
+        if !unique.insert((period.year, period.month)) {
+            return Err(AppError::validation("Duplicate period."));
+        }
+    }
+
+    Ok(())
+}
+
+#[post(
+    "/payments",
+    wrap = "RequireRole::permission(PERMISSION_PAYMENTS_WRITE)"
+)]
+pub async fn record_payment(
+    db: web::Data<DatabaseConnection>,
+    request: web::Json<PaymentRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+
+    validate_periods(&request.periods)?;
+    service::account::fetch_account_by_id(db.clone(), request.account_id).await?;
+
+    let periods = request
+        .periods
+        .into_iter()
+        .map(|p| PeriodPayment {
+            year: p.year,
+            month: p.month,
+            amount: p.amount,
+        })
+        .collect();
+
+    let payments = service::payment::record_payments(db, request.account_id, periods).await?;
+
+    Ok(HttpResponse::Created().json(Payments::from(payments)))
+}
+
+#[post(
+    "/payments/reverse",
+    wrap = "RequireRole::permission(PERMISSION_PAYMENTS_REVERSE)"
+)]
+pub async fn reverse_payment(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+    request: web::Json<ReversePaymentRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let request = request.into_inner();
+
+    let (kind, payment_id) = match (request.payment_id, request.pre_payment_id) {
+        (Some(id), None) => (PaymentKind::Payment, id),
+        (None, Some(id)) => (PaymentKind::PrePayment, id),
+        _ => {
+            return Err(AppError::validation(
+                "Either payment_id or pre_payment_id is required.",
+            ));
+        }
+    };
+    if let Some(amount) = &request.amount {
+        validate_amount(amount)?;
+    }
+    service::account::fetch_account_by_id(db.clone(), request.account_id).await?;
+    let reversed_by = fetch_authenticated_account(db.clone(), claims).await?;
+
+    service::payment::reverse_payment(
+        db,
+        request.account_id,
+        kind,
+        payment_id,
+        request.amount,
+        reversed_by.id,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+#[get(
+    "/account/{account_id}/payments",
+    wrap = "RequireRole::permission(PERMISSION_PAYMENTS_READ)"
+)]
+pub async fn get_account_payments(
+    db: web::Data<DatabaseConnection>,
+    account_id: web::Path<ID>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let account_id = account_id.into_inner();
+
+    service::account::fetch_account_by_id(db.clone(), account_id).await?;
+    let payments = service::account::fetch_account_payments(db, account_id).await?;
+
+    Ok(HttpResponse::Ok().json(Payments::from(payments)))
+}
+
+/// Applies the pre-payments left behind for periods that already have a fee.
+#[post(
+    "/pre_payments/apply",
+    wrap = "RequireRole::permission(PERMISSION_PAYMENTS_WRITE)"
+)]
+pub async fn apply_pre_payments(
+    db: web::Data<DatabaseConnection>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let report = service::payment::apply_pre_payments(db).await?;
+
+    Ok(HttpResponse::Ok().json(PrePaymentReport::from(report)))
+}
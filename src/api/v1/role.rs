@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+
+use actix_web::{HttpResponse, delete, get, put, web};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::{
+        PERMISSION_ROLES_READ, PERMISSION_ROLES_WRITE, extract::Authenticated, guard::RequireRole,
+    },
+    db::{DatabaseConnection, types::ID},
+    error::AppError,
+    response::HttpJsonMessageBody,
+    service,
+};
+
+use super::me::fetch_authenticated_account;
+
+#[derive(Serialize)]
+struct Role {
+    id: ID,
+    role: String,
+}
+
+impl From<service::Role> for Role {
+    fn from(role: service::Role) -> Self {
+        Role {
+            id: role.id,
+            role: role.role,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RenameRoleRequest {
+    role: String,
+}
+
+#[derive(Deserialize)]
+struct ReplaceRolePermissionsRequest {
+    permissions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ReplaceAccountRolesRequest {
+    role_ids: Vec<ID>,
+}
+
+#[get("/roles", wrap = "RequireRole::permission(PERMISSION_ROLES_READ)")]
+pub async fn get_roles(db: web::Data<DatabaseConnection>) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let roles = service::role::fetch_roles(db).await?;
+
+    Ok(HttpResponse::Ok().json(roles.into_iter().map(Role::from).collect::<Vec<_>>()))
+}
+
+#[put(
+    "/roles/{role_id}",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_WRITE)"
+)]
+pub async fn rename_role(
+    db: web::Data<DatabaseConnection>,
+    role_id: web::Path<ID>,
+    request: web::Json<RenameRoleRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let role = request.into_inner().role.trim().to_string();
+
+    if role.is_empty() {
+        return Err(AppError::validation("Role can not be empty."));
+    }
+
+    service::role::rename_role(db, role_id.into_inner(), role).await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+#[delete(
+    "/roles/{role_id}",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_WRITE)"
+)]
+pub async fn delete_role(
+    db: web::Data<DatabaseConnection>,
+    role_id: web::Path<ID>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    service::role::delete_role(db, role_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+#[get(
+    "/permissions",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_READ)"
+)]
+pub async fn get_permissions(db: web::Data<DatabaseConnection>) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let permissions = service::role::fetch_permissions(db).await?;
+
+    Ok(HttpResponse::Ok().json(permissions))
+}
+
+#[get(
+    "/roles/{role_id}/permissions",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_READ)"
+)]
+pub async fn get_role_permissions(
+    db: web::Data<DatabaseConnection>,
+    role_id: web::Path<ID>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let role = service::role::fetch_role(db.clone(), role_id.into_inner()).await?;
+    let permissions = service::role::fetch_permissions_of_roles(db, &[role]).await?;
+
+    Ok(HttpResponse::Ok().json(permissions))
+}
+
+/// Grants exactly the given permissions to the role. Takes effect as access tokens are renewed.
+/// Only permissions of the caller can be added.
+#[put(
+    "/roles/{role_id}/permissions",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_WRITE)"
+)]
+pub async fn replace_role_permissions(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+    role_id: web::Path<ID>,
+    request: web::Json<ReplaceRolePermissionsRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+
+    let permissions: Vec<String> = request
+        .into_inner()
+        .permissions
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let caller = fetch_authenticated_account(db.clone(), claims).await?;
+    let granted_by = service::account::fetch_account_roles(db.clone(), caller.id).await?;
+
+    service::role::replace_role_permissions(db, &granted_by, role_id.into_inner(), permissions)
+        .await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+#[get(
+    "/account/{account_id}/roles",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_READ)"
+)]
+pub async fn get_account_roles(
+    db: web::Data<DatabaseConnection>,
+    account_id: web::Path<ID>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let account_id = account_id.into_inner();
+
+    service::account::fetch_account_by_id(db.clone(), account_id).await?;
+    let roles = service::account::fetch_account_roles(db, account_id).await?;
+
+    Ok(HttpResponse::Ok().json(roles.into_iter().map(Role::from).collect::<Vec<_>>()))
+}
+
+/// Sets exactly the given roles on the account, all at once. Only roles within the permissions
+/// of the caller can be granted.
+#[put(
+    "/account/{account_id}/roles",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_WRITE)"
+)]
+pub async fn replace_account_roles(
+    db: web::Data<DatabaseConnection>,
+    claims: Authenticated,
+    account_id: web::Path<ID>,
+    request: web::Json<ReplaceAccountRolesRequest>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let account_id = account_id.into_inner();
+
+    service::account::fetch_account_by_id(db.clone(), account_id).await?;
+
+    let role_ids: Vec<ID> = request
+        .into_inner()
+        .role_ids
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let caller = fetch_authenticated_account(db.clone(), claims).await?;
+    let granted_by = service::account::fetch_account_roles(db.clone(), caller.id).await?;
+
+    service::role::replace_account_roles(db, &granted_by, account_id, role_ids).await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
+
+#[delete(
+    "/account/{account_id}/roles/{role_id}",
+    wrap = "RequireRole::permission(PERMISSION_ROLES_WRITE)"
+)]
+pub async fn revoke_account_role(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(ID, ID)>,
+) -> Result<HttpResponse, AppError> {
+    let db = (*db.into_inner()).clone();
+    let (account_id, role_id) = path.into_inner();
+
+    service::account::fetch_account_by_id(db.clone(), account_id).await?;
+    service::role::revoke_role_from_account(db, account_id, role_id).await?;
+
+    Ok(HttpResponse::Ok().json_message_body("Success"))
+}
@@ -0,0 +1,154 @@
+use std::{future::Future, ops::Deref, pin::Pin};
+
+use actix_web::{FromRequest, HttpRequest, dev::Payload};
+use serde::{Deserialize, Deserializer};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::{error::AppError, response::FieldError};
+
+pub const PASSWORD_MIN_LEN: usize = 10;
+pub const PASSWORD_MAX_LEN: usize = 72;
+
+/// Extractor running the [`Validate`] rules of the request extracted by `E`, such as
+/// `Valid<web::Json<T>>`. Requests breaking them are rejected with the list of invalid fields.
+pub struct Valid<E>(pub E);
+
+impl<E> Valid<E> {
+    pub fn into_inner(self) -> E {
+        self.0
+    }
+}
+
+impl<E, T> FromRequest for Valid<E>
+where
+    E: FromRequest + Deref<Target = T> + 'static,
+    T: Validate,
+{
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let extracted = E::from_request(req, payload);
+
+        Box::pin(async move {
+            let extracted = extracted.await.map_err(Into::into)?;
+            match extracted.validate() {
+                Ok(()) => Ok(Valid(extracted)),
+                Err(errors) => Err(AppError::InvalidFields(field_errors(errors)).into()),
+            }
+        })
+    }
+}
+
+fn field_errors(errors: ValidationErrors) -> Vec<FieldError> {
+    let mut fields: Vec<FieldError> = errors
+        .field_errors()
+        .into_iter()
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |err| FieldError {
+                field: field.to_string(),
+                code: err.code.to_string(),
+                message: err
+                    .message
+                    .as_deref()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| default_message(err)),
+            })
+        })
+        .collect();
+    fields.sort_by(|a, b| a.field.cmp(&b.field));
+
+    fields
+}
+
+fn default_message(err: &ValidationError) -> String {
+    match (&*err.code, err.params.get("min"), err.params.get("max")) {
+        ("email", _, _) => "Invalid email.".to_string(),
+        ("length", Some(min), Some(max)) => format!("Must be {min} to {max} characters long."),
+        ("length", Some(min), None) => format!("Must be at least {min} characters long."),
+        (code, _, _) => format!("Invalid value ({code})."),
+    }
+}
+
+/// Deserializes a string with the surrounding whitespace removed.
+pub fn trimmed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(String::deserialize(deserializer)?.trim().to_string())
+}
+
+pub fn trimmed_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.map(|s| s.trim().to_string()))
+}
+
+/// Emails are stored and looked up trimmed and in lowercase.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+pub fn email<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(normalize_email(&String::deserialize(deserializer)?))
+}
+
+pub fn email_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.map(|e| normalize_email(&e)))
+}
+
+/// Strips the separators people write phone numbers with, and turns the `00` international
+/// prefix into `+`. The result is checked by [`validate_e164`].
+pub fn normalize_phone_number(phone_number: &str) -> String {
+    let phone_number: String = phone_number
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+
+    match phone_number.strip_prefix("00") {
+        Some(number) => format!("+{number}"),
+        None => phone_number,
+    }
+}
+
+pub fn phone_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(normalize_phone_number(&String::deserialize(deserializer)?))
+}
+
+pub fn phone_number_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.map(|p| normalize_phone_number(&p)))
+}
+
+/// `+` and the country code followed by the subscriber number, 15 digits at most.
+pub fn validate_e164(phone_number: &str) -> Result<(), ValidationError> {
+    let valid = phone_number.strip_prefix('+').is_some_and(|digits| {
+        (8..=15).contains(&digits.len())
+            && !digits.starts_with('0')
+            && digits.bytes().all(|b| b.is_ascii_digit())
+    });
+
+    match valid {
+        true => Ok(()),
+        false => Err(ValidationError::new("e164").with_message(
+            "Phone number must be in the international format, e.g. +905551234567.".into(),
+        )),
+    }
+}
+
+/// At least `PASSWORD_MIN_LEN` characters with both letters and digits. Longer than
+/// `PASSWORD_MAX_LEN` bytes is refused, bcrypt would ignore the rest.
+pub fn validate_password(password: &str) -> Result<(), ValidationError> {
+    if password.chars().count() < PASSWORD_MIN_LEN || password.len() > PASSWORD_MAX_LEN {
+        return Err(ValidationError::new("password_length").with_message(
+            format!("Password must be {PASSWORD_MIN_LEN} to {PASSWORD_MAX_LEN} characters long.")
+                .into(),
+        ));
+    }
+    if !password.chars().any(char::is_alphabetic) || !password.chars().any(|c| c.is_ascii_digit()) {
+        return Err(ValidationError::new("password_strength")
+            .with_message("Password must contain both letters and digits.".into()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use actix_web::{
+    App, Error, HttpResponse,
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    web,
+};
+
+use crate::{
+    api,
+    auth::{TokenLifetimes, guard::RequireRole, keys::JwtKeys, throttle::LoginThrottle},
+    config::CorsConfig,
+    correlation::CorrelationId,
+    db::DatabaseConnection,
+    error::reject_malformed_request,
+    logging::RequestTracing,
+    mail::MailSender,
+    metrics::{Metrics, RequestMetrics},
+    password::PasswordHasher,
+    response::HttpErrorBody,
+};
+
+/// Everything the handlers share, cloned into the `App` of every worker.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: web::Data<DatabaseConnection>,
+    pub jwt_keys: web::Data<JwtKeys>,
+    pub token_lifetimes: web::Data<TokenLifetimes>,
+    pub password_hasher: web::Data<PasswordHasher>,
+    pub mail_sender: web::Data<dyn MailSender>,
+    pub login_throttle: web::Data<LoginThrottle>,
+    pub metrics: web::Data<Metrics>,
+    pub cors: CorsConfig,
+}
+
+/// The whole HTTP API, as served by `agem-server` and exercised by the integration tests.
+pub fn app(
+    state: AppState,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        // -- cors --
+        .wrap(state.cors.middleware())
+        // -- request logging --
+        .wrap(RequestTracing)
+        // -- correlation id --
+        .wrap(CorrelationId)
+        // -- metrics --
+        .wrap(RequestMetrics)
+        // -- malformed requests --
+        .app_data(web::JsonConfig::default().error_handler(reject_malformed_request))
+        .app_data(web::QueryConfig::default().error_handler(reject_malformed_request))
+        .app_data(web::FormConfig::default().error_handler(reject_malformed_request))
+        .app_data(web::PathConfig::default().error_handler(reject_malformed_request))
+        // -- db --
+        .app_data(state.db)
+        // -- keys --
+        .app_data(state.jwt_keys)
+        .app_data(state.token_lifetimes)
+        // -- passwords --
+        .app_data(state.password_hasher)
+        // -- mail --
+        .app_data(state.mail_sender)
+        // -- login throttle --
+        .app_data(state.login_throttle)
+        // -- metrics --
+        .app_data(state.metrics)
+        // -- health --
+        .service(api::health::healthz)
+        .service(api::health::readyz)
+        .service(api::health::version)
+        .service(api::metrics::get_metrics)
+        // -- well-known --
+        .service(web::scope("/.well-known").service(api::auth::get_jwks))
+        // -- auth --
+        .service(
+            web::scope("/auth")
+                .service(api::auth::get_me_from_access_token)
+                .service(api::auth::create_access_token)
+                .service(api::auth::refresh_access_token)
+                .service(api::auth::revoke_refresh_token)
+                .service(api::auth::revoke_all_refresh_tokens)
+                .service(api::auth::change_password)
+                .service(api::auth::request_password_reset)
+                .service(api::auth::reset_password)
+                .service(api::mfa::enroll_totp)
+                .service(api::mfa::confirm_totp)
+                .service(api::mfa::disable_totp)
+                .service(api::mfa::regenerate_recovery_codes)
+                .service(api::mfa::enroll_mfa)
+                .service(api::mfa::verify_mfa),
+        )
+        // -- api --
+        .service(
+            web::scope("/api")
+                .wrap(RequireRole::authenticated())
+                .service(api::whoami)
+                // -- v1 --
+                .service(
+                    web::scope("/v1")
+                        // -- -- account --
+                        .service(api::v1::account::create_account)
+                        .service(api::v1::account::create_roles)
+                        .service(api::v1::account::add_roles_to_account)
+                        .service(api::v1::account::unlock_account)
+                        .service(api::v1::account::get_accounts)
+                        // -- -- role --
+                        .service(api::v1::role::get_roles)
+                        .service(api::v1::role::rename_role)
+                        .service(api::v1::role::delete_role)
+                        .service(api::v1::role::get_permissions)
+                        .service(api::v1::role::get_role_permissions)
+                        .service(api::v1::role::replace_role_permissions)
+                        .service(api::v1::role::get_account_roles)
+                        .service(api::v1::role::replace_account_roles)
+                        .service(api::v1::role::revoke_account_role)
+                        // -- -- me --
+                        .service(api::v1::me::get_profile)
+                        .service(api::v1::me::update_profile)
+                        .service(api::v1::me::get_my_payments)
+                        .service(api::v1::me::get_my_balance)
+                        // -- -- fee --
+                        .service(api::v1::fee::get_all_fees)
+                        .service(api::v1::fee::create_fee)
+                        .service(api::v1::fee::create_fees_for_year)
+                        .service(api::v1::fee::update_fee)
+                        .service(api::v1::fee::delete_fee)
+                        // -- -- payment --
+                        .service(api::v1::payment::record_payment)
+                        .service(api::v1::payment::reverse_payment)
+                        .service(api::v1::payment::get_account_payments)
+                        .service(api::v1::payment::apply_pre_payments)
+                        // -- -- balance --
+                        .service(api::v1::balance::get_account_statement)
+                        .service(api::v1::balance::get_arrears),
+                ),
+        )
+        .default_service(web::to(|| async {
+            HttpResponse::NotFound().error_body("Resource not found.")
+        }))
+}
@@ -0,0 +1,57 @@
+use std::future::{Ready, ready};
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload, http::header::Header, web};
+
+use crate::util::BearerToken;
+
+use super::{AuthError, Claims, keys::JwtKeys};
+
+/// Claims of a validated access token presented as `Authorization: Bearer <token>`.
+///
+/// Refresh tokens are rejected. The claims are cached in the request extensions,
+/// so guards and handlers of the same request decode the token only once.
+pub struct Authenticated(pub Claims);
+
+impl Authenticated {
+    pub fn into_inner(self) -> Claims {
+        self.0
+    }
+}
+
+impl std::ops::Deref for Authenticated {
+    type Target = Claims;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for Authenticated {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authenticate(req).map(Authenticated))
+    }
+}
+
+pub fn authenticate(req: &HttpRequest) -> Result<Claims, AuthError> {
+    if let Some(claims) = req.extensions().get::<Claims>() {
+        return Ok(claims.clone());
+    }
+
+    let bearer_token = BearerToken::parse(req).map_err(|_| AuthError::MissingToken)?;
+
+    let keys = req
+        .app_data::<web::Data<JwtKeys>>()
+        .ok_or(AuthError::KeysNotConfigured)?;
+
+    let claims: Claims = keys.decode(&bearer_token.token)?;
+    if !claims.is_access_token() {
+        return Err(AuthError::NotAnAccessToken);
+    }
+
+    req.extensions_mut().insert(claims.clone());
+
+    Ok(claims)
+}
@@ -0,0 +1,139 @@
+use std::rc::Rc;
+
+use actix_web::{
+    Error,
+    body::EitherBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::{
+    TryFutureExt,
+    future::{Either, MapOk, Ready, ready},
+};
+
+use super::{AuthError, Claims, ROLE_ADMIN, extract::authenticate};
+
+#[derive(Debug, Clone)]
+pub enum RoleRequirement {
+    /// Any valid access token.
+    Authenticated,
+    /// Access token holding exactly this role.
+    Role(String),
+    /// Access token holding a role starting with this prefix.
+    RolePrefix(String),
+    /// Access token holding at least one of these roles.
+    AnyRole(Vec<String>),
+    /// Access token granting this permission through its roles.
+    Permission(String),
+}
+
+impl RoleRequirement {
+    pub fn is_satisfied_by(&self, claims: &Claims) -> bool {
+        match self {
+            RoleRequirement::Authenticated => true,
+            RoleRequirement::Role(role) => claims.has_role(role),
+            RoleRequirement::RolePrefix(prefix) => claims.has_role_starts_with(prefix),
+            RoleRequirement::AnyRole(roles) => roles.iter().any(|r| claims.has_role(r)),
+            RoleRequirement::Permission(permission) => claims.has_permission(permission),
+        }
+    }
+}
+
+/// Middleware rejecting requests whose access token does not satisfy a [`RoleRequirement`].
+///
+/// Can be applied to a scope with `.wrap(RequireRole::authenticated())`
+/// or to a single handler with
+/// `#[post("/path", wrap = "RequireRole::permission(PERMISSION_FEES_WRITE)")]`.
+#[derive(Clone)]
+pub struct RequireRole {
+    requirement: Rc<RoleRequirement>,
+}
+
+impl RequireRole {
+    pub fn new(requirement: RoleRequirement) -> RequireRole {
+        RequireRole {
+            requirement: Rc::new(requirement),
+        }
+    }
+
+    pub fn authenticated() -> RequireRole {
+        RequireRole::new(RoleRequirement::Authenticated)
+    }
+
+    pub fn role(role: impl Into<String>) -> RequireRole {
+        RequireRole::new(RoleRequirement::Role(role.into()))
+    }
+
+    pub fn role_prefix(prefix: impl Into<String>) -> RequireRole {
+        RequireRole::new(RoleRequirement::RolePrefix(prefix.into()))
+    }
+
+    pub fn any_role<R: Into<String>>(roles: impl IntoIterator<Item = R>) -> RequireRole {
+        RequireRole::new(RoleRequirement::AnyRole(
+            roles.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    pub fn permission(permission: impl Into<String>) -> RequireRole {
+        RequireRole::new(RoleRequirement::Permission(permission.into()))
+    }
+
+    pub fn admin() -> RequireRole {
+        RequireRole::role(ROLE_ADMIN)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireRoleMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireRoleMiddleware {
+            service,
+            requirement: self.requirement.clone(),
+        }))
+    }
+}
+
+pub struct RequireRoleMiddleware<S> {
+    service: S,
+    requirement: Rc<RoleRequirement>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireRoleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Either<
+        Ready<Result<Self::Response, Self::Error>>,
+        MapOk<S::Future, fn(ServiceResponse<B>) -> Self::Response>,
+    >;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let authorized = authenticate(req.request()).and_then(|claims| {
+            match self.requirement.is_satisfied_by(&claims) {
+                true => Ok(()),
+                false => Err(AuthError::Forbidden),
+            }
+        });
+
+        // responded here rather than failed, so the response is built in the request's context
+        match authorized {
+            Ok(()) => Either::Right(
+                self.service
+                    .call(req)
+                    .map_ok(ServiceResponse::map_into_left_body as fn(_) -> _),
+            ),
+            Err(err) => Either::Left(ready(Ok(req.error_response(err).map_into_right_body()))),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+
+pub mod extract;
+pub mod guard;
+pub mod keys;
+pub mod throttle;
+pub mod totp;
+
+pub const TOKEN_TYPE_ACCESS: &str = "access";
+pub const TOKEN_TYPE_REFRESH: &str = "refresh";
+pub const TOKEN_TYPE_MFA_PENDING: &str = "mfa_pending";
+pub const TOKEN_TYPE_TOTP_ENROLLMENT: &str = "totp_enrollment";
+// Default token lifetimes, see `TokenLifetimes`
+pub const ACCESS_TOKEN_EXPIRES_IN: usize = 60 * 20; // 20 minutes
+pub const REFRESH_TOKEN_EXPIRES_IN: usize = 60 * 60 * 24 * 7; // 1 week
+pub const MFA_PENDING_TOKEN_EXPIRES_IN: usize = 60 * 5; // 5 minutes
+pub const PASSWORD_RESET_TOKEN_EXPIRES_IN: usize = 60 * 30; // 30 minutes
+
+pub const ROLE_ADMIN: &str = "Admin";
+pub const ROLE_TREASURER: &str = "Treasurer";
+
+pub const PERMISSION_ACCOUNTS_READ: &str = "accounts.read";
+pub const PERMISSION_ACCOUNTS_WRITE: &str = "accounts.write";
+pub const PERMISSION_ROLES_READ: &str = "roles.read";
+pub const PERMISSION_ROLES_WRITE: &str = "roles.write";
+pub const PERMISSION_FEES_READ: &str = "fees.read";
+pub const PERMISSION_FEES_WRITE: &str = "fees.write";
+pub const PERMISSION_PAYMENTS_READ: &str = "payments.read";
+pub const PERMISSION_PAYMENTS_WRITE: &str = "payments.write";
+pub const PERMISSION_PAYMENTS_REVERSE: &str = "payments.reverse";
+
+/// Lifetimes of the issued tokens in seconds, configurable.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenLifetimes {
+    pub access: usize,
+    pub refresh: usize,
+    pub mfa_pending: usize,
+    pub password_reset: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Claims {
+    // aud: String,         // Optional. Audience
+    pub exp: usize,             // Required (validate_exp defaults to true in validation). Expiration time (as UTC timestamp)
+    pub iat: usize,             // Optional. Issued at (as UTC timestamp)
+    // iss: String,         // Optional. Issuer
+    // nbf: usize,          // Optional. Not Before (as UTC timestamp)
+    pub sub: String,            // Optional. Subject (whom token refers to)
+    // -- custom --
+    pub token_type: String,
+    pub role: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permissions: Vec<String>,// Access tokens only. Resolved from the roles at issue time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,    // Refresh tokens only. Id of the server side refresh token record
+}
+
+impl Claims {
+    pub fn for_access_token(
+        now: usize,
+        expires_in: usize,
+        sub: String,
+        role: Vec<String>,
+        permissions: Vec<String>,
+    ) -> Claims {
+        Claims {
+            exp: now + expires_in,
+            iat: now,
+            sub,
+            token_type: TOKEN_TYPE_ACCESS.to_string(),
+            role,
+            permissions,
+            jti: None,
+        }
+    }
+
+    pub fn for_refresh_token(now: usize, expires_in: usize, sub: String, jti: String) -> Claims {
+        Claims {
+            exp: now + expires_in,
+            iat: now,
+            sub,
+            token_type: TOKEN_TYPE_REFRESH.to_string(),
+            role: Vec::new(),
+            permissions: Vec::new(),
+            jti: Some(jti),
+        }
+    }
+
+    /// Proof of a verified password, exchanged for real tokens after the second factor.
+    pub fn for_mfa_pending_token(now: usize, expires_in: usize, sub: String) -> Claims {
+        Claims {
+            exp: now + expires_in,
+            iat: now,
+            sub,
+            token_type: TOKEN_TYPE_MFA_PENDING.to_string(),
+            role: Vec::new(),
+            permissions: Vec::new(),
+            jti: None,
+        }
+    }
+
+    /// Proof of access to the mailbox of an account that has to enroll before logging in,
+    /// mailed at login and valid as long as the `mfa_pending` token it goes with.
+    pub fn for_totp_enrollment_token(now: usize, expires_in: usize, sub: String) -> Claims {
+        Claims {
+            exp: now + expires_in,
+            iat: now,
+            sub,
+            token_type: TOKEN_TYPE_TOTP_ENROLLMENT.to_string(),
+            role: Vec::new(),
+            permissions: Vec::new(),
+            jti: None,
+        }
+    }
+
+    pub fn is_access_token(&self) -> bool {
+        self.token_type == TOKEN_TYPE_ACCESS
+    }
+
+    pub fn is_refresh_token(&self) -> bool {
+        self.token_type == TOKEN_TYPE_REFRESH
+    }
+
+    pub fn is_mfa_pending_token(&self) -> bool {
+        self.token_type == TOKEN_TYPE_MFA_PENDING
+    }
+
+    pub fn is_totp_enrollment_token(&self) -> bool {
+        self.token_type == TOKEN_TYPE_TOTP_ENROLLMENT
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.role.iter().any(|r| r.eq(role))
+    }
+
+    pub fn has_role_starts_with(&self, role: &str) -> bool {
+        self.role.iter().any(|r| r.starts_with(role))
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.has_role(ROLE_ADMIN)
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p.eq(permission))
+    }
+}
+
+/// Random opaque token with 256 bits of entropy, for single-use tokens such as password resets.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hex encoded SHA-256 of a token, the form in which issued tokens are persisted.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("Missing bearer token.")]
+    MissingToken,
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("Token is not an access token.")]
+    NotAnAccessToken,
+    #[error("Insufficient role or permission.")]
+    Forbidden,
+    #[error("Token keys are not configured.")]
+    KeysNotConfigured,
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::Forbidden => StatusCode::FORBIDDEN,
+            AuthError::KeysNotConfigured => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        AppError::from(self).error_response()
+    }
+}
+
+impl From<&AuthError> for AppError {
+    fn from(err: &AuthError) -> Self {
+        match err {
+            AuthError::Forbidden => AppError::Forbidden(err.to_string()),
+            AuthError::KeysNotConfigured => AppError::Internal(anyhow::anyhow!(err.to_string())),
+            _ => AppError::Unauthorized(err.to_string()),
+        }
+    }
+}
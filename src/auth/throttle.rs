@@ -0,0 +1,145 @@
+use std::{collections::HashMap, hash::Hash, net::IpAddr, sync::Mutex};
+
+use crate::util::unix_timestamp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottlePolicy {
+    /// Failed attempts allowed before the first lockout.
+    pub free_attempts: u32,
+    /// Length of the first lockout, doubled on every further failure.
+    pub base_lockout_secs: u64,
+    pub max_lockout_secs: u64,
+    /// Failures older than this are forgotten.
+    pub reset_after_secs: u64,
+}
+
+pub const ACCOUNT_THROTTLE_POLICY: ThrottlePolicy = ThrottlePolicy {
+    free_attempts: 5,
+    base_lockout_secs: 30,
+    max_lockout_secs: 60 * 60,      // 1 hour
+    reset_after_secs: 60 * 60 * 24, // 1 day
+};
+
+pub const IP_THROTTLE_POLICY: ThrottlePolicy = ThrottlePolicy {
+    free_attempts: 20,
+    base_lockout_secs: 10,
+    max_lockout_secs: 60 * 15, // 15 minutes
+    reset_after_secs: 60 * 60, // 1 hour
+};
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Attempts {
+    failures: u32,
+    last_failure: u64,
+    locked_until: u64,
+}
+
+struct AttemptTracker<K> {
+    policy: ThrottlePolicy,
+    attempts: Mutex<HashMap<K, Attempts>>,
+}
+
+impl<K: Eq + Hash> AttemptTracker<K> {
+    fn new(policy: ThrottlePolicy) -> AttemptTracker<K> {
+        AttemptTracker {
+            policy,
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn locked_for(&self, key: &K, now: u64) -> Option<u64> {
+        let attempts = self.attempts.lock().unwrap();
+        attempts
+            .get(key)
+            .filter(|a| a.locked_until > now)
+            .map(|a| a.locked_until - now)
+    }
+
+    fn record_failure(&self, key: K, now: u64) {
+        let policy = self.policy;
+        let mut attempts = self.attempts.lock().unwrap();
+
+        if attempts.len() >= PRUNE_THRESHOLD {
+            attempts.retain(|_, a| now < a.last_failure + policy.reset_after_secs);
+        }
+
+        let entry = attempts.entry(key).or_default();
+        if now >= entry.last_failure + policy.reset_after_secs {
+            *entry = Attempts::default();
+        }
+
+        entry.failures += 1;
+        entry.last_failure = now;
+
+        if entry.failures > policy.free_attempts {
+            let exponent = (entry.failures - policy.free_attempts - 1).min(32);
+            let lockout = policy
+                .base_lockout_secs
+                .saturating_mul(1 << exponent)
+                .min(policy.max_lockout_secs);
+            entry.locked_until = now + lockout;
+        }
+    }
+
+    fn clear(&self, key: &K) -> bool {
+        self.attempts.lock().unwrap().remove(key).is_some()
+    }
+}
+
+/// In-memory failed login tracking per account email and per client IP.
+///
+/// After the free attempts of a policy are used up, every further failure locks the
+/// key out for an exponentially growing duration.
+pub struct LoginThrottle {
+    accounts: AttemptTracker<String>,
+    ips: AttemptTracker<IpAddr>,
+}
+
+impl LoginThrottle {
+    pub fn new(account_policy: ThrottlePolicy, ip_policy: ThrottlePolicy) -> LoginThrottle {
+        LoginThrottle {
+            accounts: AttemptTracker::new(account_policy),
+            ips: AttemptTracker::new(ip_policy),
+        }
+    }
+
+    /// Seconds until the account or the ip may attempt to log in again, if locked out.
+    pub fn locked_for(&self, email: &str, ip: Option<IpAddr>) -> Option<u64> {
+        let now = unix_timestamp();
+        let account = self.accounts.locked_for(&normalize(email), now);
+        let ip = ip.and_then(|ip| self.ips.locked_for(&ip, now));
+
+        account.max(ip)
+    }
+
+    pub fn record_failure(&self, email: &str, ip: Option<IpAddr>) {
+        let now = unix_timestamp();
+        self.accounts.record_failure(normalize(email), now);
+        if let Some(ip) = ip {
+            self.ips.record_failure(ip, now);
+        }
+    }
+
+    /// Forgets the failures of the account. The ip keeps its history,
+    /// so a valid login cannot be used to reset a guessing attempt.
+    pub fn record_success(&self, email: &str) {
+        self.accounts.clear(&normalize(email));
+    }
+
+    /// Lifts a lockout of the account. Returns `false` if it had no failed attempts.
+    pub fn unlock_account(&self, email: &str) -> bool {
+        self.accounts.clear(&normalize(email))
+    }
+}
+
+impl Default for LoginThrottle {
+    fn default() -> Self {
+        LoginThrottle::new(ACCOUNT_THROTTLE_POLICY, IP_THROTTLE_POLICY)
+    }
+}
+
+fn normalize(email: &str) -> String {
+    email.trim().to_lowercase()
+}
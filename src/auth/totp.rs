@@ -0,0 +1,195 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+pub const TOTP_ISSUER: &str = "AGEM";
+pub const TOTP_DIGITS: u32 = 6;
+pub const TOTP_PERIOD: u64 = 30;
+/// Accepted clock drift, in periods, in either direction.
+pub const TOTP_SKEW: u64 = 1;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Random 160 bit secret, base32 encoded as expected by authenticator apps.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+pub fn otpauth_uri(account_name: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_PERIOD}",
+        issuer = percent_encode(TOTP_ISSUER),
+        account = percent_encode(account_name),
+    )
+}
+
+/// RFC 4226 HOTP value of the given counter.
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    value % 10u32.pow(TOTP_DIGITS)
+}
+
+/// Checks an RFC 6238 code and returns the time step it belongs to.
+///
+/// Steps up to `last_used_step` are rejected so that a code cannot be replayed.
+pub fn verify(secret: &str, code: &str, now: u64, last_used_step: Option<u64>) -> Option<u64> {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    if !is_totp_code(code) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+
+    let current = now / TOTP_PERIOD;
+    (current.saturating_sub(TOTP_SKEW)..=current + TOTP_SKEW)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| hotp(&secret, *step) == code)
+}
+
+pub fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS as usize && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Single-use codes of the form `xxxxx-xxxxx` for when the authenticator is lost.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 7];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+            format!("{}-{}", &code[..5], &code[5..10])
+        })
+        .collect()
+}
+
+/// Recovery codes are compared case-insensitively and without separators.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Secret of the RFC 6238 SHA-1 test vectors, the ASCII string `12345678901234567890`.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn rfc_secret() -> String {
+        BASE32_NOPAD.encode(RFC_SECRET)
+    }
+
+    fn code_at(now: u64) -> String {
+        format!("{:06}", hotp(RFC_SECRET, now / TOTP_PERIOD))
+    }
+
+    #[test]
+    fn hotp_matches_the_rfc_6238_sha1_vectors() {
+        // RFC 6238 Appendix B lists 8 digit codes, of which 6 digit codes are the last digits.
+        let vectors = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ];
+
+        for (time, code) in vectors {
+            assert_eq!(code_at(time), code[2..], "time {time}");
+            assert_eq!(
+                verify(&rfc_secret(), &code[2..], time, None),
+                Some(time / TOTP_PERIOD),
+                "time {time}"
+            );
+        }
+    }
+
+    #[test]
+    fn verify_rejects_replayed_steps() {
+        let now = 1111111111;
+        let step = now / TOTP_PERIOD;
+        let code = code_at(now);
+
+        assert_eq!(
+            verify(&rfc_secret(), &code, now, Some(step - 1)),
+            Some(step)
+        );
+        assert_eq!(verify(&rfc_secret(), &code, now, Some(step)), None);
+        assert_eq!(verify(&rfc_secret(), &code, now, Some(step + 1)), None);
+    }
+
+    #[test]
+    fn verify_accepts_one_step_of_clock_drift() {
+        let now = 1234567890;
+
+        for drift in [-1i64, 0, 1] {
+            let then = (now as i64 + drift * TOTP_PERIOD as i64) as u64;
+            assert_eq!(
+                verify(&rfc_secret(), &code_at(then), now, None),
+                Some(then / TOTP_PERIOD),
+                "drift {drift}"
+            );
+        }
+        for drift in [-2i64, 2] {
+            let then = (now as i64 + drift * TOTP_PERIOD as i64) as u64;
+            assert_eq!(
+                verify(&rfc_secret(), &code_at(then), now, None),
+                None,
+                "drift {drift}"
+            );
+        }
+    }
+
+    #[test]
+    fn verify_rejects_malformed_codes_and_secrets() {
+        let now = 59;
+
+        assert_eq!(verify(&rfc_secret(), "94287082", now, None), None);
+        assert_eq!(verify(&rfc_secret(), "28708", now, None), None);
+        assert_eq!(verify(&rfc_secret(), "28708a", now, None), None);
+        assert_eq!(verify("not base32!", "287082", now, None), None);
+    }
+
+    #[test]
+    fn recovery_codes_are_normalized() {
+        assert_eq!(normalize_recovery_code("AbCde-12345"), "abcde12345");
+        assert_eq!(normalize_recovery_code(" abcde 12345 "), "abcde12345");
+        assert_eq!(normalize_recovery_code("abcde12345"), "abcde12345");
+
+        for code in generate_recovery_codes() {
+            assert_eq!(
+                normalize_recovery_code(&code.to_uppercase()),
+                normalize_recovery_code(&code)
+            );
+            assert_eq!(normalize_recovery_code(&code).len(), 10);
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use std::{collections::HashMap, env, fmt::Display, path::PathBuf, str::FromStr, time::Duration};
+
+use actix_cors::Cors;
+use actix_web::http::{
+    Method, Uri,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+};
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    auth::{
+        ACCESS_TOKEN_EXPIRES_IN, MFA_PENDING_TOKEN_EXPIRES_IN, PASSWORD_RESET_TOKEN_EXPIRES_IN,
+        REFRESH_TOKEN_EXPIRES_IN, TokenLifetimes,
+    },
+    correlation::CORRELATION_ID_HEADER,
+    db::{DbConnectionParameters, DbPoolParameters},
+};
+
+const BCRYPT_MIN_COST: u32 = 4;
+const BCRYPT_MAX_COST: u32 = 31;
+
+/// Path of the configuration file, read if it exists unless `AGEM_CONFIG` names another one.
+pub const DEFAULT_CONFIG_FILE: &str = "agem.toml";
+
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Number of worker threads, one per CPU core when not set.
+    pub workers: Option<usize>,
+    pub client_request_timeout: Duration,
+    pub shutdown_timeout_secs: u64,
+}
+
+pub struct PostgresConfig {
+    pub connection: DbConnectionParameters,
+    pub pool: DbPoolParameters,
+    /// Applies the pending migrations at startup. `agem-server migrate` applies them regardless.
+    pub run_migrations: bool,
+}
+
+/// Account given the `Admin` role at startup while no account holds it, so that a new
+/// deployment, or the in-memory store which starts empty, can be administered.
+pub struct AdminConfig {
+    pub email: String,
+    pub password: String,
+}
+
+/// Storage of the server, selected by `database.backend`.
+pub enum DatabaseConfig {
+    Postgres(PostgresConfig),
+    /// Everything is lost when the server stops, meant for development and tests.
+    Memory,
+}
+
+enum DatabaseBackend {
+    Postgres,
+    Memory,
+}
+
+impl FromStr for DatabaseBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "postgres" => Ok(DatabaseBackend::Postgres),
+            "memory" => Ok(DatabaseBackend::Memory),
+            _ => Err("expected postgres or memory".to_string()),
+        }
+    }
+}
+
+pub struct AuthConfig {
+    pub jwt_keys_file: PathBuf,
+    pub token_lifetimes: TokenLifetimes,
+    pub bcrypt_cost: u32,
+}
+
+#[derive(Clone)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API from a browser, none when empty.
+    pub allowed_origins: Vec<String>,
+    pub max_age_secs: usize,
+}
+
+pub struct MailConfig {
+    pub outbox_dir: PathBuf,
+}
+
+#[derive(Clone, Copy)]
+pub enum LogFormat {
+    /// One line of text per event, for terminals.
+    Human,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(LogFormat::Human),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("expected human or json".to_string()),
+        }
+    }
+}
+
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// Level of the logged events, as `info` or as per target directives such as
+    /// `info,agem_server=debug`.
+    pub level: String,
+}
+
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub admin: Option<AdminConfig>,
+    pub auth: AuthConfig,
+    pub cors: CorsConfig,
+    pub mail: MailConfig,
+    pub log: LogConfig,
+}
+
+/// Every missing or invalid setting found while loading, not only the first.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid configuration:{}", .0.iter().map(|e| format!("\n  - {e}")).collect::<String>())]
+pub struct ConfigError(pub Vec<String>);
+
+/// Settings of the configuration file flattened to `section.key`, overridden by environment
+/// variables. Values of both layers are parsed the same way, lists are comma separated.
+struct Layers {
+    file: HashMap<String, String>,
+    errors: Vec<String>,
+}
+
+impl Layers {
+    fn value(&mut self, key: &str, env_var: &str) -> Option<String> {
+        let from_file = self.file.remove(key);
+        env::var(env_var).ok().or(from_file)
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str, env_var: &str, value: &str) -> Option<T>
+    where
+        T::Err: Display,
+    {
+        match value.trim().parse() {
+            Ok(v) => Some(v),
+            Err(err) => {
+                self.errors
+                    .push(format!("{key} ({env_var}): invalid value {value:?}, {err}"));
+                None
+            }
+        }
+    }
+
+    fn optional<T: FromStr>(&mut self, key: &str, env_var: &str) -> Option<T>
+    where
+        T::Err: Display,
+    {
+        let value = self.value(key, env_var)?;
+        self.parse(key, env_var, &value)
+    }
+
+    fn or_default<T: FromStr>(&mut self, key: &str, env_var: &str, default: T) -> T
+    where
+        T::Err: Display,
+    {
+        self.optional(key, env_var).unwrap_or(default)
+    }
+
+    /// Missing values are reported, the returned default is never used then.
+    fn required<T: FromStr + Default>(&mut self, key: &str, env_var: &str) -> T
+    where
+        T::Err: Display,
+    {
+        match self.value(key, env_var) {
+            Some(value) => self.parse(key, env_var, &value).unwrap_or_default(),
+            None => {
+                self.errors.push(format!("{key} ({env_var}): missing"));
+                T::default()
+            }
+        }
+    }
+
+    fn list(&mut self, key: &str, env_var: &str) -> Vec<String> {
+        self.value(key, env_var)
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Reads settings whose errors do not matter, e.g. those of the unused database backend,
+    /// so they are not reported as unknown either.
+    fn discard<T>(&mut self, read: impl FnOnce(&mut Layers) -> T) {
+        let errors = self.errors.len();
+        read(self);
+        self.errors.truncate(errors);
+    }
+
+    fn check(&mut self, valid: bool, key: &str, env_var: &str, requirement: &str) {
+        if !valid {
+            self.errors
+                .push(format!("{key} ({env_var}): {requirement}"));
+        }
+    }
+}
+
+fn is_valid_origin(origin: &str) -> bool {
+    origin.parse::<Uri>().is_ok_and(|uri| {
+        matches!(uri.scheme_str(), Some("http" | "https"))
+            && uri.authority().is_some()
+            && !origin.ends_with('/')
+    })
+}
+
+fn flatten(prefix: &str, table: toml::Table, into: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = match prefix.is_empty() {
+            true => key,
+            false => format!("{prefix}.{key}"),
+        };
+        let value = match value {
+            toml::Value::Table(table) => {
+                flatten(&key, table, into);
+                continue;
+            }
+            toml::Value::String(s) => s,
+            toml::Value::Array(values) => values
+                .into_iter()
+                .map(|v| match v {
+                    toml::Value::String(s) => s,
+                    v => v.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            v => v.to_string(),
+        };
+        into.insert(key, value);
+    }
+}
+
+fn read_config_file() -> Result<HashMap<String, String>, String> {
+    let (path, explicit) = match env::var("AGEM_CONFIG") {
+        Ok(path) => (PathBuf::from(path), true),
+        Err(_) => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+    };
+
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if explicit || err.kind() != std::io::ErrorKind::NotFound => {
+            return Err(format!("{} (AGEM_CONFIG): {err}", path.display()));
+        }
+        Err(_) => return Ok(HashMap::new()),
+    };
+
+    let table: toml::Table = content
+        .parse()
+        .map_err(|err| format!("{} (AGEM_CONFIG): {err}", path.display()))?;
+    let mut settings = HashMap::new();
+    flatten("", table, &mut settings);
+
+    Ok(settings)
+}
+
+impl Config {
+    /// Loads the configuration file and the environment, `.env` included.
+    pub fn load() -> Result<Config, ConfigError> {
+        dotenv::dotenv().ok();
+
+        let file = read_config_file().map_err(|err| ConfigError(vec![err]))?;
+        let mut layers = Layers {
+            file,
+            errors: Vec::new(),
+        };
+        let config = Config::from_layers(&mut layers);
+
+        let mut unknown: Vec<_> = layers.file.keys().cloned().collect();
+        unknown.sort();
+        for key in unknown {
+            layers.errors.push(format!("{key}: unknown setting"));
+        }
+
+        match layers.errors.is_empty() {
+            true => Ok(config),
+            false => Err(ConfigError(layers.errors)),
+        }
+    }
+
+    fn from_layers(l: &mut Layers) -> Config {
+        let server = ServerConfig {
+            host: l.or_default("server.host", "SERVER_HOST", "127.0.0.1".to_string()),
+            port: l.or_default("server.port", "SERVER_PORT", 8080),
+            workers: l.optional("server.workers", "SERVER_WORKERS"),
+            client_request_timeout: Duration::from_secs(l.or_default(
+                "server.client_request_timeout_secs",
+                "SERVER_CLIENT_REQUEST_TIMEOUT_SECS",
+                5,
+            )),
+            shutdown_timeout_secs: l.or_default(
+                "server.shutdown_timeout_secs",
+                "SERVER_SHUTDOWN_TIMEOUT_SECS",
+                30,
+            ),
+        };
+        l.check(
+            server.workers != Some(0),
+            "server.workers",
+            "SERVER_WORKERS",
+            "must be at least 1",
+        );
+
+        let backend = l.or_default(
+            "database.backend",
+            "DATABASE_BACKEND",
+            DatabaseBackend::Postgres,
+        );
+        let database = match backend {
+            DatabaseBackend::Postgres => DatabaseConfig::Postgres(Config::postgres_config(l)),
+            DatabaseBackend::Memory => {
+                l.discard(Config::postgres_config);
+                DatabaseConfig::Memory
+            }
+        };
+
+        let email = l.value("admin.email", "ADMIN_EMAIL");
+        let password = l.value("admin.password", "ADMIN_PASSWORD");
+        l.check(
+            email.is_some() == password.is_some(),
+            "admin.password",
+            "ADMIN_PASSWORD",
+            "must be set together with admin.email",
+        );
+        let admin = email
+            .zip(password)
+            .map(|(email, password)| AdminConfig { email, password });
+
+        let auth = AuthConfig {
+            jwt_keys_file: l.required("auth.jwt_keys_file", "JWT_KEYS_FILE"),
+            token_lifetimes: TokenLifetimes {
+                access: l.or_default(
+                    "auth.access_token_ttl_secs",
+                    "ACCESS_TOKEN_TTL_SECS",
+                    ACCESS_TOKEN_EXPIRES_IN,
+                ),
+                refresh: l.or_default(
+                    "auth.refresh_token_ttl_secs",
+                    "REFRESH_TOKEN_TTL_SECS",
+                    REFRESH_TOKEN_EXPIRES_IN,
+                ),
+                mfa_pending: l.or_default(
+                    "auth.mfa_pending_token_ttl_secs",
+                    "MFA_PENDING_TOKEN_TTL_SECS",
+                    MFA_PENDING_TOKEN_EXPIRES_IN,
+                ),
+                password_reset: l.or_default(
+                    "auth.password_reset_token_ttl_secs",
+                    "PASSWORD_RESET_TOKEN_TTL_SECS",
+                    PASSWORD_RESET_TOKEN_EXPIRES_IN,
+                ),
+            },
+            bcrypt_cost: l.or_default("auth.bcrypt_cost", "BCRYPT_COST", bcrypt::DEFAULT_COST),
+        };
+        let lifetimes = auth.token_lifetimes;
+        for (valid, key, env_var) in [
+            (
+                lifetimes.access > 0,
+                "auth.access_token_ttl_secs",
+                "ACCESS_TOKEN_TTL_SECS",
+            ),
+            (
+                lifetimes.refresh > 0,
+                "auth.refresh_token_ttl_secs",
+                "REFRESH_TOKEN_TTL_SECS",
+            ),
+            (
+                lifetimes.mfa_pending > 0,
+                "auth.mfa_pending_token_ttl_secs",
+                "MFA_PENDING_TOKEN_TTL_SECS",
+            ),
+            (
+                lifetimes.password_reset > 0,
+                "auth.password_reset_token_ttl_secs",
+                "PASSWORD_RESET_TOKEN_TTL_SECS",
+            ),
+        ] {
+            l.check(valid, key, env_var, "must be at least 1");
+        }
+        l.check(
+            (BCRYPT_MIN_COST..=BCRYPT_MAX_COST).contains(&auth.bcrypt_cost),
+            "auth.bcrypt_cost",
+            "BCRYPT_COST",
+            &format!("must be between {BCRYPT_MIN_COST} and {BCRYPT_MAX_COST}"),
+        );
+
+        let cors = CorsConfig {
+            allowed_origins: l.list("cors.allowed_origins", "CORS_ALLOWED_ORIGINS"),
+            max_age_secs: l.or_default("cors.max_age_secs", "CORS_MAX_AGE_SECS", 60 * 60),
+        };
+        for origin in &cors.allowed_origins {
+            l.check(
+                is_valid_origin(origin),
+                "cors.allowed_origins",
+                "CORS_ALLOWED_ORIGINS",
+                &format!("{origin:?} is not an origin such as https://example.com"),
+            );
+        }
+
+        let mail = MailConfig {
+            outbox_dir: l.required("mail.outbox_dir", "MAIL_OUTBOX_DIR"),
+        };
+
+        let log = LogConfig {
+            format: l.or_default("log.format", "LOG_FORMAT", LogFormat::Human),
+            level: l.or_default("log.level", "LOG_LEVEL", "info".to_string()),
+        };
+        l.check(
+            EnvFilter::try_new(&log.level).is_ok(),
+            "log.level",
+            "LOG_LEVEL",
+            "must be a level such as info, or directives such as info,agem_server=debug",
+        );
+
+        Config {
+            server,
+            database,
+            admin,
+            auth,
+            cors,
+            mail,
+            log,
+        }
+    }
+
+    fn postgres_config(l: &mut Layers) -> PostgresConfig {
+        let postgres = PostgresConfig {
+            connection: DbConnectionParameters {
+                host: l.required("database.host", "SQLDB_HOST"),
+                port: l.required("database.port", "SQLDB_PORT"),
+                database: l.required("database.database", "SQLDB_DATABASE"),
+                user: l.required("database.user", "SQLDB_USER"),
+                password: l.required("database.password", "SQLDB_PASSWORD"),
+            },
+            pool: DbPoolParameters {
+                max_connections: l.or_default(
+                    "database.max_connections",
+                    "SQLDB_MAX_CONNECTIONS",
+                    5,
+                ),
+                min_connections: l.or_default(
+                    "database.min_connections",
+                    "SQLDB_MIN_CONNECTIONS",
+                    0,
+                ),
+                acquire_timeout: Duration::from_secs(l.or_default(
+                    "database.acquire_timeout_secs",
+                    "SQLDB_ACQUIRE_TIMEOUT_SECS",
+                    30,
+                )),
+                idle_timeout: Duration::from_secs(l.or_default(
+                    "database.idle_timeout_secs",
+                    "SQLDB_IDLE_TIMEOUT_SECS",
+                    600,
+                )),
+            },
+            run_migrations: l.or_default("database.run_migrations", "SQLDB_RUN_MIGRATIONS", true),
+        };
+        l.check(
+            postgres.pool.max_connections >= 1,
+            "database.max_connections",
+            "SQLDB_MAX_CONNECTIONS",
+            "must be at least 1",
+        );
+        l.check(
+            postgres.pool.min_connections <= postgres.pool.max_connections,
+            "database.min_connections",
+            "SQLDB_MIN_CONNECTIONS",
+            "must not exceed database.max_connections",
+        );
+
+        postgres
+    }
+}
+
+impl CorsConfig {
+    pub fn middleware(&self) -> Cors {
+        self.allowed_origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+            .allowed_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allowed_headers([AUTHORIZATION, CONTENT_TYPE, CORRELATION_ID_HEADER])
+            .expose_headers([CORRELATION_ID_HEADER])
+            .max_age(self.max_age_secs)
+    }
+}
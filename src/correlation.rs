@@ -0,0 +1,104 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{
+    Error,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::{HeaderName, HeaderValue},
+};
+use futures_util::future::{Ready, ready};
+use uuid::Uuid;
+
+pub const CORRELATION_ID_HEADER: HeaderName = HeaderName::from_static("x-correlation-id");
+
+const MAX_CORRELATION_ID_LEN: usize = 64;
+
+/// The request being handled, available to everything running on behalf of it.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub correlation_id: String,
+    pub path: String,
+}
+
+tokio::task_local! {
+    static REQUEST: RequestContext;
+}
+
+/// Context of the request being handled, `None` outside of a request.
+pub fn current_request() -> Option<RequestContext> {
+    REQUEST.try_with(|r| r.clone()).ok()
+}
+
+/// Ids sent by the clients are kept when they are safe to echo back and to log.
+fn is_valid_correlation_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_CORRELATION_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b))
+}
+
+/// Tags every request with a correlation id, taken from the `X-Correlation-Id` request header or
+/// generated, and returns it in the same header of the response. The inner services run in the
+/// [`RequestContext`] of the request, so problem bodies and logs carry the id as well.
+///
+/// Errors are only in context when they are turned into a response by the inner services, so the
+/// middlewares within respond with their errors instead of failing.
+pub struct CorrelationId;
+
+impl<S, B> Transform<S, ServiceRequest> for CorrelationId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CorrelationIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CorrelationIdMiddleware { service }))
+    }
+}
+
+pub struct CorrelationIdMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for CorrelationIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let correlation_id = req
+            .headers()
+            .get(CORRELATION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|id| is_valid_correlation_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let context = RequestContext {
+            correlation_id: correlation_id.clone(),
+            path: req.path().to_string(),
+        };
+
+        let response = REQUEST.sync_scope(context.clone(), || self.service.call(req));
+
+        Box::pin(REQUEST.scope(context, async move {
+            let mut response = response.await?;
+            response.headers_mut().insert(
+                CORRELATION_ID_HEADER,
+                HeaderValue::from_str(&correlation_id).unwrap(),
+            );
+
+            Ok(response)
+        }))
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+
+use crate::{
+    auth::ROLE_ADMIN,
+    db::{
+        repository::AccountRepository,
+        types::{DB_Account, DB_AccountListing, DB_Role, ID},
+    },
+    error::{AppError, AppResult},
+    service::{
+        Account, AccountCursor, AccountPage, Role,
+        account::{AccountFilter, AccountOrder, into_account, into_account_page},
+        role::into_role,
+    },
+};
+
+use super::{MemoryRepository, Tables};
+
+const CONTACT_TAKEN: &str = "Phone number or email is already used by another account.";
+
+impl Tables {
+    fn account(&self, account_id: ID) -> Option<&DB_Account> {
+        self.accounts.iter().find(|a| a.id == account_id)
+    }
+
+    fn account_mut(&mut self, account_id: ID) -> Option<&mut DB_Account> {
+        self.accounts.iter_mut().find(|a| a.id == account_id)
+    }
+
+    /// An account, other than `except`, has the phone number or the email.
+    fn contact_taken(&self, phone_number: &str, email: &str, except: Option<ID>) -> bool {
+        self.accounts
+            .iter()
+            .any(|a| Some(a.id) != except && (a.phone_number == phone_number || a.email == email))
+    }
+
+    pub(super) fn account_roles(&self, account_id: ID) -> Vec<DB_Role> {
+        self.roles
+            .iter()
+            .filter(|r| self.account_roles.contains(&(account_id, r.id)))
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl AccountRepository for MemoryRepository {
+    async fn create_account(&self, mut account: DB_Account) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if tables.contact_taken(&account.phone_number, &account.email, None) {
+            return Err(AppError::conflict(CONTACT_TAKEN));
+        }
+
+        account.id = tables.next_id();
+        tables.accounts.push(account);
+
+        Ok(())
+    }
+
+    async fn create_admin_account(&self, mut account: DB_Account) -> AppResult<bool> {
+        let mut tables = self.tables();
+
+        let admin = tables.role_id(ROLE_ADMIN).unwrap();
+        if tables
+            .account_roles
+            .iter()
+            .any(|(_, role_id)| *role_id == admin)
+        {
+            return Ok(false);
+        }
+        if tables.contact_taken(&account.phone_number, &account.email, None) {
+            return Err(AppError::conflict(CONTACT_TAKEN));
+        }
+
+        account.id = tables.next_id();
+        tables.account_roles.push((account.id, admin));
+        tables.accounts.push(account);
+
+        Ok(true)
+    }
+
+    async fn create_roles(&self, roles: Vec<String>) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        let mut unique = HashSet::new();
+        if roles
+            .iter()
+            .any(|r| !unique.insert(r) || tables.role_id(r).is_some())
+        {
+            return Err(AppError::conflict("Duplicate role."));
+        }
+
+        for role in roles {
+            let id = tables.next_id();
+            tables.roles.push(DB_Role { id, role });
+        }
+
+        Ok(())
+    }
+
+    async fn add_roles_to_account(&self, account_id: ID, role_ids: Vec<ID>) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if tables.account(account_id).is_none()
+            || !role_ids
+                .iter()
+                .all(|id| tables.roles.iter().any(|r| r.id == *id))
+        {
+            return Err(AppError::not_found("Account or role not found."));
+        }
+
+        let mut unique = HashSet::new();
+        if role_ids
+            .iter()
+            .any(|id| !unique.insert(*id) || tables.account_roles.contains(&(account_id, *id)))
+        {
+            return Err(AppError::conflict("Account already has the role."));
+        }
+
+        for role_id in role_ids {
+            tables.account_roles.push((account_id, role_id));
+        }
+
+        Ok(())
+    }
+
+    async fn fetch_account_by_email(&self, email: String) -> AppResult<Option<Account>> {
+        let tables = self.tables();
+
+        Ok(tables
+            .accounts
+            .iter()
+            .find(|a| a.email == email)
+            .cloned()
+            .map(into_account))
+    }
+
+    async fn fetch_account_by_phone_number(
+        &self,
+        phone_number: String,
+    ) -> AppResult<Option<Account>> {
+        let tables = self.tables();
+
+        Ok(tables
+            .accounts
+            .iter()
+            .find(|a| a.phone_number == phone_number)
+            .cloned()
+            .map(into_account))
+    }
+
+    async fn fetch_account_by_id(&self, account_id: ID) -> AppResult<Option<Account>> {
+        let tables = self.tables();
+
+        Ok(tables.account(account_id).cloned().map(into_account))
+    }
+
+    async fn fetch_account_roles(&self, account_id: ID) -> AppResult<Vec<Role>> {
+        let tables = self.tables();
+
+        Ok(tables
+            .account_roles(account_id)
+            .into_iter()
+            .map(into_role)
+            .collect())
+    }
+
+    async fn update_password(
+        &self,
+        account_id: ID,
+        hashed_password: String,
+        password_set_ts: i64,
+    ) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        let account = tables
+            .account_mut(account_id)
+            .ok_or_else(|| AppError::not_found("Account not found."))?;
+        account.hashed_password = hashed_password;
+        account.password_set_ts = password_set_ts;
+
+        Ok(())
+    }
+
+    async fn update_account_profile(
+        &self,
+        account_id: ID,
+        name: String,
+        lastname: String,
+        phone_number: String,
+        email: String,
+    ) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if tables.contact_taken(&phone_number, &email, Some(account_id)) {
+            return Err(AppError::conflict(CONTACT_TAKEN));
+        }
+
+        let Some(account) = tables.account_mut(account_id) else {
+            return Err(AppError::conflict(CONTACT_TAKEN));
+        };
+        account.name = name;
+        account.lastname = lastname;
+        account.phone_number = phone_number;
+        account.email = email;
+
+        Ok(())
+    }
+
+    async fn fetch_accounts(
+        &self,
+        filter: AccountFilter,
+        order: AccountOrder,
+        ascending: bool,
+        after: Option<AccountCursor>,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<AccountPage> {
+        let tables = self.tables();
+
+        let in_arrears: HashSet<ID> = tables
+            .overdue_periods()
+            .into_iter()
+            .filter(|p| p.outstanding > 0)
+            .map(|p| p.account_id)
+            .collect();
+        let search: Vec<String> = filter.search.iter().map(|t| t.to_lowercase()).collect();
+
+        let mut accounts: Vec<DB_AccountListing> = tables
+            .accounts
+            .iter()
+            .filter(|a| {
+                let text = [&a.name, &a.lastname, &a.email, &a.phone_number]
+                    .map(|s| s.as_str())
+                    .join(" ")
+                    .to_lowercase();
+                search.iter().all(|term| text.contains(term.as_str()))
+            })
+            .filter(|a| {
+                filter
+                    .role
+                    .as_ref()
+                    .is_none_or(|r| tables.has_role(a.id, r))
+            })
+            .filter(|a| {
+                filter
+                    .in_arrears
+                    .is_none_or(|arrears| in_arrears.contains(&a.id) == arrears)
+            })
+            .map(|a| {
+                let mut roles: Vec<String> = tables
+                    .account_roles(a.id)
+                    .into_iter()
+                    .map(|r| r.role)
+                    .collect();
+                roles.sort();
+
+                DB_AccountListing {
+                    id: a.id,
+                    name: a.name.clone(),
+                    lastname: a.lastname.clone(),
+                    email: a.email.clone(),
+                    phone_number: a.phone_number.clone(),
+                    roles,
+                    in_arrears: in_arrears.contains(&a.id),
+                    sort_key: match order {
+                        AccountOrder::Id => String::new(),
+                        AccountOrder::Name => a.name.to_lowercase(),
+                        AccountOrder::Lastname => a.lastname.to_lowercase(),
+                        AccountOrder::Email => a.email.to_lowercase(),
+                    },
+                }
+            })
+            .collect();
+
+        let total = accounts.len() as u64;
+
+        accounts.sort_by(|a, b| (&a.sort_key, a.id).cmp(&(&b.sort_key, b.id)));
+        if !ascending {
+            accounts.reverse();
+        }
+        if let Some(after) = after {
+            accounts.retain(|a| {
+                let position = (&a.sort_key, a.id).cmp(&(&after.sort_key, after.id));
+                match ascending {
+                    true => position.is_gt(),
+                    false => position.is_lt(),
+                }
+            });
+        }
+
+        let accounts = accounts
+            .into_iter()
+            .skip(offset as usize)
+            .take(count as usize + 1)
+            .collect();
+
+        Ok(into_account_page(total, accounts, count))
+    }
+}
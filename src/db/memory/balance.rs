@@ -0,0 +1,245 @@
+use std::cmp::Ordering;
+
+use async_trait::async_trait;
+
+use crate::{
+    db::{
+        repository::BalanceRepository,
+        types::{
+            DB_Account, DB_Arrears, DB_MonthlyFee, DB_Payment, DB_PeriodStatement, DB_PrePayment,
+            ID,
+        },
+    },
+    error::AppResult,
+    service::{
+        Arrears, PeriodStatement,
+        balance::{ArrearsOrder, into_arrears, into_period_statement},
+    },
+    util::unix_timestamp,
+};
+
+use super::{MemoryRepository, Tables};
+
+/// Outstanding amount of a started fee period of an account.
+pub(super) struct OverduePeriod {
+    pub account_id: ID,
+    pub year: i32,
+    pub month: i32,
+    pub currency: String,
+    pub outstanding: i64,
+}
+
+/// `(year, month)` of today in UTC.
+fn current_period() -> (i32, i32) {
+    period_of(unix_timestamp() as i64)
+}
+
+/// `(year, month)` of a unix timestamp in UTC.
+fn period_of(timestamp: i64) -> (i32, i32) {
+    // civil date of a day count, see http://howardhinnant.github.io/date_algorithms.html
+    let days = timestamp.div_euclid(86400) + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year as i32, month as i32)
+}
+
+fn has_started(fee: &DB_MonthlyFee, today: (i32, i32)) -> bool {
+    (fee.year, fee.month) <= today
+}
+
+fn is_charged(fee: &DB_MonthlyFee, account: &DB_Account) -> bool {
+    (fee.year, fee.month) >= period_of(account.member_since)
+}
+
+impl Tables {
+    /// What is left of the payment after its reversals.
+    pub(super) fn left(&self, payment: &DB_Payment) -> i64 {
+        payment.amount
+            - self
+                .payment_reversals
+                .iter()
+                .filter(|r| r.payment_id == Some(payment.id))
+                .map(|r| r.amount)
+                .sum::<i64>()
+    }
+
+    /// Sum of what is left of the payments of the account towards the fee.
+    pub(super) fn paid(&self, account_id: ID, fee_id: ID) -> i64 {
+        self.payments
+            .iter()
+            .filter(|p| p.account_id == account_id && p.fee_id == fee_id)
+            .map(|p| self.left(p))
+            .sum()
+    }
+
+    /// What is left of the pre-payment after its reversals.
+    pub(super) fn pre_payment_left(&self, pre_payment: &DB_PrePayment) -> i64 {
+        pre_payment.payment.amount
+            - self
+                .payment_reversals
+                .iter()
+                .filter(|r| r.pre_payment_id == Some(pre_payment.payment.id))
+                .map(|r| r.amount)
+                .sum::<i64>()
+    }
+
+    /// Sum of what is left of the pre-payments of the account for the period of the fee that are
+    /// not applied yet, in its currency.
+    pub(super) fn precovered(&self, account_id: ID, fee: &DB_MonthlyFee) -> i64 {
+        self.pre_payments
+            .iter()
+            .filter(|p| {
+                p.account_id == account_id
+                    && p.applied_payment_id.is_none()
+                    && p.payment.year == fee.year
+                    && p.payment.month == fee.month
+                    && p.payment.currency == fee.currency
+            })
+            .map(|p| self.pre_payment_left(p))
+            .sum()
+    }
+
+    /// Outstanding amount of every started fee period of every account since its membership
+    /// started.
+    pub(super) fn overdue_periods(&self) -> Vec<OverduePeriod> {
+        let today = current_period();
+
+        self.accounts
+            .iter()
+            .flat_map(|account| {
+                self.monthly_fees
+                    .iter()
+                    .filter(move |fee| has_started(fee, today) && is_charged(fee, account))
+                    .map(move |fee| OverduePeriod {
+                        account_id: account.id,
+                        year: fee.year,
+                        month: fee.month,
+                        currency: fee.currency.clone(),
+                        outstanding: fee.amount
+                            - self.paid(account.id, fee.id)
+                            - self.precovered(account.id, fee),
+                    })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl BalanceRepository for MemoryRepository {
+    async fn fetch_period_statements(&self, account_id: ID) -> AppResult<Vec<PeriodStatement>> {
+        let tables = self.tables();
+        let today = current_period();
+
+        let Some(account) = tables.accounts.iter().find(|a| a.id == account_id) else {
+            return Ok(Vec::new());
+        };
+
+        let mut fees: Vec<&DB_MonthlyFee> = tables
+            .monthly_fees
+            .iter()
+            .filter(|fee| is_charged(fee, account))
+            .collect();
+        fees.sort_by_key(|f| (f.year, f.month));
+
+        fees.into_iter()
+            .map(|fee| {
+                into_period_statement(DB_PeriodStatement {
+                    year: fee.year,
+                    month: fee.month,
+                    due: fee.amount,
+                    paid: tables.paid(account_id, fee.id),
+                    precovered: tables.precovered(account_id, fee),
+                    currency: fee.currency.clone(),
+                    started: has_started(fee, today),
+                })
+            })
+            .collect()
+    }
+
+    async fn fetch_arrears(
+        &self,
+        order: ArrearsOrder,
+        ascending: bool,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<Vec<Arrears>> {
+        let tables = self.tables();
+
+        let mut arrears: Vec<DB_Arrears> = Vec::new();
+        for period in tables.overdue_periods() {
+            if period.outstanding <= 0 {
+                continue;
+            }
+            // months since year 0, so that the oldest period is the smallest
+            let months = period.year * 12 + period.month - 1;
+            match arrears
+                .iter_mut()
+                .find(|a| a.account_id == period.account_id && a.currency == period.currency)
+            {
+                Some(a) => {
+                    a.outstanding += period.outstanding;
+                    a.months_overdue += 1;
+                    if months < a.since_year * 12 + a.since_month - 1 {
+                        a.since_year = months / 12;
+                        a.since_month = months % 12 + 1;
+                    }
+                }
+                None => {
+                    let account = tables
+                        .accounts
+                        .iter()
+                        .find(|a| a.id == period.account_id)
+                        .unwrap();
+                    arrears.push(DB_Arrears {
+                        account_id: account.id,
+                        name: account.name.clone(),
+                        lastname: account.lastname.clone(),
+                        email: account.email.clone(),
+                        currency: period.currency,
+                        outstanding: period.outstanding,
+                        months_overdue: 1,
+                        since_year: months / 12,
+                        since_month: months % 12 + 1,
+                    });
+                }
+            }
+        }
+
+        arrears.sort_by(|a, b| {
+            let ordering = match order {
+                ArrearsOrder::Amount => {
+                    (a.outstanding, a.months_overdue).cmp(&(b.outstanding, b.months_overdue))
+                }
+                ArrearsOrder::MonthsOverdue => {
+                    (a.months_overdue, a.outstanding).cmp(&(b.months_overdue, b.outstanding))
+                }
+            };
+            let ordering = match ascending {
+                true => ordering,
+                false => ordering.reverse(),
+            };
+            match ordering {
+                Ordering::Equal => a.account_id.cmp(&b.account_id),
+                ordering => ordering,
+            }
+        });
+
+        arrears
+            .into_iter()
+            .skip(offset as usize)
+            .take(count as usize)
+            .map(into_arrears)
+            .collect()
+    }
+}
@@ -0,0 +1,164 @@
+use async_trait::async_trait;
+
+use crate::{
+    db::{
+        repository::FeeRepository,
+        types::{DB_MonthlyFee, ID},
+    },
+    error::{AppError, AppResult},
+    money::Money,
+    service::{MonthlyFee, PrePaymentReport, fee::into_monthly_fee},
+};
+
+use super::{MemoryRepository, Tables};
+
+const PERIOD_TAKEN: &str = "Fee for this period already exists.";
+
+impl Tables {
+    fn period_taken(&self, year: i32, month: i32, except: Option<ID>) -> bool {
+        self.monthly_fees
+            .iter()
+            .any(|f| Some(f.id) != except && f.year == year && f.month == month)
+    }
+
+    fn insert_monthly_fee(&mut self, year: i32, month: i32, amount: &Money) -> DB_MonthlyFee {
+        let fee = DB_MonthlyFee {
+            id: self.next_id(),
+            year,
+            month,
+            amount: amount.minor_units,
+            currency: amount.currency.code().to_string(),
+        };
+        self.monthly_fees.push(fee.clone());
+
+        fee
+    }
+}
+
+#[async_trait]
+impl FeeRepository for MemoryRepository {
+    async fn create_monthly_fee(
+        &self,
+        year: i32,
+        month: i32,
+        amount: Money,
+    ) -> AppResult<(MonthlyFee, PrePaymentReport)> {
+        let mut tables = self.tables();
+
+        if tables.period_taken(year, month, None) {
+            return Err(AppError::conflict(PERIOD_TAKEN));
+        }
+
+        let fee = into_monthly_fee(tables.insert_monthly_fee(year, month, &amount))?;
+        let report = tables.apply_pre_payments(Some(&[fee.id]))?;
+
+        Ok((fee, report))
+    }
+
+    async fn create_monthly_fees_for_year(
+        &self,
+        year: i32,
+        amount: Money,
+    ) -> AppResult<(Vec<MonthlyFee>, PrePaymentReport)> {
+        let mut tables = self.tables();
+
+        let mut fees = Vec::new();
+        for month in 1..=12 {
+            if !tables.period_taken(year, month, None) {
+                fees.push(into_monthly_fee(
+                    tables.insert_monthly_fee(year, month, &amount),
+                )?);
+            }
+        }
+
+        let fee_ids: Vec<ID> = fees.iter().map(|f| f.id).collect();
+        let report = tables.apply_pre_payments(Some(&fee_ids))?;
+
+        Ok((fees, report))
+    }
+
+    async fn fetch_monthly_fees(
+        &self,
+        ascending: bool,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<Vec<MonthlyFee>> {
+        let tables = self.tables();
+
+        let mut fees = tables.monthly_fees.clone();
+        fees.sort_by_key(|f| (f.year, f.month));
+        if !ascending {
+            fees.reverse();
+        }
+
+        Ok(fees
+            .into_iter()
+            .skip(offset as usize)
+            .take(count as usize)
+            .map(into_monthly_fee)
+            .collect::<Result<_, _>>()?)
+    }
+
+    async fn fetch_monthly_fee(&self, fee_id: ID) -> AppResult<Option<MonthlyFee>> {
+        let tables = self.tables();
+
+        Ok(tables
+            .monthly_fees
+            .iter()
+            .find(|f| f.id == fee_id)
+            .cloned()
+            .map(into_monthly_fee)
+            .transpose()?)
+    }
+
+    async fn update_monthly_fee(
+        &self,
+        fee_id: ID,
+        year: i32,
+        month: i32,
+        amount: Money,
+    ) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        let overpaid = tables
+            .payments
+            .iter()
+            .filter(|p| p.fee_id == fee_id && tables.left(p) > 0)
+            .any(|p| {
+                p.currency != amount.currency.code()
+                    || tables.paid(p.account_id, fee_id) > amount.minor_units
+            });
+        if overpaid {
+            return Err(AppError::conflict(
+                "An account already paid more than this amount or in another currency.",
+            ));
+        }
+
+        if tables.period_taken(year, month, Some(fee_id)) {
+            return Err(AppError::conflict(PERIOD_TAKEN));
+        }
+
+        if let Some(fee) = tables.monthly_fees.iter_mut().find(|f| f.id == fee_id) {
+            fee.year = year;
+            fee.month = month;
+            fee.amount = amount.minor_units;
+            fee.currency = amount.currency.code().to_string();
+        }
+
+        Ok(())
+    }
+
+    async fn delete_monthly_fee(&self, fee_id: ID) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if tables.payments.iter().any(|p| p.fee_id == fee_id) {
+            return Err(AppError::conflict(
+                "Fee cannot be deleted because it has payments.",
+            ));
+        }
+
+        tables.monthly_fees.retain(|f| f.id != fee_id);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+
+use crate::{
+    db::repository::HealthRepository,
+    service::health::{PoolStats, Readiness},
+};
+
+use super::MemoryRepository;
+
+#[async_trait]
+impl HealthRepository for MemoryRepository {
+    /// The store lives in the process and has no schema to migrate.
+    async fn check_readiness(&self) -> Readiness {
+        Readiness::Ready
+    }
+
+    async fn fetch_pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+}
@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+
+use crate::{
+    db::{
+        repository::MfaRepository,
+        types::{DB_AccountTotp, ID},
+    },
+    error::{AppError, AppResult},
+    service::AccountTotp,
+};
+
+use super::{MemoryRepository, RecoveryCode, Tables};
+
+impl Tables {
+    fn totp_mut(&mut self, account_id: ID) -> Option<&mut DB_AccountTotp> {
+        self.account_totp
+            .iter_mut()
+            .find(|t| t.account_id == account_id)
+    }
+
+    fn replace_recovery_codes(&mut self, account_id: ID, code_hashes: Vec<String>) {
+        self.recovery_codes.retain(|c| c.account_id != account_id);
+        self.recovery_codes
+            .extend(code_hashes.into_iter().map(|code_hash| RecoveryCode {
+                account_id,
+                code_hash,
+                used_at: None,
+            }));
+    }
+}
+
+#[async_trait]
+impl MfaRepository for MemoryRepository {
+    async fn store_totp_secret(&self, account_id: ID, secret: String, now: i64) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        match tables.totp_mut(account_id) {
+            Some(totp) if totp.confirmed => Err(AppError::conflict(
+                "Two-factor authentication is already enabled.",
+            )),
+            Some(totp) => {
+                totp.secret = secret;
+                totp.created_at = now;
+                Ok(())
+            }
+            None => {
+                tables.account_totp.push(DB_AccountTotp {
+                    account_id,
+                    secret,
+                    confirmed: false,
+                    last_used_step: None,
+                    created_at: now,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    async fn fetch_totp(&self, account_id: ID) -> AppResult<Option<AccountTotp>> {
+        let tables = self.tables();
+
+        Ok(tables
+            .account_totp
+            .iter()
+            .find(|t| t.account_id == account_id)
+            .map(|t| AccountTotp {
+                account_id: t.account_id,
+                secret: t.secret.clone(),
+                confirmed: t.confirmed,
+                last_used_step: t.last_used_step.map(|s| s as u64),
+            }))
+    }
+
+    async fn use_totp_step(&self, account_id: ID, step: i64) -> AppResult<bool> {
+        let mut tables = self.tables();
+
+        match tables.totp_mut(account_id) {
+            Some(totp) if totp.last_used_step.is_none_or(|last| last < step) => {
+                totp.last_used_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn confirm_totp(
+        &self,
+        account_id: ID,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if let Some(totp) = tables.totp_mut(account_id) {
+            totp.confirmed = true;
+            totp.last_used_step = Some(step);
+        }
+        tables.replace_recovery_codes(account_id, recovery_code_hashes);
+
+        Ok(())
+    }
+
+    async fn delete_totp(&self, account_id: ID) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        tables.account_totp.retain(|t| t.account_id != account_id);
+        tables.replace_recovery_codes(account_id, Vec::new());
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        account_id: ID,
+        code_hashes: Vec<String>,
+    ) -> AppResult<()> {
+        self.tables()
+            .replace_recovery_codes(account_id, code_hashes);
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &self,
+        account_id: ID,
+        code_hash: String,
+        now: i64,
+    ) -> AppResult<bool> {
+        let mut tables = self.tables();
+
+        let mut used = false;
+        for code in tables.recovery_codes.iter_mut().filter(|c| {
+            c.account_id == account_id && c.code_hash == code_hash && c.used_at.is_none()
+        }) {
+            code.used_at = Some(now);
+            used = true;
+        }
+
+        Ok(used)
+    }
+}
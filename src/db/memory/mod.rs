@@ -0,0 +1,165 @@
+//! Repositories kept in the memory of the process, for development and tests.
+//!
+//! Tables mirror the Postgres schema and every operation holds the lock of the whole store, so
+//! operations are as atomic as the transactions of the Postgres repositories.
+
+use std::sync::{Mutex, MutexGuard};
+
+use crate::auth::{
+    PERMISSION_ACCOUNTS_READ, PERMISSION_ACCOUNTS_WRITE, PERMISSION_FEES_READ,
+    PERMISSION_FEES_WRITE, PERMISSION_PAYMENTS_READ, PERMISSION_PAYMENTS_REVERSE,
+    PERMISSION_PAYMENTS_WRITE, PERMISSION_ROLES_READ, PERMISSION_ROLES_WRITE, ROLE_ADMIN,
+    ROLE_TREASURER,
+};
+
+use super::types::{
+    DB_Account, DB_AccountTotp, DB_MonthlyFee, DB_Payment, DB_PaymentReversal, DB_PrePayment,
+    DB_RefreshToken, DB_Role, ID,
+};
+
+mod account;
+mod balance;
+mod fee;
+mod health;
+mod mfa;
+mod payment;
+mod role;
+mod token;
+
+/// Permissions of the built-in `Treasurer` role, as seeded by migrations/0002.
+const TREASURER_PERMISSIONS: [&str; 5] = [
+    PERMISSION_ACCOUNTS_READ,
+    PERMISSION_FEES_READ,
+    PERMISSION_PAYMENTS_READ,
+    PERMISSION_PAYMENTS_WRITE,
+    PERMISSION_PAYMENTS_REVERSE,
+];
+
+#[derive(Clone)]
+struct PasswordResetToken {
+    token_hash: String,
+    account_id: ID,
+    expires_at: i64,
+    used_at: Option<i64>,
+}
+
+#[derive(Clone)]
+struct RecoveryCode {
+    account_id: ID,
+    code_hash: String,
+    used_at: Option<i64>,
+}
+
+#[derive(Default)]
+struct Tables {
+    /// Last id handed out, ids are unique across the tables.
+    last_id: ID,
+    accounts: Vec<DB_Account>,
+    roles: Vec<DB_Role>,
+    /// `(account_id, role_id)`
+    account_roles: Vec<(ID, ID)>,
+    /// `(id, permission)`
+    permissions: Vec<(ID, String)>,
+    /// `(role_id, permission_id)`
+    role_permissions: Vec<(ID, ID)>,
+    monthly_fees: Vec<DB_MonthlyFee>,
+    payments: Vec<DB_Payment>,
+    payment_reversals: Vec<DB_PaymentReversal>,
+    pre_payments: Vec<DB_PrePayment>,
+    refresh_tokens: Vec<DB_RefreshToken>,
+    password_reset_tokens: Vec<PasswordResetToken>,
+    account_totp: Vec<DB_AccountTotp>,
+    recovery_codes: Vec<RecoveryCode>,
+}
+
+impl Tables {
+    fn next_id(&mut self) -> ID {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn role_id(&self, role: &str) -> Option<ID> {
+        self.roles.iter().find(|r| r.role == role).map(|r| r.id)
+    }
+
+    fn has_role(&self, account_id: ID, role: &str) -> bool {
+        self.role_id(role)
+            .is_some_and(|role_id| self.account_roles.contains(&(account_id, role_id)))
+    }
+}
+
+/// Repository keeping every table in memory. Starts with the built-in roles and permissions,
+/// like a freshly migrated database.
+pub struct MemoryRepository {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> MemoryRepository {
+        let mut tables = Tables::default();
+
+        for role in [ROLE_ADMIN, ROLE_TREASURER] {
+            let id = tables.next_id();
+            tables.roles.push(DB_Role {
+                id,
+                role: role.to_string(),
+            });
+        }
+        for permission in [
+            PERMISSION_ACCOUNTS_READ,
+            PERMISSION_ACCOUNTS_WRITE,
+            PERMISSION_ROLES_READ,
+            PERMISSION_ROLES_WRITE,
+            PERMISSION_FEES_READ,
+            PERMISSION_FEES_WRITE,
+            PERMISSION_PAYMENTS_READ,
+            PERMISSION_PAYMENTS_WRITE,
+            PERMISSION_PAYMENTS_REVERSE,
+        ] {
+            let id = tables.next_id();
+            tables.permissions.push((id, permission.to_string()));
+        }
+        let treasurer = tables.role_id(ROLE_TREASURER).unwrap();
+        for (id, permission) in &tables.permissions {
+            if TREASURER_PERMISSIONS.contains(&permission.as_str()) {
+                tables.role_permissions.push((treasurer, *id));
+            }
+        }
+
+        MemoryRepository {
+            tables: Mutex::new(tables),
+        }
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap()
+    }
+
+    /// Adds an account holding the given roles, e.g. the first admin. Returns the id of the
+    /// account, or `None` if the phone number or the email is taken or a role does not exist.
+    pub fn seed_account(&self, mut account: DB_Account, roles: &[&str]) -> Option<ID> {
+        let mut tables = self.tables();
+
+        let taken = tables
+            .accounts
+            .iter()
+            .any(|a| a.email == account.email || a.phone_number == account.phone_number);
+        let role_ids: Option<Vec<ID>> = roles.iter().map(|r| tables.role_id(r)).collect();
+        let role_ids = role_ids.filter(|_| !taken)?;
+
+        account.id = tables.next_id();
+        for role_id in role_ids {
+            tables.account_roles.push((account.id, role_id));
+        }
+        let id = account.id;
+        tables.accounts.push(account);
+
+        Some(id)
+    }
+}
+
+impl Default for MemoryRepository {
+    fn default() -> Self {
+        MemoryRepository::new()
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::{
+    db::{
+        repository::PaymentRepository,
+        types::{
+            DB_AccountPeriodPayment, DB_MonthlyFee, DB_Payment, DB_PaymentReversal,
+            DB_PeriodPayment, DB_PrePayment, ID,
+        },
+    },
+    error::{AppError, AppResult},
+    money::Money,
+    service::{
+        Payments, PrePaymentReport,
+        payment::{
+            PRE_PAYMENT_APPLIED, PaymentKind, PaymentPlan, PeriodPayment, into_account_payment,
+            into_payments_with_reversals, into_pre_payments_with_reversals, plan_payments,
+            reversal_amount,
+        },
+    },
+    util::unix_timestamp,
+};
+
+use super::{MemoryRepository, Tables};
+
+impl Tables {
+    fn fee(&self, fee_id: ID) -> Option<&DB_MonthlyFee> {
+        self.monthly_fees.iter().find(|f| f.id == fee_id)
+    }
+
+    /// Payment joined with the period of its fee.
+    fn period_payment(&self, payment: &DB_Payment) -> DB_AccountPeriodPayment {
+        let fee = self.fee(payment.fee_id).unwrap();
+
+        DB_AccountPeriodPayment {
+            account_id: payment.account_id,
+            payment: DB_PeriodPayment {
+                id: payment.id,
+                year: fee.year,
+                month: fee.month,
+                amount: payment.amount,
+                currency: payment.currency.clone(),
+                paid_at: payment.paid_at,
+            },
+        }
+    }
+
+    /// Pre-payments not applied yet, with what is left of them after their reversals.
+    fn net_pre_payments(&self) -> Vec<DB_AccountPeriodPayment> {
+        self.pre_payments
+            .iter()
+            .filter(|p| p.applied_payment_id.is_none())
+            .map(|p| DB_AccountPeriodPayment {
+                account_id: p.account_id,
+                payment: DB_PeriodPayment {
+                    amount: self.pre_payment_left(p),
+                    ..p.payment.clone()
+                },
+            })
+            .collect()
+    }
+
+    /// Turns what is left of the pre-payments of the periods of the given fees, or of all fees,
+    /// into `payments`.
+    ///
+    /// Pre-payments are applied oldest first as long as they fit in what is outstanding for the
+    /// fee and are in its currency, the others are left in place and reported as orphaned.
+    /// Applied pre-payments are marked with the payment they became.
+    pub(super) fn apply_pre_payments(
+        &mut self,
+        fee_ids: Option<&[ID]>,
+    ) -> AppResult<PrePaymentReport> {
+        let fees: Vec<DB_MonthlyFee> = self
+            .monthly_fees
+            .iter()
+            .filter(|f| fee_ids.is_none_or(|ids| ids.contains(&f.id)))
+            .cloned()
+            .collect();
+
+        let mut candidates: Vec<DB_AccountPeriodPayment> = self
+            .net_pre_payments()
+            .into_iter()
+            .filter(|p| {
+                p.payment.amount > 0
+                    && fees.iter().any(|f| {
+                        f.year == p.payment.year
+                            && f.month == p.payment.month
+                            && f.currency == p.payment.currency
+                    })
+            })
+            .collect();
+        candidates.sort_by_key(|p| (p.payment.paid_at, p.payment.id));
+
+        // running sum of the candidates of each (account, fee), oldest first
+        let mut running: HashMap<(ID, ID), i64> = HashMap::new();
+        let mut moved: Vec<(ID, DB_Payment)> = Vec::new();
+        for candidate in candidates {
+            let fee = fees
+                .iter()
+                .find(|f| f.year == candidate.payment.year && f.month == candidate.payment.month)
+                .unwrap();
+            let sum = running.entry((candidate.account_id, fee.id)).or_insert(0);
+            *sum += candidate.payment.amount;
+
+            if fee.amount - self.paid(candidate.account_id, fee.id) - *sum >= 0 {
+                moved.push((
+                    candidate.payment.id,
+                    DB_Payment {
+                        id: 0,
+                        account_id: candidate.account_id,
+                        fee_id: fee.id,
+                        amount: candidate.payment.amount,
+                        currency: candidate.payment.currency,
+                        paid_at: candidate.payment.paid_at,
+                    },
+                ));
+            }
+        }
+
+        let applied_at = unix_timestamp() as i64;
+        let mut applied = Vec::new();
+        for (pre_payment_id, mut payment) in moved {
+            payment.id = self.next_id();
+            if let Some(p) = self
+                .pre_payments
+                .iter_mut()
+                .find(|p| p.payment.id == pre_payment_id)
+            {
+                p.applied_payment_id = Some(payment.id);
+                p.applied_at = Some(applied_at);
+            }
+            applied.push(self.period_payment(&payment));
+            self.payments.push(payment);
+        }
+        applied.sort_by_key(|p| (p.payment.year, p.payment.month, p.account_id));
+
+        let mut orphaned: Vec<DB_AccountPeriodPayment> = self
+            .net_pre_payments()
+            .into_iter()
+            .filter(|p| {
+                p.payment.amount > 0
+                    && fees
+                        .iter()
+                        .any(|f| f.year == p.payment.year && f.month == p.payment.month)
+            })
+            .collect();
+        orphaned.sort_by_key(|p| (p.payment.year, p.payment.month, p.account_id));
+
+        Ok(PrePaymentReport {
+            applied: applied
+                .into_iter()
+                .map(into_account_payment)
+                .collect::<Result<_, _>>()?,
+            orphaned: orphaned
+                .into_iter()
+                .map(into_account_payment)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[async_trait]
+impl PaymentRepository for MemoryRepository {
+    async fn fetch_account_payments(&self, account_id: ID) -> AppResult<Payments> {
+        let tables = self.tables();
+
+        let mut made: Vec<DB_PeriodPayment> = tables
+            .payments
+            .iter()
+            .filter(|p| p.account_id == account_id)
+            .map(|p| tables.period_payment(p).payment)
+            .collect();
+        made.sort_by_key(|p| (p.year, p.month, p.paid_at));
+
+        let mut precovered: Vec<DB_PrePayment> = tables
+            .pre_payments
+            .iter()
+            .filter(|p| p.account_id == account_id)
+            .cloned()
+            .collect();
+        precovered.sort_by_key(|p| (p.payment.year, p.payment.month, p.payment.paid_at));
+
+        Ok(Payments {
+            made: into_payments_with_reversals(made, &tables.payment_reversals)?,
+            precovered: into_pre_payments_with_reversals(precovered, &tables.payment_reversals)?,
+        })
+    }
+
+    async fn record_payments(
+        &self,
+        account_id: ID,
+        periods: Vec<PeriodPayment>,
+    ) -> AppResult<Payments> {
+        let mut tables = self.tables();
+        let paid_at = unix_timestamp() as i64;
+
+        let fees: Vec<DB_MonthlyFee> = tables
+            .monthly_fees
+            .iter()
+            .filter(|f| {
+                periods
+                    .iter()
+                    .any(|p| p.year == f.year && p.month == f.month)
+            })
+            .cloned()
+            .collect();
+        let paid: HashMap<ID, i64> = fees
+            .iter()
+            .map(|f| (f.id, tables.paid(account_id, f.id)))
+            .collect();
+
+        let PaymentPlan {
+            fee_payments,
+            pre_payments,
+        } = plan_payments(periods, &fees, &paid)?;
+
+        let mut made = Vec::new();
+        for (fee_id, amount) in fee_payments {
+            let payment = DB_Payment {
+                id: tables.next_id(),
+                account_id,
+                fee_id,
+                amount: amount.minor_units,
+                currency: amount.currency.code().to_string(),
+                paid_at,
+            };
+            made.push(tables.period_payment(&payment).payment);
+            tables.payments.push(payment);
+        }
+        made.sort_by_key(|p| (p.year, p.month));
+
+        let mut precovered = Vec::new();
+        for (year, month, amount) in pre_payments {
+            let pre_payment = DB_PrePayment {
+                account_id,
+                payment: DB_PeriodPayment {
+                    id: tables.next_id(),
+                    year,
+                    month,
+                    amount: amount.minor_units,
+                    currency: amount.currency.code().to_string(),
+                    paid_at,
+                },
+                applied_payment_id: None,
+                applied_at: None,
+            };
+            precovered.push(pre_payment.clone());
+            tables.pre_payments.push(pre_payment);
+        }
+
+        Ok(Payments {
+            made: into_payments_with_reversals(made, &[])?,
+            precovered: into_pre_payments_with_reversals(precovered, &[])?,
+        })
+    }
+
+    async fn reverse_payment(
+        &self,
+        account_id: ID,
+        kind: PaymentKind,
+        payment_id: ID,
+        amount: Option<Money>,
+        reversed_by: ID,
+    ) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        let payment = match kind {
+            PaymentKind::Payment => tables
+                .payments
+                .iter()
+                .find(|p| p.id == payment_id && p.account_id == account_id)
+                .map(|p| (tables.left(p), p.currency.clone(), false)),
+            PaymentKind::PrePayment => tables
+                .pre_payments
+                .iter()
+                .find(|p| p.payment.id == payment_id && p.account_id == account_id)
+                .map(|p| {
+                    (
+                        tables.pre_payment_left(p),
+                        p.payment.currency.clone(),
+                        p.applied_payment_id.is_some(),
+                    )
+                }),
+        };
+        let Some((left, currency, applied)) = payment else {
+            return Err(AppError::not_found("Payment not found."));
+        };
+        if applied {
+            return Err(AppError::conflict(PRE_PAYMENT_APPLIED));
+        }
+
+        let left = Money::from_db(left, &currency)?;
+        let amount = reversal_amount(left, amount)?;
+
+        let (reversed_payment_id, reversed_pre_payment_id) = match kind {
+            PaymentKind::Payment => (Some(payment_id), None),
+            PaymentKind::PrePayment => (None, Some(payment_id)),
+        };
+        let reversal = DB_PaymentReversal {
+            id: tables.next_id(),
+            payment_id: reversed_payment_id,
+            pre_payment_id: reversed_pre_payment_id,
+            amount: amount.minor_units,
+            reversed_by,
+            reversed_at: unix_timestamp() as i64,
+        };
+        tables.payment_reversals.push(reversal);
+
+        Ok(())
+    }
+
+    async fn apply_pre_payments(&self) -> AppResult<PrePaymentReport> {
+        self.tables().apply_pre_payments(None)
+    }
+}
@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+
+use crate::{
+    auth::ROLE_ADMIN,
+    db::{repository::RoleRepository, types::ID},
+    error::{AppError, AppResult},
+    service::{
+        Role,
+        role::{BUILT_IN_ROLE, BUILT_IN_ROLES, LAST_ADMIN, ROLE_NOT_FOUND, into_role},
+    },
+};
+
+use super::{MemoryRepository, Tables};
+
+impl Tables {
+    fn admin_exists(&self) -> bool {
+        self.role_id(ROLE_ADMIN)
+            .is_some_and(|admin| self.account_roles.iter().any(|(_, r)| *r == admin))
+    }
+
+    /// Applies the change to the roles of the account, unless it takes the `Admin` role from the
+    /// last account holding it.
+    fn change_account_roles(
+        &mut self,
+        account_id: ID,
+        change: impl FnOnce(&mut Vec<(ID, ID)>),
+    ) -> AppResult<()> {
+        let was_admin = self.has_role(account_id, ROLE_ADMIN);
+        let previous = self.account_roles.clone();
+
+        change(&mut self.account_roles);
+
+        if was_admin && !self.admin_exists() {
+            self.account_roles = previous;
+            return Err(AppError::conflict(LAST_ADMIN));
+        }
+
+        Ok(())
+    }
+
+    /// Why a role that was left unchanged could not be changed.
+    fn built_in_or_not_found(&self, role_id: ID) -> AppError {
+        match self.roles.iter().any(|r| r.id == role_id) {
+            true => AppError::conflict(BUILT_IN_ROLE),
+            false => AppError::not_found(ROLE_NOT_FOUND),
+        }
+    }
+
+    fn permission_names(&self, role_ids: &[ID]) -> Vec<String> {
+        let mut permissions: Vec<String> = self
+            .permissions
+            .iter()
+            .filter(|(id, _)| {
+                self.role_permissions
+                    .iter()
+                    .any(|(role_id, permission_id)| {
+                        permission_id == id && role_ids.contains(role_id)
+                    })
+            })
+            .map(|(_, permission)| permission.clone())
+            .collect();
+        permissions.sort();
+
+        permissions
+    }
+}
+
+#[async_trait]
+impl RoleRepository for MemoryRepository {
+    async fn fetch_roles(&self) -> AppResult<Vec<Role>> {
+        let tables = self.tables();
+
+        let mut roles = tables.roles.clone();
+        roles.sort_by(|a, b| a.role.cmp(&b.role));
+
+        Ok(roles.into_iter().map(into_role).collect())
+    }
+
+    async fn fetch_role(&self, role_id: ID) -> AppResult<Option<Role>> {
+        let tables = self.tables();
+
+        Ok(tables
+            .roles
+            .iter()
+            .find(|r| r.id == role_id)
+            .cloned()
+            .map(into_role))
+    }
+
+    async fn rename_role(&self, role_id: ID, role: String) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if tables
+            .roles
+            .iter()
+            .any(|r| r.role == role && r.id != role_id)
+        {
+            return Err(match tables.roles.iter().find(|r| r.id == role_id) {
+                Some(r) if !BUILT_IN_ROLES.contains(&r.role.as_str()) => {
+                    AppError::conflict("Duplicate role.")
+                }
+                _ => tables.built_in_or_not_found(role_id),
+            });
+        }
+
+        match tables
+            .roles
+            .iter_mut()
+            .find(|r| r.id == role_id && !BUILT_IN_ROLES.contains(&r.role.as_str()))
+        {
+            Some(r) => {
+                r.role = role;
+                Ok(())
+            }
+            None => Err(tables.built_in_or_not_found(role_id)),
+        }
+    }
+
+    async fn delete_role(&self, role_id: ID) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        let Some(index) = tables
+            .roles
+            .iter()
+            .position(|r| r.id == role_id && !BUILT_IN_ROLES.contains(&r.role.as_str()))
+        else {
+            return Err(tables.built_in_or_not_found(role_id));
+        };
+
+        tables.roles.remove(index);
+        tables.account_roles.retain(|(_, r)| *r != role_id);
+        tables.role_permissions.retain(|(r, _)| *r != role_id);
+
+        Ok(())
+    }
+
+    async fn revoke_role_from_account(&self, account_id: ID, role_id: ID) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if !tables.account_roles.contains(&(account_id, role_id)) {
+            return Err(AppError::not_found("Account does not have this role."));
+        }
+
+        tables.change_account_roles(account_id, |account_roles| {
+            account_roles.retain(|link| *link != (account_id, role_id))
+        })
+    }
+
+    async fn replace_account_roles(&self, account_id: ID, role_ids: Vec<ID>) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        let existing = tables
+            .roles
+            .iter()
+            .filter(|r| role_ids.contains(&r.id))
+            .count();
+        if existing != role_ids.len() {
+            return Err(AppError::not_found(ROLE_NOT_FOUND));
+        }
+
+        tables.change_account_roles(account_id, |account_roles| {
+            account_roles.retain(|(a, _)| *a != account_id);
+            account_roles.extend(role_ids.iter().map(|r| (account_id, *r)));
+        })
+    }
+
+    async fn fetch_permissions(&self) -> AppResult<Vec<String>> {
+        let tables = self.tables();
+
+        let mut permissions: Vec<String> =
+            tables.permissions.iter().map(|(_, p)| p.clone()).collect();
+        permissions.sort();
+
+        Ok(permissions)
+    }
+
+    async fn fetch_role_permissions(&self, role_id: ID) -> AppResult<Vec<String>> {
+        Ok(self.tables().permission_names(&[role_id]))
+    }
+
+    async fn fetch_permissions_of_role_ids(&self, role_ids: Vec<ID>) -> AppResult<Vec<String>> {
+        Ok(self.tables().permission_names(&role_ids))
+    }
+
+    async fn replace_role_permissions(
+        &self,
+        role_id: ID,
+        permissions: Vec<String>,
+    ) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        let permission_ids: Vec<ID> = tables
+            .permissions
+            .iter()
+            .filter(|(_, p)| permissions.contains(p))
+            .map(|(id, _)| *id)
+            .collect();
+        if permission_ids.len() != permissions.len() {
+            return Err(AppError::validation("Unknown permission."));
+        }
+
+        tables.role_permissions.retain(|(r, _)| *r != role_id);
+        tables
+            .role_permissions
+            .extend(permission_ids.into_iter().map(|p| (role_id, p)));
+
+        Ok(())
+    }
+}
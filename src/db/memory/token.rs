@@ -0,0 +1,151 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        repository::TokenRepository,
+        types::{DB_RefreshToken, ID},
+    },
+    error::AppResult,
+    service::RefreshToken,
+};
+
+use super::{MemoryRepository, PasswordResetToken};
+
+#[async_trait]
+impl TokenRepository for MemoryRepository {
+    async fn store_refresh_token(&self, token: DB_RefreshToken) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if tables.refresh_tokens.iter().any(|t| t.jti == token.jti) {
+            return Err(anyhow::anyhow!("refresh token {} already exists", token.jti).into());
+        }
+        tables.refresh_tokens.push(DB_RefreshToken {
+            rotated_at: None,
+            revoked_at: None,
+            ..token
+        });
+
+        Ok(())
+    }
+
+    async fn fetch_refresh_token(&self, jti: Uuid) -> AppResult<Option<RefreshToken>> {
+        let tables = self.tables();
+
+        Ok(tables
+            .refresh_tokens
+            .iter()
+            .find(|t| t.jti == jti)
+            .map(|t| RefreshToken {
+                jti: t.jti,
+                family_id: t.family_id,
+                account_id: t.account_id,
+                token_hash: t.token_hash.clone(),
+                issued_at: t.issued_at as u64,
+                expires_at: t.expires_at as u64,
+                rotated_at: t.rotated_at.map(|ts| ts as u64),
+                revoked_at: t.revoked_at.map(|ts| ts as u64),
+            }))
+    }
+
+    async fn rotate_refresh_token(&self, jti: Uuid, now: i64) -> AppResult<bool> {
+        let mut tables = self.tables();
+
+        match tables
+            .refresh_tokens
+            .iter_mut()
+            .find(|t| t.jti == jti && t.rotated_at.is_none() && t.revoked_at.is_none())
+        {
+            Some(token) => {
+                token.rotated_at = Some(now);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn revoke_refresh_token_family(&self, family_id: Uuid, now: i64) -> AppResult<u64> {
+        let mut tables = self.tables();
+
+        let mut revoked = 0;
+        for token in tables
+            .refresh_tokens
+            .iter_mut()
+            .filter(|t| t.family_id == family_id && t.revoked_at.is_none())
+        {
+            token.revoked_at = Some(now);
+            revoked += 1;
+        }
+
+        Ok(revoked)
+    }
+
+    async fn revoke_account_refresh_tokens(&self, account_id: ID, now: i64) -> AppResult<u64> {
+        let mut tables = self.tables();
+
+        let mut revoked = 0;
+        for token in tables
+            .refresh_tokens
+            .iter_mut()
+            .filter(|t| t.account_id == account_id && t.revoked_at.is_none())
+        {
+            token.revoked_at = Some(now);
+            revoked += 1;
+        }
+
+        Ok(revoked)
+    }
+
+    async fn delete_expired_refresh_tokens(&self, account_id: ID, now: i64) -> AppResult<u64> {
+        let mut tables = self.tables();
+
+        let before = tables.refresh_tokens.len();
+        tables
+            .refresh_tokens
+            .retain(|t| t.account_id != account_id || t.expires_at >= now);
+
+        Ok((before - tables.refresh_tokens.len()) as u64)
+    }
+
+    async fn store_password_reset_token(
+        &self,
+        token_hash: String,
+        account_id: ID,
+        expires_at: i64,
+    ) -> AppResult<()> {
+        let mut tables = self.tables();
+
+        if tables
+            .password_reset_tokens
+            .iter()
+            .any(|t| t.token_hash == token_hash)
+        {
+            return Err(anyhow::anyhow!("password reset token already exists").into());
+        }
+        tables.password_reset_tokens.push(PasswordResetToken {
+            token_hash,
+            account_id,
+            expires_at,
+            used_at: None,
+        });
+
+        Ok(())
+    }
+
+    async fn consume_password_reset_token(
+        &self,
+        token_hash: String,
+        now: i64,
+    ) -> AppResult<Option<ID>> {
+        let mut tables = self.tables();
+
+        Ok(tables
+            .password_reset_tokens
+            .iter_mut()
+            .find(|t| t.token_hash == token_hash && t.used_at.is_none() && t.expires_at > now)
+            .map(|token| {
+                token.used_at = Some(now);
+                token.account_id
+            }))
+    }
+}
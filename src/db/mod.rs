@@ -1,11 +1,21 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use sqlx::postgres::PgPoolOptions;
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions};
 
+use repository::Repository;
+
+pub mod memory;
+mod postgres;
+pub mod repository;
 pub mod types;
 
+/// Migrations under `migrations/`, embedded in the binary.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
 pub type DatabaseConnectionResource = sqlx::Pool<sqlx::Postgres>;
-pub type DatabaseConnection = DatabaseConnectionResource;
+
+/// Storage behind the services, either the Postgres pool or a [`memory::MemoryRepository`].
+pub type DatabaseConnection = Arc<dyn Repository>;
 
 pub trait IntoDbConnectionString {
     fn into_db_connection_string(self) -> String;
@@ -40,11 +50,31 @@ impl IntoDbConnectionString for DbConnectionParameters {
     }
 }
 
+pub struct DbPoolParameters {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a query waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+    /// Connections idle for longer are closed, down to `min_connections`.
+    pub idle_timeout: Duration,
+}
+
 pub async fn init_db_connection(
     connect: impl IntoDbConnectionString,
-) -> anyhow::Result<DatabaseConnection> {
+    pool: &DbPoolParameters,
+) -> anyhow::Result<DatabaseConnectionResource> {
     Ok(PgPoolOptions::new()
-        .max_connections(5)
+        .max_connections(pool.max_connections)
+        .min_connections(pool.min_connections)
+        .acquire_timeout(pool.acquire_timeout)
+        .idle_timeout(pool.idle_timeout)
         .connect(&connect.into_db_connection_string())
         .await?)
 }
+
+/// Applies the migrations under `migrations/` that the database has not seen yet.
+pub async fn run_migrations(db: &DatabaseConnectionResource) -> anyhow::Result<()> {
+    MIGRATOR.run(db).await?;
+
+    Ok(())
+}
@@ -0,0 +1,417 @@
+use async_trait::async_trait;
+use sqlx::Connection;
+
+use crate::{
+    auth::ROLE_ADMIN,
+    db::{
+        DatabaseConnectionResource,
+        repository::AccountRepository,
+        types::{DB_Account, DB_AccountListing, DB_Role, ID},
+    },
+    error::{AppError, AppResult},
+    service::{
+        Account, AccountCursor, AccountPage, Role,
+        account::{AccountFilter, AccountOrder, into_account, into_account_page},
+        role::into_role,
+    },
+};
+
+use super::{
+    acquire,
+    balance::OVERDUE_PERIODS,
+    role::{admin_exists, lock_admin_role},
+};
+
+#[async_trait]
+impl AccountRepository for DatabaseConnectionResource {
+    async fn create_account(&self, account: DB_Account) -> AppResult<()> {
+        match sqlx::query(
+            r"
+                INSERT INTO
+                accounts(
+                    phone_number, name, lastname, email, hashed_password, password_set_ts,
+                    member_since
+                )
+                VALUES (
+                    $1, $2, $3, $4, $5, $6, $7
+                );
+            ",
+        )
+        .bind(&account.phone_number)
+        .bind(&account.name)
+        .bind(&account.lastname)
+        .bind(&account.email)
+        .bind(&account.hashed_password)
+        .bind(account.password_set_ts)
+        .bind(account.member_since)
+        .execute(&mut *acquire(self).await?)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Err(
+                AppError::conflict("Phone number or email is already used by another account."),
+            ),
+            Err(err) => Err(err)?,
+        }
+    }
+
+    async fn create_admin_account(&self, account: DB_Account) -> AppResult<bool> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        lock_admin_role(&mut tx).await?;
+        if admin_exists(&mut tx).await? {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        let account_id: (ID,) = match sqlx::query_as(
+            r"
+                INSERT INTO
+                accounts(
+                    phone_number, name, lastname, email, hashed_password, password_set_ts,
+                    member_since
+                )
+                VALUES (
+                    $1, $2, $3, $4, $5, $6, $7
+                )
+                RETURNING id;
+            ",
+        )
+        .bind(&account.phone_number)
+        .bind(&account.name)
+        .bind(&account.lastname)
+        .bind(&account.email)
+        .bind(&account.hashed_password)
+        .bind(account.password_set_ts)
+        .bind(account.member_since)
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(account_id) => account_id,
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                tx.rollback().await?;
+                return Err(AppError::conflict(
+                    "Phone number or email is already used by another account.",
+                ));
+            }
+            Err(err) => Err(err)?,
+        };
+
+        sqlx::query(
+            r"
+                INSERT INTO account_roles(account_id, role_id)
+                SELECT $1, id FROM roles
+                WHERE role = $2;
+            ",
+        )
+        .bind(account_id.0)
+        .bind(ROLE_ADMIN)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn create_roles(&self, roles: Vec<String>) -> AppResult<()> {
+        match sqlx::query(
+            r"
+                INSERT INTO
+                roles(
+                    role
+                )
+                SELECT * FROM UNNEST(
+                    $1::text[]
+                );
+            ",
+        )
+        .bind(&roles)
+        .execute(&mut *acquire(self).await?)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                Err(AppError::conflict("Duplicate role."))
+            }
+            Err(err) => Err(err)?,
+        }
+    }
+
+    async fn add_roles_to_account(&self, account_id: ID, role_ids: Vec<ID>) -> AppResult<()> {
+        match sqlx::query(
+            r"
+                INSERT INTO
+                account_roles(
+                    account_id, role_id
+                )
+                SELECT * FROM UNNEST(
+                    $1::int[], $2::int[]
+                );
+            ",
+        )
+        .bind((0..role_ids.len()).map(|_| account_id).collect::<Vec<_>>())
+        .bind(&role_ids)
+        .execute(&mut *acquire(self).await?)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                Err(AppError::conflict("Account already has the role."))
+            }
+            Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+                Err(AppError::not_found("Account or role not found."))
+            }
+            Err(err) => Err(err)?,
+        }
+    }
+
+    async fn fetch_account_by_email(&self, email: String) -> AppResult<Option<Account>> {
+        let account: Option<DB_Account> = match sqlx::query_as(
+            r"
+                SELECT * FROM accounts
+                WHERE email = $1;
+            ",
+        )
+        .bind(email)
+        .fetch_one(&mut *acquire(self).await?)
+        .await
+        {
+            Ok(a) => Some(a),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(err) => Err(err)?,
+        };
+
+        Ok(account.map(into_account))
+    }
+
+    async fn fetch_account_by_phone_number(
+        &self,
+        phone_number: String,
+    ) -> AppResult<Option<Account>> {
+        let account: Option<DB_Account> = match sqlx::query_as(
+            r"
+                SELECT * FROM accounts
+                WHERE phone_number = $1;
+            ",
+        )
+        .bind(phone_number)
+        .fetch_one(&mut *acquire(self).await?)
+        .await
+        {
+            Ok(a) => Some(a),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(err) => Err(err)?,
+        };
+
+        Ok(account.map(into_account))
+    }
+
+    async fn fetch_account_by_id(&self, account_id: ID) -> AppResult<Option<Account>> {
+        let account: Option<DB_Account> = sqlx::query_as(
+            r"
+                SELECT * FROM accounts
+                WHERE id = $1;
+            ",
+        )
+        .bind(account_id)
+        .fetch_optional(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(account.map(into_account))
+    }
+
+    async fn fetch_account_roles(&self, account_id: ID) -> AppResult<Vec<Role>> {
+        let roles: Vec<DB_Role> = sqlx::query_as(
+            r"
+                SELECT
+                    roles.id,
+                    roles.role
+                FROM account_roles
+                JOIN roles ON roles.id = account_roles.role_id
+                    AND account_roles.account_id = $1;
+            ",
+        )
+        .bind(account_id)
+        .fetch_all(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(roles.into_iter().map(into_role).collect())
+    }
+
+    async fn update_password(
+        &self,
+        account_id: ID,
+        hashed_password: String,
+        password_set_ts: i64,
+    ) -> AppResult<()> {
+        let result = sqlx::query(
+            r"
+                UPDATE accounts
+                SET hashed_password = $2,
+                    password_set_ts = $3
+                WHERE id = $1;
+            ",
+        )
+        .bind(account_id)
+        .bind(&hashed_password)
+        .bind(password_set_ts)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        match result.rows_affected() {
+            1 => Ok(()),
+            _ => Err(AppError::not_found("Account not found.")),
+        }
+    }
+
+    async fn update_account_profile(
+        &self,
+        account_id: ID,
+        name: String,
+        lastname: String,
+        phone_number: String,
+        email: String,
+    ) -> AppResult<()> {
+        match sqlx::query(
+            r"
+                UPDATE accounts
+                SET name = $2,
+                    lastname = $3,
+                    phone_number = $4,
+                    email = $5
+                WHERE id = $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM accounts
+                        WHERE email = $5
+                            AND id <> $1
+                    );
+            ",
+        )
+        .bind(account_id)
+        .bind(&name)
+        .bind(&lastname)
+        .bind(&phone_number)
+        .bind(&email)
+        .execute(&mut *acquire(self).await?)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => Err(AppError::conflict(
+                "Phone number or email is already used by another account.",
+            )),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Err(
+                AppError::conflict("Phone number or email is already used by another account."),
+            ),
+            Err(err) => Err(err)?,
+        }
+    }
+
+    async fn fetch_accounts(
+        &self,
+        filter: AccountFilter,
+        order: AccountOrder,
+        ascending: bool,
+        after: Option<AccountCursor>,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<AccountPage> {
+        let sort_key = match order {
+            AccountOrder::Id => "''",
+            AccountOrder::Name => "lower(accounts.name)",
+            AccountOrder::Lastname => "lower(accounts.lastname)",
+            AccountOrder::Email => "lower(accounts.email)",
+        };
+        let (direction, comparison) = match ascending {
+            true => ("ASC", ">"),
+            false => ("DESC", "<"),
+        };
+
+        let filter_condition = r"
+            NOT EXISTS (
+                SELECT 1 FROM UNNEST($1::text[]) AS term
+                WHERE concat_ws(' ', accounts.name, accounts.lastname, accounts.email, accounts.phone_number)
+                    NOT ILIKE '%' || term || '%'
+            )
+            AND (
+                $2::text IS NULL OR EXISTS (
+                    SELECT 1 FROM account_roles
+                    JOIN roles ON roles.id = account_roles.role_id
+                    WHERE account_roles.account_id = accounts.id
+                        AND roles.role = $2
+                )
+            )
+            AND (
+                $3::bool IS NULL
+                OR (accounts.id IN (SELECT account_id FROM overdue WHERE outstanding > 0)) = $3
+            )
+        ";
+
+        let search: Vec<String> = filter.search.iter().map(|t| escape_like(t)).collect();
+
+        let total: (i64,) = sqlx::query_as(&format!(
+            r"
+                WITH overdue AS (
+                    {OVERDUE_PERIODS}
+                )
+                SELECT COUNT(*) FROM accounts
+                WHERE {filter_condition};
+            "
+        ))
+        .bind(&search)
+        .bind(&filter.role)
+        .bind(filter.in_arrears)
+        .fetch_one(&mut *acquire(self).await?)
+        .await?;
+
+        let accounts: Vec<DB_AccountListing> = sqlx::query_as(&format!(
+            r"
+                WITH overdue AS (
+                    {OVERDUE_PERIODS}
+                ),
+                listing AS (
+                    SELECT
+                        accounts.id,
+                        accounts.name,
+                        accounts.lastname,
+                        accounts.email,
+                        accounts.phone_number,
+                        ARRAY(
+                            SELECT roles.role FROM account_roles
+                            JOIN roles ON roles.id = account_roles.role_id
+                            WHERE account_roles.account_id = accounts.id
+                            ORDER BY roles.role
+                        ) AS roles,
+                        accounts.id IN (
+                            SELECT account_id FROM overdue WHERE outstanding > 0
+                        ) AS in_arrears,
+                        {sort_key} AS sort_key
+                    FROM accounts
+                    WHERE {filter_condition}
+                )
+                SELECT * FROM listing
+                WHERE $4::text IS NULL OR (sort_key, id) {comparison} ($4, $5)
+                ORDER BY sort_key {direction}, id {direction}
+                OFFSET $6 LIMIT $7;
+            "
+        ))
+        .bind(&search)
+        .bind(&filter.role)
+        .bind(filter.in_arrears)
+        .bind(after.as_ref().map(|c| &c.sort_key))
+        .bind(after.as_ref().map(|c| c.id))
+        .bind(offset)
+        .bind(count + 1)
+        .fetch_all(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(into_account_page(total.0 as u64, accounts, count))
+    }
+}
+
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -0,0 +1,134 @@
+use async_trait::async_trait;
+
+use crate::{
+    db::{
+        DatabaseConnectionResource,
+        repository::BalanceRepository,
+        types::{DB_Arrears, DB_PeriodStatement, ID},
+    },
+    error::AppResult,
+    service::{
+        Arrears, PeriodStatement,
+        balance::{ArrearsOrder, into_arrears, into_period_statement},
+    },
+};
+
+use super::acquire;
+
+/// Outstanding amount of every started fee period of every account since its membership started,
+/// to be used as a CTE.
+pub(super) const OVERDUE_PERIODS: &str = r"
+        SELECT
+            accounts.id AS account_id,
+            monthly_fees.year,
+            monthly_fees.month,
+            monthly_fees.currency,
+            monthly_fees.amount
+                - COALESCE(paid.amount, 0)
+                - COALESCE(precovered.amount, 0) AS outstanding
+        FROM accounts
+        CROSS JOIN monthly_fees
+        LEFT JOIN (
+            SELECT account_id, fee_id, SUM(amount) AS amount FROM net_payments
+            GROUP BY account_id, fee_id
+        ) AS paid ON paid.account_id = accounts.id
+            AND paid.fee_id = monthly_fees.id
+        LEFT JOIN (
+            SELECT account_id, year, month, currency, SUM(amount) AS amount
+            FROM net_pre_payments
+            GROUP BY account_id, year, month, currency
+        ) AS precovered ON precovered.account_id = accounts.id
+            AND precovered.year = monthly_fees.year
+            AND precovered.month = monthly_fees.month
+            AND precovered.currency = monthly_fees.currency
+        WHERE make_date(monthly_fees.year, monthly_fees.month, 1) <= CURRENT_DATE
+            AND make_date(monthly_fees.year, monthly_fees.month, 1)
+                >= date_trunc('month', to_timestamp(accounts.member_since) AT TIME ZONE 'UTC')
+";
+
+#[async_trait]
+impl BalanceRepository for DatabaseConnectionResource {
+    async fn fetch_period_statements(&self, account_id: ID) -> AppResult<Vec<PeriodStatement>> {
+        let periods: Vec<DB_PeriodStatement> = sqlx::query_as(
+            r"
+                SELECT
+                    monthly_fees.year,
+                    monthly_fees.month,
+                    monthly_fees.amount AS due,
+                    COALESCE(paid.amount, 0)::INT8 AS paid,
+                    COALESCE(precovered.amount, 0)::INT8 AS precovered,
+                    monthly_fees.currency,
+                    make_date(monthly_fees.year, monthly_fees.month, 1) <= CURRENT_DATE AS started
+                FROM monthly_fees
+                JOIN accounts ON accounts.id = $1
+                LEFT JOIN (
+                    SELECT fee_id, SUM(amount) AS amount FROM net_payments
+                    WHERE account_id = $1
+                    GROUP BY fee_id
+                ) AS paid ON paid.fee_id = monthly_fees.id
+                LEFT JOIN (
+                    SELECT year, month, currency, SUM(amount) AS amount FROM net_pre_payments
+                    WHERE account_id = $1
+                    GROUP BY year, month, currency
+                ) AS precovered ON precovered.year = monthly_fees.year
+                    AND precovered.month = monthly_fees.month
+                    AND precovered.currency = monthly_fees.currency
+                WHERE make_date(monthly_fees.year, monthly_fees.month, 1)
+                    >= date_trunc('month', to_timestamp(accounts.member_since) AT TIME ZONE 'UTC')
+                ORDER BY monthly_fees.year, monthly_fees.month;
+            ",
+        )
+        .bind(account_id)
+        .fetch_all(&mut *acquire(self).await?)
+        .await?;
+
+        periods.into_iter().map(into_period_statement).collect()
+    }
+
+    async fn fetch_arrears(
+        &self,
+        order: ArrearsOrder,
+        ascending: bool,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<Vec<Arrears>> {
+        let order_by = match (order, ascending) {
+            (ArrearsOrder::Amount, true) => "outstanding ASC, months_overdue ASC",
+            (ArrearsOrder::Amount, false) => "outstanding DESC, months_overdue DESC",
+            (ArrearsOrder::MonthsOverdue, true) => "months_overdue ASC, outstanding ASC",
+            (ArrearsOrder::MonthsOverdue, false) => "months_overdue DESC, outstanding DESC",
+        };
+
+        let query = format!(
+            r"
+                WITH overdue AS (
+                    {OVERDUE_PERIODS}
+                )
+                SELECT
+                    accounts.id AS account_id,
+                    accounts.name,
+                    accounts.lastname,
+                    accounts.email,
+                    overdue.currency,
+                    SUM(overdue.outstanding)::INT8 AS outstanding,
+                    COUNT(*)::INT8 AS months_overdue,
+                    MIN(overdue.year * 12 + overdue.month - 1) / 12 AS since_year,
+                    MIN(overdue.year * 12 + overdue.month - 1) % 12 + 1 AS since_month
+                FROM overdue
+                JOIN accounts ON accounts.id = overdue.account_id
+                WHERE overdue.outstanding > 0
+                GROUP BY accounts.id, overdue.currency
+                ORDER BY {order_by}, accounts.id
+                OFFSET $1 LIMIT $2;
+            "
+        );
+
+        let arrears: Vec<DB_Arrears> = sqlx::query_as(&query)
+            .bind(offset)
+            .bind(count)
+            .fetch_all(&mut *acquire(self).await?)
+            .await?;
+
+        arrears.into_iter().map(into_arrears).collect()
+    }
+}
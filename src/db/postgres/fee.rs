@@ -0,0 +1,247 @@
+use async_trait::async_trait;
+use sqlx::Connection;
+
+use crate::{
+    db::{
+        DatabaseConnectionResource,
+        repository::FeeRepository,
+        types::{DB_MonthlyFee, ID},
+    },
+    error::{AppError, AppResult},
+    money::Money,
+    service::{MonthlyFee, PrePaymentReport, fee::into_monthly_fee},
+};
+
+use super::{acquire, payment::apply_pre_payments_in};
+
+const PERIOD_TAKEN: &str = "Fee for this period already exists.";
+
+#[async_trait]
+impl FeeRepository for DatabaseConnectionResource {
+    async fn create_monthly_fee(
+        &self,
+        year: i32,
+        month: i32,
+        amount: Money,
+    ) -> AppResult<(MonthlyFee, PrePaymentReport)> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        let fee = match sqlx::query_as::<_, DB_MonthlyFee>(
+            r"
+                INSERT INTO
+                monthly_fees(
+                    year, month, amount, currency
+                )
+                VALUES (
+                    $1, $2, $3, $4
+                )
+                RETURNING *;
+            ",
+        )
+        .bind(year)
+        .bind(month)
+        .bind(amount.minor_units)
+        .bind(amount.currency.code())
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(fee) => into_monthly_fee(fee)?,
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                tx.rollback().await?;
+                return Err(AppError::conflict(PERIOD_TAKEN));
+            }
+            Err(err) => Err(err)?,
+        };
+
+        let report = apply_pre_payments_in(&mut tx, Some(vec![fee.id])).await?;
+
+        tx.commit().await?;
+
+        Ok((fee, report))
+    }
+
+    async fn create_monthly_fees_for_year(
+        &self,
+        year: i32,
+        amount: Money,
+    ) -> AppResult<(Vec<MonthlyFee>, PrePaymentReport)> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        let fees: Vec<DB_MonthlyFee> = sqlx::query_as(
+            r"
+                INSERT INTO
+                monthly_fees(
+                    year, month, amount, currency
+                )
+                SELECT $1::int, month, $2, $3 FROM generate_series(1, 12) AS month
+                ON CONFLICT (year, month) DO NOTHING
+                RETURNING *;
+            ",
+        )
+        .bind(year)
+        .bind(amount.minor_units)
+        .bind(amount.currency.code())
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut fees: Vec<MonthlyFee> = fees
+            .into_iter()
+            .map(into_monthly_fee)
+            .collect::<Result<_, _>>()?;
+        fees.sort_by_key(|f| f.month);
+
+        let report =
+            apply_pre_payments_in(&mut tx, Some(fees.iter().map(|f| f.id).collect())).await?;
+
+        tx.commit().await?;
+
+        Ok((fees, report))
+    }
+
+    async fn fetch_monthly_fees(
+        &self,
+        ascending: bool,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<Vec<MonthlyFee>> {
+        let query = match ascending {
+            true => {
+                r"
+                    SELECT * FROM monthly_fees
+                    ORDER BY year ASC, month ASC
+                    OFFSET $1 LIMIT $2;
+                "
+            }
+            false => {
+                r"
+                    SELECT * FROM monthly_fees
+                    ORDER BY year DESC, month DESC
+                    OFFSET $1 LIMIT $2;
+                "
+            }
+        };
+
+        let fees: Vec<DB_MonthlyFee> = sqlx::query_as(query)
+            .bind(offset)
+            .bind(count)
+            .fetch_all(&mut *acquire(self).await?)
+            .await?;
+
+        fees.into_iter().map(into_monthly_fee).collect()
+    }
+
+    async fn fetch_monthly_fee(&self, fee_id: ID) -> AppResult<Option<MonthlyFee>> {
+        let fee: Option<DB_MonthlyFee> = sqlx::query_as(
+            r"
+                SELECT * FROM monthly_fees
+                WHERE id = $1;
+            ",
+        )
+        .bind(fee_id)
+        .fetch_optional(&mut *acquire(self).await?)
+        .await?;
+
+        fee.map(into_monthly_fee).transpose()
+    }
+
+    async fn update_monthly_fee(
+        &self,
+        fee_id: ID,
+        year: i32,
+        month: i32,
+        amount: Money,
+    ) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        // blocks payments against this fee until the update is done
+        sqlx::query(
+            r"
+                SELECT id FROM monthly_fees
+                WHERE id = $1
+                FOR UPDATE;
+            ",
+        )
+        .bind(fee_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let overpaid: Option<(ID,)> = sqlx::query_as(
+            r"
+                SELECT account_id FROM net_payments
+                WHERE fee_id = $1
+                    AND amount > 0
+                GROUP BY account_id
+                HAVING SUM(amount) > $2 OR bool_or(currency <> $3)
+                LIMIT 1;
+            ",
+        )
+        .bind(fee_id)
+        .bind(amount.minor_units)
+        .bind(amount.currency.code())
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if overpaid.is_some() {
+            tx.rollback().await?;
+            return Err(AppError::conflict(
+                "An account already paid more than this amount or in another currency.",
+            ));
+        }
+
+        match sqlx::query(
+            r"
+                UPDATE monthly_fees
+                SET year = $2,
+                    month = $3,
+                    amount = $4,
+                    currency = $5
+                WHERE id = $1;
+            ",
+        )
+        .bind(fee_id)
+        .bind(year)
+        .bind(month)
+        .bind(amount.minor_units)
+        .bind(amount.currency.code())
+        .execute(&mut *tx)
+        .await
+        {
+            Ok(_) => {}
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                tx.rollback().await?;
+                return Err(AppError::conflict(PERIOD_TAKEN));
+            }
+            Err(err) => Err(err)?,
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_monthly_fee(&self, fee_id: ID) -> AppResult<()> {
+        let result = sqlx::query(
+            r"
+                DELETE FROM monthly_fees
+                WHERE id = $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM payments
+                        WHERE payments.fee_id = $1
+                    );
+            ",
+        )
+        .bind(fee_id)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        match result.rows_affected() {
+            1 => Ok(()),
+            _ => Err(AppError::conflict(
+                "Fee cannot be deleted because it has payments.",
+            )),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+
+use crate::{
+    db::{DatabaseConnectionResource, MIGRATOR, repository::HealthRepository},
+    service::health::{PoolStats, Readiness},
+};
+
+use super::{acquire, take_max_acquire_wait};
+
+#[async_trait]
+impl HealthRepository for DatabaseConnectionResource {
+    async fn check_readiness(&self) -> Readiness {
+        let Ok(mut connection) = acquire(self).await else {
+            return Readiness::DatabaseUnavailable;
+        };
+        if sqlx::query("SELECT 1;")
+            .execute(&mut *connection)
+            .await
+            .is_err()
+        {
+            return Readiness::DatabaseUnavailable;
+        }
+
+        let applied: Vec<(i64,)> = match sqlx::query_as(
+            r"
+                SELECT version FROM _sqlx_migrations
+                WHERE success = TRUE;
+            ",
+        )
+        .fetch_all(&mut *connection)
+        .await
+        {
+            Ok(applied) => applied,
+            // the migrations table is created by the first migration run
+            Err(sqlx::Error::Database(err)) if err.code().as_deref() == Some("42P01") => Vec::new(),
+            Err(_) => return Readiness::DatabaseUnavailable,
+        };
+
+        let pending: Vec<i64> = MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .map(|m| m.version)
+            .filter(|version| !applied.contains(&(*version,)))
+            .collect();
+
+        match pending.is_empty() {
+            true => Readiness::Ready,
+            false => Readiness::PendingMigrations(pending),
+        }
+    }
+
+    async fn fetch_pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.size(),
+            idle: self.num_idle(),
+            max_size: self.options().get_max_connections(),
+            acquire_wait: take_max_acquire_wait(),
+        })
+    }
+}
@@ -0,0 +1,208 @@
+use async_trait::async_trait;
+use sqlx::Connection;
+
+use crate::{
+    db::{
+        DatabaseConnectionResource,
+        repository::MfaRepository,
+        types::{DB_AccountTotp, ID},
+    },
+    error::{AppError, AppResult},
+    service::AccountTotp,
+};
+
+use super::acquire;
+
+#[async_trait]
+impl MfaRepository for DatabaseConnectionResource {
+    async fn store_totp_secret(&self, account_id: ID, secret: String, now: i64) -> AppResult<()> {
+        let result = sqlx::query(
+            r"
+                INSERT INTO
+                account_totp(
+                    account_id, secret, confirmed, last_used_step, created_at
+                )
+                VALUES (
+                    $1, $2, FALSE, NULL, $3
+                )
+                ON CONFLICT (account_id) DO UPDATE
+                SET secret = EXCLUDED.secret,
+                    created_at = EXCLUDED.created_at
+                WHERE account_totp.confirmed = FALSE;
+            ",
+        )
+        .bind(account_id)
+        .bind(&secret)
+        .bind(now)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        match result.rows_affected() {
+            1 => Ok(()),
+            _ => Err(AppError::conflict(
+                "Two-factor authentication is already enabled.",
+            )),
+        }
+    }
+
+    async fn fetch_totp(&self, account_id: ID) -> AppResult<Option<AccountTotp>> {
+        let totp: Option<DB_AccountTotp> = sqlx::query_as(
+            r"
+                SELECT * FROM account_totp
+                WHERE account_id = $1;
+            ",
+        )
+        .bind(account_id)
+        .fetch_optional(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(totp.map(|t| AccountTotp {
+            account_id: t.account_id,
+            secret: t.secret,
+            confirmed: t.confirmed,
+            last_used_step: t.last_used_step.map(|s| s as u64),
+        }))
+    }
+
+    async fn use_totp_step(&self, account_id: ID, step: i64) -> AppResult<bool> {
+        let result = sqlx::query(
+            r"
+                UPDATE account_totp
+                SET last_used_step = $2
+                WHERE account_id = $1
+                    AND (last_used_step IS NULL OR last_used_step < $2);
+            ",
+        )
+        .bind(account_id)
+        .bind(step)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn confirm_totp(
+        &self,
+        account_id: ID,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        sqlx::query(
+            r"
+                UPDATE account_totp
+                SET confirmed = TRUE,
+                    last_used_step = $2
+                WHERE account_id = $1;
+            ",
+        )
+        .bind(account_id)
+        .bind(step)
+        .execute(&mut *tx)
+        .await?;
+
+        replace_recovery_codes_in(&mut tx, account_id, recovery_code_hashes).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_totp(&self, account_id: ID) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        sqlx::query(
+            r"
+                DELETE FROM account_totp
+                WHERE account_id = $1;
+            ",
+        )
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        replace_recovery_codes_in(&mut tx, account_id, Vec::new()).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        account_id: ID,
+        code_hashes: Vec<String>,
+    ) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+        replace_recovery_codes_in(&mut tx, account_id, code_hashes).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &self,
+        account_id: ID,
+        code_hash: String,
+        now: i64,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r"
+                UPDATE account_recovery_codes
+                SET used_at = $3
+                WHERE account_id = $1
+                    AND code_hash = $2
+                    AND used_at IS NULL;
+            ",
+        )
+        .bind(account_id)
+        .bind(&code_hash)
+        .bind(now)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(result.rows_affected() >= 1)
+    }
+}
+
+async fn replace_recovery_codes_in(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    account_id: ID,
+    code_hashes: Vec<String>,
+) -> AppResult<()> {
+    sqlx::query(
+        r"
+            DELETE FROM account_recovery_codes
+            WHERE account_id = $1;
+        ",
+    )
+    .bind(account_id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(
+        r"
+            INSERT INTO
+            account_recovery_codes(
+                account_id, code_hash
+            )
+            SELECT * FROM UNNEST(
+                $1::int[], $2::text[]
+            );
+        ",
+    )
+    .bind(
+        (0..code_hashes.len())
+            .map(|_| account_id)
+            .collect::<Vec<_>>(),
+    )
+    .bind(&code_hashes)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
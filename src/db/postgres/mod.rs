@@ -0,0 +1,40 @@
+//! Repositories implemented on the Postgres pool, see `migrations/` for the schema.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use sqlx::{Postgres, pool::PoolConnection};
+
+use super::DatabaseConnectionResource;
+
+mod account;
+mod balance;
+mod fee;
+mod health;
+mod mfa;
+mod payment;
+mod role;
+mod token;
+
+/// Longest time a connection was waited for since the pool stats were last taken, in
+/// microseconds.
+static MAX_ACQUIRE_WAIT: AtomicU64 = AtomicU64::new(0);
+
+/// Takes a connection of the pool for the queries of a repository, recording how long it was
+/// waited for.
+async fn acquire(pool: &DatabaseConnectionResource) -> sqlx::Result<PoolConnection<Postgres>> {
+    let started = Instant::now();
+    // a timed out acquire is recorded as the time waited
+    let connection = pool.acquire().await;
+    let wait = started.elapsed().as_micros().try_into().unwrap_or(u64::MAX);
+    MAX_ACQUIRE_WAIT.fetch_max(wait, Ordering::Relaxed);
+
+    connection
+}
+
+/// Longest time a connection was waited for since the last call.
+fn take_max_acquire_wait() -> Duration {
+    Duration::from_micros(MAX_ACQUIRE_WAIT.swap(0, Ordering::Relaxed))
+}
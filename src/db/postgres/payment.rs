@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+
+use actix_web::rt::task::JoinHandle;
+use async_trait::async_trait;
+use sqlx::Connection;
+
+use crate::{
+    db::{
+        DatabaseConnectionResource,
+        repository::PaymentRepository,
+        types::{
+            DB_AccountPeriodPayment, DB_MonthlyFee, DB_PaymentReversal, DB_PeriodPayment,
+            DB_PrePayment, ID,
+        },
+    },
+    error::{AppError, AppResult},
+    money::Money,
+    service::{
+        Payments, PrePaymentReport,
+        payment::{
+            PRE_PAYMENT_APPLIED, PaymentKind, PaymentPlan, PeriodPayment, into_account_payment,
+            into_payment, into_payments_with_reversals, into_pre_payments_with_reversals,
+            plan_payments, reversal_amount,
+        },
+    },
+    util::unix_timestamp,
+};
+
+use super::acquire;
+
+#[async_trait]
+impl PaymentRepository for DatabaseConnectionResource {
+    async fn fetch_account_payments(&self, account_id: ID) -> AppResult<Payments> {
+        let join_payment_rows: JoinHandle<sqlx::Result<Vec<DB_PeriodPayment>>> = {
+            let db = self.clone();
+            actix_web::rt::spawn(async move {
+                sqlx::query_as(
+                    r"
+                        SELECT
+                            payments.id,
+                            monthly_fees.year,
+                            monthly_fees.month,
+                            payments.amount,
+                            payments.currency,
+                            payments.paid_at
+                        FROM payments
+                        JOIN monthly_fees ON monthly_fees.id = payments.fee_id
+                            AND payments.account_id = $1
+                        ORDER BY monthly_fees.year, monthly_fees.month, payments.paid_at;
+                    ",
+                )
+                .bind(account_id)
+                .fetch_all(&mut *acquire(&db).await?)
+                .await
+            })
+        };
+
+        let join_pre_payment_rows: JoinHandle<sqlx::Result<Vec<DB_PrePayment>>> = {
+            let db = self.clone();
+            actix_web::rt::spawn(async move {
+                sqlx::query_as(
+                    r"
+                        SELECT * FROM pre_payments
+                        WHERE pre_payments.account_id = $1
+                        ORDER BY pre_payments.year, pre_payments.month, pre_payments.paid_at;
+                    ",
+                )
+                .bind(account_id)
+                .fetch_all(&mut *acquire(&db).await?)
+                .await
+            })
+        };
+
+        let join_reversal_rows: JoinHandle<sqlx::Result<Vec<DB_PaymentReversal>>> = {
+            let db = self.clone();
+            actix_web::rt::spawn(async move {
+                sqlx::query_as(
+                    r"
+                        SELECT payment_reversals.* FROM payment_reversals
+                        LEFT JOIN payments ON payments.id = payment_reversals.payment_id
+                        LEFT JOIN pre_payments
+                            ON pre_payments.id = payment_reversals.pre_payment_id
+                        WHERE payments.account_id = $1
+                            OR pre_payments.account_id = $1
+                        ORDER BY payment_reversals.reversed_at, payment_reversals.id;
+                    ",
+                )
+                .bind(account_id)
+                .fetch_all(&mut *acquire(&db).await?)
+                .await
+            })
+        };
+
+        let reversals = join_reversal_rows.await??;
+
+        Ok(Payments {
+            made: into_payments_with_reversals(join_payment_rows.await??, &reversals)?,
+            precovered: into_pre_payments_with_reversals(
+                join_pre_payment_rows.await??,
+                &reversals,
+            )?,
+        })
+    }
+
+    async fn record_payments(
+        &self,
+        account_id: ID,
+        periods: Vec<PeriodPayment>,
+    ) -> AppResult<Payments> {
+        let years: Vec<i32> = periods.iter().map(|p| p.year).collect();
+        let months: Vec<i32> = periods.iter().map(|p| p.month).collect();
+        let paid_at = unix_timestamp() as i64;
+
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        // serializes the payments of an account so the outstanding amounts stay accurate
+        sqlx::query(
+            r"
+                SELECT id FROM accounts
+                WHERE id = $1
+                FOR UPDATE;
+            ",
+        )
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let fees: Vec<DB_MonthlyFee> = sqlx::query_as(
+            r"
+                SELECT * FROM monthly_fees
+                WHERE (year, month) IN (
+                    SELECT * FROM UNNEST($1::int[], $2::int[])
+                )
+                FOR SHARE;
+            ",
+        )
+        .bind(&years)
+        .bind(&months)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let fee_ids: Vec<ID> = fees.iter().map(|f| f.id).collect();
+        let paid: HashMap<ID, i64> = sqlx::query_as(
+            r"
+                SELECT fee_id, SUM(amount)::INT8 FROM net_payments
+                WHERE account_id = $1
+                    AND fee_id = ANY($2::int[])
+                GROUP BY fee_id;
+            ",
+        )
+        .bind(account_id)
+        .bind(&fee_ids)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .collect();
+
+        let PaymentPlan {
+            fee_payments,
+            pre_payments,
+        } = match plan_payments(periods, &fees, &paid) {
+            Ok(plan) => plan,
+            Err(err) => {
+                tx.rollback().await?;
+                return Err(err);
+            }
+        };
+
+        let made: Vec<DB_PeriodPayment> = sqlx::query_as(
+            r"
+                WITH inserted AS (
+                    INSERT INTO
+                    payments(
+                        account_id, fee_id, amount, currency, paid_at
+                    )
+                    SELECT $1::int, fee_id, amount, currency, $5::int8 FROM UNNEST(
+                        $2::int[], $3::int8[], $4::text[]
+                    ) AS payment(fee_id, amount, currency)
+                    RETURNING *
+                )
+                SELECT
+                    inserted.id,
+                    monthly_fees.year,
+                    monthly_fees.month,
+                    inserted.amount,
+                    inserted.currency,
+                    inserted.paid_at
+                FROM inserted
+                JOIN monthly_fees ON monthly_fees.id = inserted.fee_id
+                ORDER BY monthly_fees.year, monthly_fees.month;
+            ",
+        )
+        .bind(account_id)
+        .bind(fee_payments.iter().map(|p| p.0).collect::<Vec<_>>())
+        .bind(
+            fee_payments
+                .iter()
+                .map(|p| p.1.minor_units)
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            fee_payments
+                .iter()
+                .map(|p| p.1.currency.code())
+                .collect::<Vec<_>>(),
+        )
+        .bind(paid_at)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let precovered: Vec<DB_PrePayment> = sqlx::query_as(
+            r"
+                INSERT INTO
+                pre_payments(
+                    account_id, year, month, amount, currency, paid_at
+                )
+                SELECT $1::int, year, month, amount, currency, $6::int8 FROM UNNEST(
+                    $2::int[], $3::int[], $4::int8[], $5::text[]
+                ) AS period(year, month, amount, currency)
+                RETURNING *;
+            ",
+        )
+        .bind(account_id)
+        .bind(pre_payments.iter().map(|p| p.0).collect::<Vec<_>>())
+        .bind(pre_payments.iter().map(|p| p.1).collect::<Vec<_>>())
+        .bind(
+            pre_payments
+                .iter()
+                .map(|p| p.2.minor_units)
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            pre_payments
+                .iter()
+                .map(|p| p.2.currency.code())
+                .collect::<Vec<_>>(),
+        )
+        .bind(paid_at)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Payments {
+            made: made
+                .into_iter()
+                .map(into_payment)
+                .collect::<Result<_, _>>()?,
+            precovered: into_pre_payments_with_reversals(precovered, &[])?,
+        })
+    }
+
+    async fn reverse_payment(
+        &self,
+        account_id: ID,
+        kind: PaymentKind,
+        payment_id: ID,
+        amount: Option<Money>,
+        reversed_by: ID,
+    ) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        let left: Option<(i64, String, bool)> = sqlx::query_as(match kind {
+            PaymentKind::Payment => {
+                r"
+                    SELECT
+                        (payments.amount - COALESCE((
+                            SELECT SUM(amount) FROM payment_reversals
+                            WHERE payment_id = payments.id
+                        ), 0))::INT8,
+                        payments.currency,
+                        FALSE
+                    FROM payments
+                    WHERE id = $1
+                        AND account_id = $2
+                    FOR UPDATE;
+                "
+            }
+            PaymentKind::PrePayment => {
+                r"
+                    SELECT
+                        (pre_payments.amount - COALESCE((
+                            SELECT SUM(amount) FROM payment_reversals
+                            WHERE pre_payment_id = pre_payments.id
+                        ), 0))::INT8,
+                        pre_payments.currency,
+                        pre_payments.applied_payment_id IS NOT NULL
+                    FROM pre_payments
+                    WHERE id = $1
+                        AND account_id = $2
+                    FOR UPDATE;
+                "
+            }
+        })
+        .bind(payment_id)
+        .bind(account_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some((left, currency, applied)) = left else {
+            tx.rollback().await?;
+            return Err(AppError::not_found("Payment not found."));
+        };
+        if applied {
+            tx.rollback().await?;
+            return Err(AppError::conflict(PRE_PAYMENT_APPLIED));
+        }
+
+        let left = Money::from_db(left, &currency)?;
+        let amount = match reversal_amount(left, amount) {
+            Ok(amount) => amount,
+            Err(err) => {
+                tx.rollback().await?;
+                return Err(err);
+            }
+        };
+
+        let (reversed_payment_id, reversed_pre_payment_id) = match kind {
+            PaymentKind::Payment => (Some(payment_id), None),
+            PaymentKind::PrePayment => (None, Some(payment_id)),
+        };
+        sqlx::query(
+            r"
+                INSERT INTO
+                payment_reversals(
+                    payment_id, pre_payment_id, amount, reversed_by, reversed_at
+                )
+                VALUES (
+                    $1, $2, $3, $4, $5
+                );
+            ",
+        )
+        .bind(reversed_payment_id)
+        .bind(reversed_pre_payment_id)
+        .bind(amount.minor_units)
+        .bind(reversed_by)
+        .bind(unix_timestamp() as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn apply_pre_payments(&self) -> AppResult<PrePaymentReport> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+        let report = apply_pre_payments_in(&mut tx, None).await?;
+        tx.commit().await?;
+
+        Ok(report)
+    }
+}
+
+/// Turns what is left of the pre-payments of the periods of the given fees, or of all fees, into
+/// `payments`.
+///
+/// Pre-payments are applied oldest first as long as they fit in what is outstanding for the
+/// fee and are in its currency, the others are left in place and reported as orphaned.
+/// Applied pre-payments are marked with the payment they became, so applying again has no
+/// effect.
+pub(super) async fn apply_pre_payments_in(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    fee_ids: Option<Vec<ID>>,
+) -> AppResult<PrePaymentReport> {
+    sqlx::query(
+        r"
+            SELECT pre_payments.id FROM pre_payments
+            JOIN monthly_fees ON monthly_fees.year = pre_payments.year
+                AND monthly_fees.month = pre_payments.month
+            WHERE pre_payments.applied_payment_id IS NULL
+                AND ($1::int[] IS NULL OR monthly_fees.id = ANY($1))
+            FOR UPDATE OF pre_payments;
+        ",
+    )
+    .bind(&fee_ids)
+    .execute(&mut **tx)
+    .await?;
+
+    let applied: Vec<DB_AccountPeriodPayment> = sqlx::query_as(
+        r"
+            WITH candidates AS (
+                SELECT
+                    net_pre_payments.id,
+                    net_pre_payments.account_id,
+                    monthly_fees.id AS fee_id,
+                    net_pre_payments.amount,
+                    net_pre_payments.currency,
+                    net_pre_payments.paid_at,
+                    monthly_fees.amount
+                        - COALESCE((
+                            SELECT SUM(net_payments.amount) FROM net_payments
+                            WHERE net_payments.account_id = net_pre_payments.account_id
+                                AND net_payments.fee_id = monthly_fees.id
+                        ), 0)
+                        - SUM(net_pre_payments.amount) OVER (
+                            PARTITION BY net_pre_payments.account_id, monthly_fees.id
+                            ORDER BY net_pre_payments.paid_at, net_pre_payments.id
+                        ) AS remaining
+                FROM net_pre_payments
+                JOIN monthly_fees ON monthly_fees.year = net_pre_payments.year
+                    AND monthly_fees.month = net_pre_payments.month
+                    AND monthly_fees.currency = net_pre_payments.currency
+                WHERE net_pre_payments.amount > 0
+                    AND ($1::int[] IS NULL OR monthly_fees.id = ANY($1))
+            ),
+            moved AS (
+                SELECT
+                    candidates.*,
+                    nextval(pg_get_serial_sequence('payments', 'id'))::INT AS payment_id
+                FROM candidates
+                WHERE candidates.remaining >= 0
+            ),
+            marked AS (
+                UPDATE pre_payments
+                SET applied_payment_id = moved.payment_id, applied_at = $2
+                FROM moved
+                WHERE pre_payments.id = moved.id
+            ),
+            inserted AS (
+                INSERT INTO
+                payments(
+                    id, account_id, fee_id, amount, currency, paid_at
+                )
+                SELECT payment_id, account_id, fee_id, amount, currency, paid_at FROM moved
+                RETURNING *
+            )
+            SELECT
+                inserted.account_id,
+                inserted.id,
+                monthly_fees.year,
+                monthly_fees.month,
+                inserted.amount,
+                inserted.currency,
+                inserted.paid_at
+            FROM inserted
+            JOIN monthly_fees ON monthly_fees.id = inserted.fee_id
+            ORDER BY monthly_fees.year, monthly_fees.month, inserted.account_id;
+        ",
+    )
+    .bind(&fee_ids)
+    .bind(unix_timestamp() as i64)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let orphaned: Vec<DB_AccountPeriodPayment> = sqlx::query_as(
+        r"
+            SELECT
+                net_pre_payments.account_id,
+                net_pre_payments.id,
+                net_pre_payments.year,
+                net_pre_payments.month,
+                net_pre_payments.amount,
+                net_pre_payments.currency,
+                net_pre_payments.paid_at
+            FROM net_pre_payments
+            JOIN monthly_fees ON monthly_fees.year = net_pre_payments.year
+                AND monthly_fees.month = net_pre_payments.month
+            WHERE net_pre_payments.amount > 0
+                AND ($1::int[] IS NULL OR monthly_fees.id = ANY($1))
+            ORDER BY net_pre_payments.year, net_pre_payments.month, net_pre_payments.account_id;
+        ",
+    )
+    .bind(&fee_ids)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(PrePaymentReport {
+        applied: applied
+            .into_iter()
+            .map(into_account_payment)
+            .collect::<Result<_, _>>()?,
+        orphaned: orphaned
+            .into_iter()
+            .map(into_account_payment)
+            .collect::<Result<_, _>>()?,
+    })
+}
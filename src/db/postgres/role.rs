@@ -0,0 +1,366 @@
+use async_trait::async_trait;
+use sqlx::Connection;
+
+use crate::{
+    auth::ROLE_ADMIN,
+    db::{
+        DatabaseConnectionResource,
+        repository::RoleRepository,
+        types::{DB_Role, ID},
+    },
+    error::{AppError, AppResult},
+    service::{
+        Role,
+        role::{BUILT_IN_ROLE, BUILT_IN_ROLES, LAST_ADMIN, ROLE_NOT_FOUND, into_role},
+    },
+};
+
+use super::acquire;
+
+#[async_trait]
+impl RoleRepository for DatabaseConnectionResource {
+    async fn fetch_roles(&self) -> AppResult<Vec<Role>> {
+        let roles: Vec<DB_Role> = sqlx::query_as(
+            r"
+                SELECT * FROM roles
+                ORDER BY role;
+            ",
+        )
+        .fetch_all(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(roles.into_iter().map(into_role).collect())
+    }
+
+    async fn fetch_role(&self, role_id: ID) -> AppResult<Option<Role>> {
+        let role: Option<DB_Role> = sqlx::query_as(
+            r"
+                SELECT * FROM roles
+                WHERE id = $1;
+            ",
+        )
+        .bind(role_id)
+        .fetch_optional(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(role.map(into_role))
+    }
+
+    async fn rename_role(&self, role_id: ID, role: String) -> AppResult<()> {
+        match sqlx::query(
+            r"
+                UPDATE roles
+                SET role = $2
+                WHERE id = $1
+                    AND role <> ALL($3::text[]);
+            ",
+        )
+        .bind(role_id)
+        .bind(&role)
+        .bind(&BUILT_IN_ROLES[..])
+        .execute(&mut *acquire(self).await?)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => Err(built_in_or_not_found(self, role_id).await?),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                Err(AppError::conflict("Duplicate role."))
+            }
+            Err(err) => Err(err)?,
+        }
+    }
+
+    async fn delete_role(&self, role_id: ID) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        let result = sqlx::query(
+            r"
+                DELETE FROM roles
+                WHERE id = $1
+                    AND role <> ALL($2::text[]);
+            ",
+        )
+        .bind(role_id)
+        .bind(&BUILT_IN_ROLES[..])
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(built_in_or_not_found(self, role_id).await?);
+        }
+
+        sqlx::query(
+            r"
+                DELETE FROM account_roles
+                WHERE role_id = $1;
+            ",
+        )
+        .bind(role_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r"
+                DELETE FROM role_permissions
+                WHERE role_id = $1;
+            ",
+        )
+        .bind(role_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn revoke_role_from_account(&self, account_id: ID, role_id: ID) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+        lock_admin_role(&mut tx).await?;
+        let was_admin = is_admin(&mut tx, account_id).await?;
+
+        let result = sqlx::query(
+            r"
+                DELETE FROM account_roles
+                WHERE account_id = $1
+                    AND role_id = $2;
+            ",
+        )
+        .bind(account_id)
+        .bind(role_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(AppError::not_found("Account does not have this role."));
+        }
+        if was_admin && !admin_exists(&mut tx).await? {
+            tx.rollback().await?;
+            return Err(AppError::conflict(LAST_ADMIN));
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn replace_account_roles(&self, account_id: ID, role_ids: Vec<ID>) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+        lock_admin_role(&mut tx).await?;
+        let was_admin = is_admin(&mut tx, account_id).await?;
+
+        let existing: (i64,) = sqlx::query_as(
+            r"
+                SELECT COUNT(*) FROM roles
+                WHERE id = ANY($1::int[]);
+            ",
+        )
+        .bind(&role_ids)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if existing.0 as usize != role_ids.len() {
+            tx.rollback().await?;
+            return Err(AppError::not_found(ROLE_NOT_FOUND));
+        }
+
+        sqlx::query(
+            r"
+                DELETE FROM account_roles
+                WHERE account_id = $1;
+            ",
+        )
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r"
+                INSERT INTO
+                account_roles(
+                    account_id, role_id
+                )
+                SELECT $1::int, role_id FROM UNNEST(
+                    $2::int[]
+                ) AS role_id;
+            ",
+        )
+        .bind(account_id)
+        .bind(&role_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        if was_admin && !admin_exists(&mut tx).await? {
+            tx.rollback().await?;
+            return Err(AppError::conflict(LAST_ADMIN));
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn fetch_permissions(&self) -> AppResult<Vec<String>> {
+        let permissions: Vec<(String,)> = sqlx::query_as(
+            r"
+                SELECT permission FROM permissions
+                ORDER BY permission;
+            ",
+        )
+        .fetch_all(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(permissions.into_iter().map(|p| p.0).collect())
+    }
+
+    async fn fetch_role_permissions(&self, role_id: ID) -> AppResult<Vec<String>> {
+        let permissions: Vec<(String,)> = sqlx::query_as(
+            r"
+                SELECT permissions.permission FROM role_permissions
+                JOIN permissions ON permissions.id = role_permissions.permission_id
+                WHERE role_permissions.role_id = $1
+                ORDER BY permissions.permission;
+            ",
+        )
+        .bind(role_id)
+        .fetch_all(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(permissions.into_iter().map(|p| p.0).collect())
+    }
+
+    async fn fetch_permissions_of_role_ids(&self, role_ids: Vec<ID>) -> AppResult<Vec<String>> {
+        let permissions: Vec<(String,)> = sqlx::query_as(
+            r"
+                SELECT DISTINCT permissions.permission FROM role_permissions
+                JOIN permissions ON permissions.id = role_permissions.permission_id
+                WHERE role_permissions.role_id = ANY($1::int[])
+                ORDER BY permissions.permission;
+            ",
+        )
+        .bind(&role_ids)
+        .fetch_all(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(permissions.into_iter().map(|p| p.0).collect())
+    }
+
+    async fn replace_role_permissions(
+        &self,
+        role_id: ID,
+        permissions: Vec<String>,
+    ) -> AppResult<()> {
+        let mut connection = acquire(self).await?;
+        let mut tx = connection.begin().await?;
+
+        sqlx::query(
+            r"
+                DELETE FROM role_permissions
+                WHERE role_id = $1;
+            ",
+        )
+        .bind(role_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let granted = sqlx::query(
+            r"
+                INSERT INTO
+                role_permissions(
+                    role_id, permission_id
+                )
+                SELECT $1::int, id FROM permissions
+                WHERE permission = ANY($2::text[]);
+            ",
+        )
+        .bind(role_id)
+        .bind(&permissions)
+        .execute(&mut *tx)
+        .await?;
+
+        if granted.rows_affected() != permissions.len() as u64 {
+            tx.rollback().await?;
+            return Err(AppError::validation("Unknown permission."));
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Why a role that was left unchanged could not be changed.
+async fn built_in_or_not_found(
+    db: &DatabaseConnectionResource,
+    role_id: ID,
+) -> AppResult<AppError> {
+    Ok(match db.fetch_role(role_id).await? {
+        Some(_) => AppError::conflict(BUILT_IN_ROLE),
+        None => AppError::not_found(ROLE_NOT_FOUND),
+    })
+}
+
+/// Serializes the changes that may take away the `Admin` role, so that two admins revoking
+/// each other at the same time can not both succeed.
+pub(super) async fn lock_admin_role(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> AppResult<()> {
+    sqlx::query(
+        r"
+            SELECT id FROM roles
+            WHERE role = $1
+            FOR UPDATE;
+        ",
+    )
+    .bind(ROLE_ADMIN)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn is_admin(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    account_id: ID,
+) -> AppResult<bool> {
+    let is_admin: (bool,) = sqlx::query_as(
+        r"
+            SELECT EXISTS (
+                SELECT 1 FROM account_roles
+                JOIN roles ON roles.id = account_roles.role_id
+                WHERE account_roles.account_id = $1
+                    AND roles.role = $2
+            );
+        ",
+    )
+    .bind(account_id)
+    .bind(ROLE_ADMIN)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(is_admin.0)
+}
+
+pub(super) async fn admin_exists(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> AppResult<bool> {
+    let exists: (bool,) = sqlx::query_as(
+        r"
+            SELECT EXISTS (
+                SELECT 1 FROM account_roles
+                JOIN roles ON roles.id = account_roles.role_id
+                WHERE roles.role = $1
+            );
+        ",
+    )
+    .bind(ROLE_ADMIN)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(exists.0)
+}
@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        DatabaseConnectionResource,
+        repository::TokenRepository,
+        types::{DB_RefreshToken, ID},
+    },
+    error::AppResult,
+    service::RefreshToken,
+};
+
+use super::acquire;
+
+#[async_trait]
+impl TokenRepository for DatabaseConnectionResource {
+    async fn store_refresh_token(&self, token: DB_RefreshToken) -> AppResult<()> {
+        sqlx::query(
+            r"
+                INSERT INTO
+                refresh_tokens(
+                    jti, family_id, account_id, token_hash, issued_at, expires_at
+                )
+                VALUES (
+                    $1, $2, $3, $4, $5, $6
+                );
+            ",
+        )
+        .bind(token.jti)
+        .bind(token.family_id)
+        .bind(token.account_id)
+        .bind(&token.token_hash)
+        .bind(token.issued_at)
+        .bind(token.expires_at)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fetch_refresh_token(&self, jti: Uuid) -> AppResult<Option<RefreshToken>> {
+        let token: Option<DB_RefreshToken> = sqlx::query_as(
+            r"
+                SELECT * FROM refresh_tokens
+                WHERE jti = $1;
+            ",
+        )
+        .bind(jti)
+        .fetch_optional(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(token.map(|t| RefreshToken {
+            jti: t.jti,
+            family_id: t.family_id,
+            account_id: t.account_id,
+            token_hash: t.token_hash,
+            issued_at: t.issued_at as u64,
+            expires_at: t.expires_at as u64,
+            rotated_at: t.rotated_at.map(|ts| ts as u64),
+            revoked_at: t.revoked_at.map(|ts| ts as u64),
+        }))
+    }
+
+    async fn rotate_refresh_token(&self, jti: Uuid, now: i64) -> AppResult<bool> {
+        let result = sqlx::query(
+            r"
+                UPDATE refresh_tokens
+                SET rotated_at = $2
+                WHERE jti = $1
+                    AND rotated_at IS NULL
+                    AND revoked_at IS NULL;
+            ",
+        )
+        .bind(jti)
+        .bind(now)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn revoke_refresh_token_family(&self, family_id: Uuid, now: i64) -> AppResult<u64> {
+        let result = sqlx::query(
+            r"
+                UPDATE refresh_tokens
+                SET revoked_at = $2
+                WHERE family_id = $1
+                    AND revoked_at IS NULL;
+            ",
+        )
+        .bind(family_id)
+        .bind(now)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn revoke_account_refresh_tokens(&self, account_id: ID, now: i64) -> AppResult<u64> {
+        let result = sqlx::query(
+            r"
+                UPDATE refresh_tokens
+                SET revoked_at = $2
+                WHERE account_id = $1
+                    AND revoked_at IS NULL;
+            ",
+        )
+        .bind(account_id)
+        .bind(now)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn delete_expired_refresh_tokens(&self, account_id: ID, now: i64) -> AppResult<u64> {
+        let result = sqlx::query(
+            r"
+                DELETE FROM refresh_tokens
+                WHERE account_id = $1
+                    AND expires_at < $2;
+            ",
+        )
+        .bind(account_id)
+        .bind(now)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn store_password_reset_token(
+        &self,
+        token_hash: String,
+        account_id: ID,
+        expires_at: i64,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r"
+                INSERT INTO
+                password_reset_tokens(
+                    token_hash, account_id, expires_at
+                )
+                VALUES (
+                    $1, $2, $3
+                );
+            ",
+        )
+        .bind(&token_hash)
+        .bind(account_id)
+        .bind(expires_at)
+        .execute(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn consume_password_reset_token(
+        &self,
+        token_hash: String,
+        now: i64,
+    ) -> AppResult<Option<ID>> {
+        let account_id: Option<(ID,)> = sqlx::query_as(
+            r"
+                UPDATE password_reset_tokens
+                SET used_at = $2
+                WHERE token_hash = $1
+                    AND used_at IS NULL
+                    AND expires_at > $2
+                RETURNING account_id;
+            ",
+        )
+        .bind(&token_hash)
+        .bind(now)
+        .fetch_optional(&mut *acquire(self).await?)
+        .await?;
+
+        Ok(account_id.map(|(id,)| id))
+    }
+}
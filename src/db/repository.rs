@@ -0,0 +1,300 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::{
+    error::AppResult,
+    money::Money,
+    service::{
+        Account, AccountCursor, AccountPage, AccountTotp, Arrears, MonthlyFee, Payments,
+        PeriodStatement, PrePaymentReport, RefreshToken, Role,
+        account::{AccountFilter, AccountOrder},
+        balance::ArrearsOrder,
+        health::{PoolStats, Readiness},
+        payment::{PaymentKind, PeriodPayment},
+    },
+};
+
+use super::types::{DB_Account, DB_RefreshToken, ID};
+
+#[async_trait]
+pub trait AccountRepository: Send + Sync {
+    /// Fails with a conflict if the phone number or the email is already used.
+    async fn create_account(&self, account: DB_Account) -> AppResult<()>;
+
+    /// Creates the account holding the `Admin` role, unless an account already holds it.
+    /// Returns `false` then. Fails with a conflict like [`Self::create_account`].
+    async fn create_admin_account(&self, account: DB_Account) -> AppResult<bool>;
+
+    async fn create_roles(&self, roles: Vec<String>) -> AppResult<()>;
+
+    /// Fails with a conflict if the account already has one of the roles, and with not found
+    /// if the account or a role does not exist.
+    async fn add_roles_to_account(&self, account_id: ID, role_ids: Vec<ID>) -> AppResult<()>;
+
+    async fn fetch_account_by_email(&self, email: String) -> AppResult<Option<Account>>;
+
+    async fn fetch_account_by_phone_number(
+        &self,
+        phone_number: String,
+    ) -> AppResult<Option<Account>>;
+
+    async fn fetch_account_by_id(&self, account_id: ID) -> AppResult<Option<Account>>;
+
+    async fn fetch_account_roles(&self, account_id: ID) -> AppResult<Vec<Role>>;
+
+    async fn update_password(
+        &self,
+        account_id: ID,
+        hashed_password: String,
+        password_set_ts: i64,
+    ) -> AppResult<()>;
+
+    /// Fails with a conflict if the phone number or the email is already used by another account.
+    async fn update_account_profile(
+        &self,
+        account_id: ID,
+        name: String,
+        lastname: String,
+        phone_number: String,
+        email: String,
+    ) -> AppResult<()>;
+
+    /// Lists the accounts matching the filter. Pages continue either from `offset`
+    /// or, when given, right after the `after` cursor.
+    async fn fetch_accounts(
+        &self,
+        filter: AccountFilter,
+        order: AccountOrder,
+        ascending: bool,
+        after: Option<AccountCursor>,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<AccountPage>;
+}
+
+#[async_trait]
+pub trait RoleRepository: Send + Sync {
+    /// Ordered by name.
+    async fn fetch_roles(&self) -> AppResult<Vec<Role>>;
+
+    async fn fetch_role(&self, role_id: ID) -> AppResult<Option<Role>>;
+
+    /// Built-in roles can not be renamed. Fails with a conflict if another role has the name.
+    async fn rename_role(&self, role_id: ID, role: String) -> AppResult<()>;
+
+    /// Deletes the role and takes it away from every account holding it.
+    /// Built-in roles can not be deleted.
+    async fn delete_role(&self, role_id: ID) -> AppResult<()>;
+
+    /// Fails with not found if the account does not hold the role, and with a conflict if it is
+    /// the last account holding `Admin`.
+    async fn revoke_role_from_account(&self, account_id: ID, role_id: ID) -> AppResult<()>;
+
+    /// Sets exactly the given roles on the account at once. Fails with not found if a role does
+    /// not exist, and with a conflict if it takes `Admin` from the last account holding it.
+    async fn replace_account_roles(&self, account_id: ID, role_ids: Vec<ID>) -> AppResult<()>;
+
+    /// Every permission there is, ordered by name.
+    async fn fetch_permissions(&self) -> AppResult<Vec<String>>;
+
+    async fn fetch_role_permissions(&self, role_id: ID) -> AppResult<Vec<String>>;
+
+    /// Permissions granted by any of the roles, ordered by name.
+    async fn fetch_permissions_of_role_ids(&self, role_ids: Vec<ID>) -> AppResult<Vec<String>>;
+
+    /// Grants exactly the given permissions to the role. The role is known to exist.
+    /// Fails with a validation error if a permission does not exist.
+    async fn replace_role_permissions(
+        &self,
+        role_id: ID,
+        permissions: Vec<String>,
+    ) -> AppResult<()>;
+}
+
+#[async_trait]
+pub trait FeeRepository: Send + Sync {
+    /// Creates the fee and applies the pre-payments of its period.
+    /// Fails with a conflict if a fee for the same period already exists.
+    async fn create_monthly_fee(
+        &self,
+        year: i32,
+        month: i32,
+        amount: Money,
+    ) -> AppResult<(MonthlyFee, PrePaymentReport)>;
+
+    /// Creates the fees of the months of the year that have none, and applies the pre-payments
+    /// of the new periods. Returns only the newly created fees.
+    async fn create_monthly_fees_for_year(
+        &self,
+        year: i32,
+        amount: Money,
+    ) -> AppResult<(Vec<MonthlyFee>, PrePaymentReport)>;
+
+    async fn fetch_monthly_fees(
+        &self,
+        ascending: bool,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<Vec<MonthlyFee>>;
+
+    async fn fetch_monthly_fee(&self, fee_id: ID) -> AppResult<Option<MonthlyFee>>;
+
+    /// Changes the period and price of a fee. The price can not go below what an account already
+    /// paid towards the fee. Fails with a conflict if another fee already covers the period.
+    async fn update_monthly_fee(
+        &self,
+        fee_id: ID,
+        year: i32,
+        month: i32,
+        amount: Money,
+    ) -> AppResult<()>;
+
+    /// Fails with a conflict if payments were already recorded against the fee.
+    async fn delete_monthly_fee(&self, fee_id: ID) -> AppResult<()>;
+}
+
+#[async_trait]
+pub trait PaymentRepository: Send + Sync {
+    /// Payments and pre-payments of the account, ordered by period.
+    async fn fetch_account_payments(&self, account_id: ID) -> AppResult<Payments>;
+
+    /// Records the payments of the periods, all of them or none.
+    /// See [`crate::service::payment::record_payments`].
+    async fn record_payments(
+        &self,
+        account_id: ID,
+        periods: Vec<PeriodPayment>,
+    ) -> AppResult<Payments>;
+
+    /// Reverses part or all of what is left of a payment of the account.
+    /// See [`crate::service::payment::reverse_payment`].
+    async fn reverse_payment(
+        &self,
+        account_id: ID,
+        kind: PaymentKind,
+        payment_id: ID,
+        amount: Option<Money>,
+        reversed_by: ID,
+    ) -> AppResult<()>;
+
+    /// Applies the pre-payments of every period that has a fee. Safe to run repeatedly.
+    async fn apply_pre_payments(&self) -> AppResult<PrePaymentReport>;
+}
+
+#[async_trait]
+pub trait BalanceRepository: Send + Sync {
+    /// Every fee period with what the account paid towards it, ordered by period.
+    async fn fetch_period_statements(&self, account_id: ID) -> AppResult<Vec<PeriodStatement>>;
+
+    /// Accounts with overdue periods, one entry per account and currency.
+    async fn fetch_arrears(
+        &self,
+        order: ArrearsOrder,
+        ascending: bool,
+        offset: i64,
+        count: i64,
+    ) -> AppResult<Vec<Arrears>>;
+}
+
+#[async_trait]
+pub trait TokenRepository: Send + Sync {
+    async fn store_refresh_token(&self, token: DB_RefreshToken) -> AppResult<()>;
+
+    async fn fetch_refresh_token(&self, jti: Uuid) -> AppResult<Option<RefreshToken>>;
+
+    /// Marks the token as used. Returns `false` if it was already rotated or revoked,
+    /// which means the token is being reused.
+    async fn rotate_refresh_token(&self, jti: Uuid, now: i64) -> AppResult<bool>;
+
+    async fn revoke_refresh_token_family(&self, family_id: Uuid, now: i64) -> AppResult<u64>;
+
+    async fn revoke_account_refresh_tokens(&self, account_id: ID, now: i64) -> AppResult<u64>;
+
+    async fn delete_expired_refresh_tokens(&self, account_id: ID, now: i64) -> AppResult<u64>;
+
+    async fn store_password_reset_token(
+        &self,
+        token_hash: String,
+        account_id: ID,
+        expires_at: i64,
+    ) -> AppResult<()>;
+
+    /// Marks an unused, unexpired reset token as used and returns the account it was issued for.
+    async fn consume_password_reset_token(
+        &self,
+        token_hash: String,
+        now: i64,
+    ) -> AppResult<Option<ID>>;
+}
+
+#[async_trait]
+pub trait MfaRepository: Send + Sync {
+    /// Stores a new, unconfirmed secret, replacing any previous unconfirmed one.
+    /// Fails with a conflict if the account already has a confirmed secret.
+    async fn store_totp_secret(&self, account_id: ID, secret: String, now: i64) -> AppResult<()>;
+
+    async fn fetch_totp(&self, account_id: ID) -> AppResult<Option<AccountTotp>>;
+
+    /// Records the time step of an accepted code. Returns `false` if the same or a later step
+    /// was already used, i.e. the code is being replayed.
+    async fn use_totp_step(&self, account_id: ID, step: i64) -> AppResult<bool>;
+
+    /// Confirms the enrolled secret and replaces the recovery codes of the account.
+    async fn confirm_totp(
+        &self,
+        account_id: ID,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> AppResult<()>;
+
+    /// Removes the secret and the recovery codes of the account.
+    async fn delete_totp(&self, account_id: ID) -> AppResult<()>;
+
+    async fn replace_recovery_codes(
+        &self,
+        account_id: ID,
+        code_hashes: Vec<String>,
+    ) -> AppResult<()>;
+
+    /// Marks an unused recovery code as used. Returns `false` if there is no such code.
+    async fn use_recovery_code(
+        &self,
+        account_id: ID,
+        code_hash: String,
+        now: i64,
+    ) -> AppResult<bool>;
+}
+
+#[async_trait]
+pub trait HealthRepository: Send + Sync {
+    /// Whether the storage answers and its schema is up to date.
+    async fn check_readiness(&self) -> Readiness;
+
+    /// State of the connection pool, `None` for backends without one.
+    async fn fetch_pool_stats(&self) -> Option<PoolStats>;
+}
+
+/// Everything the services store, implemented by every backend.
+pub trait Repository:
+    AccountRepository
+    + RoleRepository
+    + FeeRepository
+    + PaymentRepository
+    + BalanceRepository
+    + TokenRepository
+    + MfaRepository
+    + HealthRepository
+{
+}
+
+impl<T> Repository for T where
+    T: AccountRepository
+        + RoleRepository
+        + FeeRepository
+        + PaymentRepository
+        + BalanceRepository
+        + TokenRepository
+        + MfaRepository
+        + HealthRepository
+{
+}
@@ -1,10 +1,11 @@
 #![allow(non_camel_case_types)]
 
 use sqlx::prelude::FromRow;
+use uuid::Uuid;
 
 pub type ID = i32;
 
-#[derive(FromRow)]
+#[derive(Clone, FromRow)]
 pub struct DB_Account {
     pub id: ID,
     pub phone_number: String,
@@ -13,19 +14,34 @@ pub struct DB_Account {
     pub email: String,
     pub hashed_password: String,
     pub password_set_ts: i64,
+    pub member_since: i64,
 }
 
 #[derive(FromRow)]
+pub struct DB_AccountListing {
+    pub id: ID,
+    pub name: String,
+    pub lastname: String,
+    pub email: String,
+    pub phone_number: String,
+    pub roles: Vec<String>,
+    pub in_arrears: bool,
+    pub sort_key: String,
+}
+
+#[derive(Clone, FromRow)]
 pub struct DB_Role {
     pub id: ID,
     pub role: String,
 }
 
-#[derive(FromRow)]
+#[derive(Clone, FromRow)]
 pub struct DB_MonthlyFee {
     pub id: ID,
     pub year: i32,
     pub month: i32,
+    pub amount: i64,
+    pub currency: String,
 }
 
 #[derive(FromRow)]
@@ -34,8 +50,95 @@ pub struct DB_UserRole {
     pub role_id: ID,
 }
 
-#[derive(FromRow)]
+#[derive(Clone, FromRow)]
 pub struct DB_Payment {
-    pub user_id: ID,
+    pub id: ID,
+    pub account_id: ID,
     pub fee_id: ID,
-}
\ No newline at end of file
+    pub amount: i64,
+    pub currency: String,
+    pub paid_at: i64,
+}
+
+/// A row of `payments` joined with its fee period, or a row of `pre_payments`.
+#[derive(Clone, FromRow)]
+pub struct DB_PeriodPayment {
+    pub id: ID,
+    pub year: i32,
+    pub month: i32,
+    pub amount: i64,
+    pub currency: String,
+    pub paid_at: i64,
+}
+
+#[derive(Clone, FromRow)]
+pub struct DB_AccountPeriodPayment {
+    pub account_id: ID,
+    #[sqlx(flatten)]
+    pub payment: DB_PeriodPayment,
+}
+
+#[derive(Clone, FromRow)]
+pub struct DB_PrePayment {
+    pub account_id: ID,
+    #[sqlx(flatten)]
+    pub payment: DB_PeriodPayment,
+    pub applied_payment_id: Option<ID>,
+    pub applied_at: Option<i64>,
+}
+
+/// Reversal of either a payment or a pre-payment.
+#[derive(Clone, FromRow)]
+pub struct DB_PaymentReversal {
+    pub id: ID,
+    pub payment_id: Option<ID>,
+    pub pre_payment_id: Option<ID>,
+    pub amount: i64,
+    pub reversed_by: ID,
+    pub reversed_at: i64,
+}
+
+#[derive(FromRow)]
+pub struct DB_PeriodStatement {
+    pub year: i32,
+    pub month: i32,
+    pub due: i64,
+    pub paid: i64,
+    pub precovered: i64,
+    pub currency: String,
+    pub started: bool,
+}
+
+#[derive(FromRow)]
+pub struct DB_Arrears {
+    pub account_id: ID,
+    pub name: String,
+    pub lastname: String,
+    pub email: String,
+    pub currency: String,
+    pub outstanding: i64,
+    pub months_overdue: i64,
+    pub since_year: i32,
+    pub since_month: i32,
+}
+
+#[derive(Clone, FromRow)]
+pub struct DB_RefreshToken {
+    pub jti: Uuid,
+    pub family_id: Uuid,
+    pub account_id: ID,
+    pub token_hash: String,
+    pub issued_at: i64,
+    pub expires_at: i64,
+    pub rotated_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+#[derive(Clone, FromRow)]
+pub struct DB_AccountTotp {
+    pub account_id: ID,
+    pub secret: String,
+    pub confirmed: bool,
+    pub last_used_step: Option<i64>,
+    pub created_at: i64,
+}
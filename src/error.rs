@@ -0,0 +1,124 @@
+use actix_web::{HttpRequest, HttpResponse, ResponseError, http::StatusCode, rt::task::JoinError};
+
+use crate::{
+    money::MoneyError,
+    response::{FieldError, HttpErrorBody},
+};
+
+pub type AppResult<T> = Result<T, AppError>;
+
+/// Errors surfaced to the clients. The message of an internal error is never sent, the cause is
+/// only logged.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("Request has invalid fields.")]
+    InvalidFields(Vec<FieldError>),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("Internal server error.")]
+    Internal(#[source] anyhow::Error),
+}
+
+impl AppError {
+    pub fn not_found(msg: impl ToString) -> AppError {
+        AppError::NotFound(msg.to_string())
+    }
+
+    pub fn conflict(msg: impl ToString) -> AppError {
+        AppError::Conflict(msg.to_string())
+    }
+
+    pub fn validation(msg: impl ToString) -> AppError {
+        AppError::Validation(msg.to_string())
+    }
+
+    pub fn unauthorized(msg: impl ToString) -> AppError {
+        AppError::Unauthorized(msg.to_string())
+    }
+
+    pub fn forbidden(msg: impl ToString) -> AppError {
+        AppError::Forbidden(msg.to_string())
+    }
+
+    /// Machine-readable code of the error, stable across releases.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::Validation(_) | AppError::InvalidFields(_) => "validation",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    /// Keeps the kind of an `AppError` that was passed through `anyhow`.
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<AppError>() {
+            Ok(err) => err,
+            Err(err) => AppError::Internal(err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        AppError::Internal(err.into())
+    }
+}
+
+/// Amounts of requests are checked while deserializing them, so this is a stored amount that can
+/// not be read back.
+impl From<MoneyError> for AppError {
+    fn from(err: MoneyError) -> Self {
+        AppError::Internal(err.into())
+    }
+}
+
+impl From<JoinError> for AppError {
+    fn from(err: JoinError) -> Self {
+        AppError::Internal(err.into())
+    }
+}
+
+/// Error handler of the extractor configs, rejects malformed requests as validation problems.
+pub fn reject_malformed_request<E: ToString>(err: E, _: &HttpRequest) -> actix_web::Error {
+    AppError::validation(err).into()
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Validation(_) | AppError::InvalidFields(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // logged in the span of the request, which carries its correlation id
+        if let AppError::Internal(err) = self {
+            tracing::error!(error = format!("{err:#}"), "internal error");
+        }
+
+        match self {
+            AppError::InvalidFields(errors) => {
+                HttpResponse::build(self.status_code()).invalid_fields_body(self, errors.clone())
+            }
+            _ => HttpResponse::build(self.status_code()).problem_body(self.code(), self),
+        }
+    }
+}
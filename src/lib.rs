@@ -0,0 +1,15 @@
+pub mod api;
+pub mod app;
+pub mod auth;
+pub mod config;
+pub mod correlation;
+pub mod db;
+pub mod error;
+pub mod logging;
+pub mod mail;
+pub mod metrics;
+pub mod money;
+pub mod password;
+pub mod response;
+pub mod service;
+pub mod util;
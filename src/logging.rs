@@ -0,0 +1,128 @@
+use std::{
+    future::Future,
+    io::{self, IsTerminal},
+    pin::Pin,
+    time::Instant,
+};
+
+use actix_web::{
+    Error, HttpMessage,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{Ready, ready};
+use tracing::{Instrument, field::Empty};
+use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::{
+    auth::Claims,
+    config::{LogConfig, LogFormat},
+    correlation::current_request,
+};
+
+/// Installs the global subscriber, writing the events to stderr.
+/// Events of the `log` crate, such as those of actix, are logged as well.
+pub fn init(config: &LogConfig) {
+    let filter = EnvFilter::new(&config.level);
+    let registry = tracing_subscriber::registry().with(filter);
+
+    match config.format {
+        LogFormat::Human => registry
+            .with(
+                fmt::layer()
+                    .with_ansi(io::stderr().is_terminal())
+                    .with_writer(io::stderr),
+            )
+            .init(),
+        LogFormat::Json => registry
+            .with(
+                fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_writer(io::stderr),
+            )
+            .init(),
+    }
+}
+
+/// Runs every request in a `request` span and logs its completion with the status and latency.
+///
+/// Only the path of the request is recorded, never the query, the headers or the body,
+/// so neither passwords nor tokens reach the logs. The subject is that of an access token
+/// validated while handling the request.
+///
+/// Must be wrapped by [`crate::correlation::CorrelationId`], whose id the span carries.
+pub struct RequestTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware { service }))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let correlation_id = current_request().map(|r| r.correlation_id);
+        let span = tracing::info_span!(
+            "request",
+            method = %req.method(),
+            path = req.path(),
+            correlation_id = correlation_id.as_deref().unwrap_or("-"),
+            subject = Empty,
+            status = Empty,
+            latency_ms = Empty,
+        );
+        let started = Instant::now();
+
+        let response = span.in_scope(|| self.service.call(req));
+
+        let request_span = span.clone();
+        Box::pin(
+            async move {
+                let response = response.await;
+                let status = match &response {
+                    Ok(response) => {
+                        if let Some(claims) = response.request().extensions().get::<Claims>() {
+                            request_span.record("subject", claims.sub.as_str());
+                        }
+                        response.status()
+                    }
+                    Err(err) => err.as_response_error().status_code(),
+                };
+                request_span.record("status", status.as_u16());
+                request_span.record("latency_ms", started.elapsed().as_millis() as u64);
+
+                match status.is_server_error() {
+                    true => tracing::warn!("request failed"),
+                    false => tracing::info!("request completed"),
+                }
+
+                response
+            }
+            .instrument(span),
+        )
+    }
+}
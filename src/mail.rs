@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use actix_web::web;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::util::unix_timestamp;
+
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait MailSender: Send + Sync {
+    async fn send(&self, mail: Mail) -> anyhow::Result<()>;
+}
+
+/// Writes every mail as a plain text file into a directory instead of delivering it.
+/// Meant for local development and testing.
+pub struct OutboxMailSender {
+    dir: PathBuf,
+}
+
+impl OutboxMailSender {
+    pub fn new(dir: impl Into<PathBuf>) -> anyhow::Result<OutboxMailSender> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(OutboxMailSender { dir })
+    }
+}
+
+#[async_trait]
+impl MailSender for OutboxMailSender {
+    async fn send(&self, mail: Mail) -> anyhow::Result<()> {
+        let path = self
+            .dir
+            .join(format!("{}-{}.eml", unix_timestamp(), Uuid::new_v4()));
+        let content = format!(
+            "To: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            mail.to, mail.subject, mail.body
+        );
+
+        web::block(move || std::fs::write(path, content)).await??;
+
+        Ok(())
+    }
+}
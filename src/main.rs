@@ -1,58 +1,105 @@
-use std::env;
+use std::{env, sync::Arc};
 
-use actix_web::{App, HttpServer, web};
-use db::{DbConnectionParameters, init_db_connection};
-
-mod api;
-mod auth;
-mod db;
-mod password;
-mod response;
-mod service;
-mod util;
+use actix_web::{HttpServer, web};
+use agem_server::{
+    api::validation::normalize_email,
+    app::{AppState, app},
+    auth::{keys::JwtKeys, throttle::LoginThrottle},
+    config::{Config, DatabaseConfig},
+    db::{
+        DatabaseConnection, init_db_connection, memory::MemoryRepository, run_migrations,
+        types::DB_Account,
+    },
+    logging,
+    mail::{MailSender, OutboxMailSender},
+    metrics::Metrics,
+    password::PasswordHasher,
+    service,
+    util::unix_timestamp,
+};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    dotenv::dotenv().unwrap();
-
-    let db = web::Data::new(
-        init_db_connection(DbConnectionParameters {
-            host: env::var("SQLDB_HOST").unwrap(),
-            port: env::var("SQLDB_PORT").unwrap().parse::<u32>().unwrap(),
-            database: env::var("SQLDB_DATABASE").unwrap(),
-            user: env::var("SQLDB_USER").unwrap(),
-            password: env::var("SQLDB_PASSWORD").unwrap(),
-        })
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    logging::init(&config.log);
+
+    // `agem-server migrate` only brings the schema up to date
+    let migrate_only = env::args().nth(1).as_deref() == Some("migrate");
+
+    let password_hasher = PasswordHasher::new(config.auth.bcrypt_cost);
+
+    let db: DatabaseConnection = match config.database {
+        DatabaseConfig::Postgres(postgres) => {
+            let pool = init_db_connection(postgres.connection, &postgres.pool)
+                .await
+                .expect("Database connection could not be initialized.");
+            if postgres.run_migrations || migrate_only {
+                run_migrations(&pool)
+                    .await
+                    .expect("Database migrations could not be applied.");
+            }
+            Arc::new(pool)
+        }
+        DatabaseConfig::Memory => Arc::new(MemoryRepository::new()),
+    };
+    if migrate_only {
+        return Ok(());
+    }
+
+    if let Some(admin) = config.admin {
+        let account = DB_Account {
+            id: 0,
+            phone_number: String::new(),
+            name: "Admin".to_string(),
+            lastname: String::new(),
+            email: normalize_email(&admin.email),
+            hashed_password: password_hasher.hash_password(&admin.password),
+            password_set_ts: unix_timestamp() as i64,
+            member_since: unix_timestamp() as i64,
+        };
+        let created = service::account::create_admin_account(db.clone(), account)
+            .await
+            .expect("Admin account could not be created.");
+        if created {
+            tracing::info!("admin account created");
+        }
+    }
+    let db = web::Data::new(db);
+
+    let state = AppState {
+        db,
+        jwt_keys: web::Data::new(
+            JwtKeys::load_from_file(&config.auth.jwt_keys_file)
+                .expect("JWT keys could not be loaded."),
+        ),
+        token_lifetimes: web::Data::new(config.auth.token_lifetimes),
+        password_hasher: web::Data::new(password_hasher),
+        mail_sender: web::Data::from(Arc::new(
+            OutboxMailSender::new(&config.mail.outbox_dir)
+                .expect("Mail outbox could not be initialized."),
+        ) as Arc<dyn MailSender>),
+        login_throttle: web::Data::new(LoginThrottle::default()),
+        metrics: web::Data::new(Metrics::new().expect("Metrics could not be registered.")),
+        cors: config.cors,
+    };
+
+    let server = HttpServer::new(move || app(state.clone()))
+        .client_request_timeout(config.server.client_request_timeout)
+        .shutdown_timeout(config.server.shutdown_timeout_secs);
+
+    let server = match config.server.workers {
+        Some(workers) => server.workers(workers),
+        None => server,
+    };
+
+    server
+        .bind((config.server.host, config.server.port))?
+        .run()
         .await
-        .expect("Database connection could not be initialized."),
-    );
-
-    HttpServer::new(move || {
-        App::new()
-            // -- db --
-            .app_data(db.clone())
-            // -- auth --
-            .service(
-                web::scope("/auth")
-                    .service(api::auth::get_me_from_access_token)
-                    .service(api::auth::create_access_token)
-                    .service(api::auth::refresh_access_token),
-            )
-            // -- api --
-            .service(
-                web::scope("/api")
-                    .service(api::whoami)
-                    // -- v1 --
-                    .service(
-                        web::scope("/v1")
-                            // -- -- account --
-                            .service(api::v1::account::create_account)
-                            .service(api::v1::account::create_roles)
-                            .service(api::v1::account::add_roles_to_account),
-                    ),
-            )
-    })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
 }
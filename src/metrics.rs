@@ -0,0 +1,244 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    Error,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::StatusCode,
+    web,
+};
+use futures_util::future::{Ready, ready};
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+
+use crate::service::health::PoolStats;
+
+/// Route label of the requests answered without reaching a route, such as unknown paths or those
+/// rejected by the authentication guard, so they do not add a series per path.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// How a pair of access and refresh tokens was obtained.
+#[derive(Debug, Clone, Copy)]
+pub enum TokenGrant {
+    Password,
+    /// The password was verified earlier, with a second factor exchanged at `/mfa/verify`.
+    Mfa,
+    Refresh,
+}
+
+impl TokenGrant {
+    fn label(self) -> &'static str {
+        match self {
+            TokenGrant::Password => "password",
+            TokenGrant::Mfa => "mfa",
+            TokenGrant::Refresh => "refresh",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LoginFailure {
+    InvalidCredentials,
+    InvalidCode,
+    /// The account or the client ip was locked out by the login throttle.
+    Throttled,
+}
+
+impl LoginFailure {
+    fn label(self) -> &'static str {
+        match self {
+            LoginFailure::InvalidCredentials => "invalid_credentials",
+            LoginFailure::InvalidCode => "invalid_code",
+            LoginFailure::Throttled => "throttled",
+        }
+    }
+}
+
+/// Metrics of the server, rendered in the Prometheus text format at `/metrics`.
+///
+/// The pool gauges are kept apart and only rendered by backends with a connection pool.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: HistogramVec,
+    tokens_issued: IntCounterVec,
+    refresh_token_reuses: IntCounter,
+    failed_logins: IntCounterVec,
+    pool_registry: Registry,
+    pool_connections: IntGauge,
+    pool_idle_connections: IntGauge,
+    pool_max_connections: IntGauge,
+    pool_acquire_wait: Gauge,
+}
+
+impl Metrics {
+    pub fn new() -> prometheus::Result<Metrics> {
+        let registry = Registry::new_custom(Some("agem".to_string()), None)?;
+        let pool_registry = Registry::new_custom(Some("agem".to_string()), None)?;
+
+        let http_requests = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to respond to HTTP requests, by route pattern.",
+            ),
+            &["method", "route", "status"],
+        )?;
+        let tokens_issued = IntCounterVec::new(
+            Opts::new(
+                "auth_tokens_issued_total",
+                "Pairs of access and refresh tokens issued, by grant.",
+            ),
+            &["grant"],
+        )?;
+        let refresh_token_reuses = IntCounter::new(
+            "auth_refresh_token_reuses_total",
+            "Rotated refresh tokens presented again, each revoking its token family.",
+        )?;
+        let failed_logins = IntCounterVec::new(
+            Opts::new(
+                "auth_failed_logins_total",
+                "Rejected login attempts, by reason.",
+            ),
+            &["reason"],
+        )?;
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(tokens_issued.clone()))?;
+        registry.register(Box::new(refresh_token_reuses.clone()))?;
+        registry.register(Box::new(failed_logins.clone()))?;
+
+        let pool_connections = IntGauge::new(
+            "db_pool_connections",
+            "Connections of the database pool, idle or in use.",
+        )?;
+        let pool_idle_connections = IntGauge::new(
+            "db_pool_idle_connections",
+            "Idle connections of the database pool.",
+        )?;
+        let pool_max_connections = IntGauge::new(
+            "db_pool_max_connections",
+            "Connections the database pool may open at most.",
+        )?;
+        let pool_acquire_wait = Gauge::new(
+            "db_pool_acquire_wait_seconds",
+            "Longest time a query waited for a connection of the database pool since the last scrape.",
+        )?;
+        pool_registry.register(Box::new(pool_connections.clone()))?;
+        pool_registry.register(Box::new(pool_idle_connections.clone()))?;
+        pool_registry.register(Box::new(pool_max_connections.clone()))?;
+        pool_registry.register(Box::new(pool_acquire_wait.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            http_requests,
+            tokens_issued,
+            refresh_token_reuses,
+            failed_logins,
+            pool_registry,
+            pool_connections,
+            pool_idle_connections,
+            pool_max_connections,
+            pool_acquire_wait,
+        })
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: StatusCode, took: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, status.as_str()])
+            .observe(took.as_secs_f64());
+    }
+
+    pub fn record_tokens_issued(&self, grant: TokenGrant) {
+        self.tokens_issued.with_label_values(&[grant.label()]).inc();
+    }
+
+    pub fn record_refresh_token_reuse(&self) {
+        self.refresh_token_reuses.inc();
+    }
+
+    pub fn record_failed_login(&self, reason: LoginFailure) {
+        self.failed_logins
+            .with_label_values(&[reason.label()])
+            .inc();
+    }
+
+    /// Every metric in the Prometheus text format, with the pool gauges set from `pool`.
+    pub fn render(&self, pool: Option<PoolStats>) -> prometheus::Result<String> {
+        let mut families = self.registry.gather();
+        if let Some(pool) = pool {
+            self.pool_connections.set(pool.size as i64);
+            self.pool_idle_connections.set(pool.idle as i64);
+            self.pool_max_connections.set(pool.max_size as i64);
+            self.pool_acquire_wait.set(pool.acquire_wait.as_secs_f64());
+            families.extend(self.pool_registry.gather());
+        }
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&families, &mut buffer)?;
+        Ok(String::from_utf8(buffer).expect("text format is UTF-8"))
+    }
+}
+
+/// Observes the duration of every request in [`Metrics`], labelled with the pattern of the matched
+/// route rather than the path, so ids in paths do not add series.
+///
+/// Must wrap the other middlewares, so the requests they respond to are observed as well.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let metrics = req.app_data::<web::Data<Metrics>>().cloned();
+        let method = req.method().clone();
+        let started = Instant::now();
+
+        let response = self.service.call(req);
+
+        Box::pin(async move {
+            let response = response.await?;
+            if let Some(metrics) = metrics {
+                let route = response.request().match_pattern();
+                metrics.observe_request(
+                    method.as_str(),
+                    route.as_deref().unwrap_or(UNMATCHED_ROUTE),
+                    response.status(),
+                    started.elapsed(),
+                );
+            }
+
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MoneyError {
+    #[error("Currency must be a three letter ISO 4217 code.")]
+    InvalidCurrency,
+    #[error("Amount must be a decimal number.")]
+    InvalidAmount,
+    #[error("{0} amounts can have at most {1} decimal places.")]
+    TooManyDecimals(Currency, u32),
+    #[error("Amount is too large.")]
+    Overflow,
+}
+
+/// ISO 4217 currency code, e.g. `TRY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub fn parse(code: &str) -> Result<Currency, MoneyError> {
+        match code.as_bytes() {
+            &[a, b, c] if code.bytes().all(|b| b.is_ascii_uppercase()) => Ok(Currency([a, b, c])),
+            _ => Err(MoneyError::InvalidCurrency),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        // only constructed from ascii uppercase letters
+        std::str::from_utf8(&self.0).unwrap()
+    }
+
+    /// Number of decimal places of the minor unit, e.g. 2 for kuruş or cents.
+    pub fn minor_unit_digits(&self) -> u32 {
+        match self.code() {
+            "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF"
+            | "UGX" | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Currency::parse(&code).map_err(de::Error::custom)
+    }
+}
+
+/// Exact amount of money, kept as an integer count of the currency's minor units.
+///
+/// Serialized as `{"amount": "150.50", "currency": "TRY"}`, the amount being a decimal string
+/// so that it never passes through a float.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Money {
+    pub minor_units: i64,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(minor_units: i64, currency: Currency) -> Money {
+        Money {
+            minor_units,
+            currency,
+        }
+    }
+
+    pub fn zero(currency: Currency) -> Money {
+        Money::new(0, currency)
+    }
+
+    /// Builds the amount from the columns it is stored in, `BIGINT` minor units and `CHAR(3)` code.
+    pub fn from_db(minor_units: i64, currency: &str) -> Result<Money, MoneyError> {
+        Ok(Money::new(minor_units, Currency::parse(currency)?))
+    }
+
+    /// Parses a decimal amount like `150`, `150.5` or `-12.75` without rounding.
+    pub fn parse(amount: &str, currency: Currency) -> Result<Money, MoneyError> {
+        let digits = currency.minor_unit_digits();
+
+        let (negative, unsigned) = match amount.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, amount),
+        };
+        let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+        let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(MoneyError::InvalidAmount);
+        }
+        if unsigned.contains('.') && fraction.is_empty() {
+            return Err(MoneyError::InvalidAmount);
+        }
+        if fraction.len() as u32 > digits {
+            return Err(MoneyError::TooManyDecimals(currency, digits));
+        }
+
+        let scale = 10i64.pow(digits);
+        let whole: i64 = whole.parse().map_err(|_| MoneyError::Overflow)?;
+        let fraction: i64 = match fraction.is_empty() {
+            true => 0,
+            false => fraction.parse::<i64>().unwrap() * 10i64.pow(digits - fraction.len() as u32),
+        };
+
+        let minor_units = whole
+            .checked_mul(scale)
+            .and_then(|w| w.checked_add(fraction))
+            .ok_or(MoneyError::Overflow)?;
+
+        Ok(Money::new(
+            match negative {
+                true => -minor_units,
+                false => minor_units,
+            },
+            currency,
+        ))
+    }
+
+    /// Decimal representation of the amount without the currency, e.g. `150.50`.
+    pub fn amount_string(&self) -> String {
+        let digits = self.currency.minor_unit_digits();
+        let sign = if self.minor_units < 0 { "-" } else { "" };
+        let units = self.minor_units.unsigned_abs();
+
+        match digits {
+            0 => format!("{sign}{units}"),
+            _ => {
+                let scale = 10u64.pow(digits);
+                format!(
+                    "{sign}{}.{:0width$}",
+                    units / scale,
+                    units % scale,
+                    width = digits as usize
+                )
+            }
+        }
+    }
+
+    /// Returns `None` if the currencies differ or the sum overflows.
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        match self.currency == other.currency {
+            true => Some(Money::new(
+                self.minor_units.checked_add(other.minor_units)?,
+                self.currency,
+            )),
+            false => None,
+        }
+    }
+
+    /// Returns `None` if the currencies differ or the difference overflows.
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        match self.currency == other.currency {
+            true => Some(Money::new(
+                self.minor_units.checked_sub(other.minor_units)?,
+                self.currency,
+            )),
+            false => None,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor_units == 0
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.minor_units > 0
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount_string(), self.currency)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MoneyRepr {
+    amount: String,
+    currency: Currency,
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoneyRepr {
+            amount: self.amount_string(),
+            currency: self.currency,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = MoneyRepr::deserialize(deserializer)?;
+        Money::parse(&repr.amount, repr.currency).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn currency(code: &str) -> Currency {
+        Currency::parse(code).unwrap()
+    }
+
+    fn parse(amount: &str, code: &str) -> Result<Money, MoneyError> {
+        Money::parse(amount, currency(code))
+    }
+
+    #[test]
+    fn parse_scales_fractions_to_minor_units() {
+        assert_eq!(parse("150", "TRY").unwrap().minor_units, 15000);
+        assert_eq!(parse("150.5", "TRY").unwrap().minor_units, 15050);
+        assert_eq!(parse("150.05", "TRY").unwrap().minor_units, 15005);
+        assert_eq!(parse("0.01", "TRY").unwrap().minor_units, 1);
+        assert_eq!(parse("1.234", "KWD").unwrap().minor_units, 1234);
+        assert_eq!(parse("150.50", "TRY").unwrap().amount_string(), "150.50");
+    }
+
+    #[test]
+    fn parse_rejects_more_decimals_than_the_minor_unit() {
+        assert!(matches!(
+            parse("1.001", "TRY"),
+            Err(MoneyError::TooManyDecimals(_, 2))
+        ));
+        assert!(matches!(
+            parse("1.0001", "KWD"),
+            Err(MoneyError::TooManyDecimals(_, 3))
+        ));
+    }
+
+    #[test]
+    fn parse_takes_whole_amounts_of_currencies_without_minor_units() {
+        assert_eq!(parse("1500", "JPY").unwrap().minor_units, 1500);
+        assert!(matches!(
+            parse("1500.5", "JPY"),
+            Err(MoneyError::TooManyDecimals(_, 0))
+        ));
+        assert_eq!(parse("1500", "JPY").unwrap().amount_string(), "1500");
+    }
+
+    #[test]
+    fn parse_keeps_the_sign_of_negative_amounts() {
+        let amount = parse("-12.75", "TRY").unwrap();
+        assert_eq!(amount.minor_units, -1275);
+        assert!(!amount.is_positive());
+        assert_eq!(parse("-0.5", "TRY").unwrap().minor_units, -50);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_amounts() {
+        for amount in [
+            "", "-", ".5", "1.", "1,5", "+1", "1e3", " 1", "1.2.3", "--1",
+        ] {
+            assert!(
+                matches!(parse(amount, "TRY"), Err(MoneyError::InvalidAmount)),
+                "{amount:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_reports_overflow() {
+        // i64::MAX minor units fit, one more does not
+        assert_eq!(
+            parse("92233720368547758.07", "TRY").unwrap().minor_units,
+            i64::MAX
+        );
+        assert!(matches!(
+            parse("92233720368547758.08", "TRY"),
+            Err(MoneyError::Overflow)
+        ));
+        assert!(matches!(
+            parse("99999999999999999999", "JPY"),
+            Err(MoneyError::Overflow)
+        ));
+    }
+}
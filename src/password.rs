@@ -1,12 +1,31 @@
-use bcrypt::{DEFAULT_COST, hash, verify};
+use bcrypt::{hash, verify};
 
-pub fn hash_password(password: &str) -> String {
-    hash(password, DEFAULT_COST).unwrap()
+/// Hashes passwords at the configured bcrypt cost.
+pub struct PasswordHasher {
+    cost: u32,
+    /// Hash of the same cost as real ones, for [`PasswordHasher::verify_dummy_password`].
+    dummy_hash: String,
 }
 
-pub fn verify_password(password: &str, hashed_password: &str) -> bool {
-    match verify(password, hashed_password) {
-        Ok(r) => r,
-        Err(_err) => false,
+impl PasswordHasher {
+    pub fn new(cost: u32) -> PasswordHasher {
+        PasswordHasher {
+            cost,
+            dummy_hash: hash("dummy-password", cost).unwrap(),
+        }
+    }
+
+    pub fn hash_password(&self, password: &str) -> String {
+        hash(password, self.cost).unwrap()
+    }
+
+    /// Burns the same time as [`verify_password`] for a login attempt on an unknown account,
+    /// so response times do not reveal which emails are registered.
+    pub fn verify_dummy_password(&self, password: &str) {
+        let _ = verify(password, &self.dummy_hash);
     }
 }
+
+pub fn verify_password(password: &str, hashed_password: &str) -> bool {
+    verify(password, hashed_password).unwrap_or_default()
+}
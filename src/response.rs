@@ -1,27 +1,122 @@
-use actix_web::{HttpResponse, HttpResponseBuilder};
+use actix_web::{
+    HttpResponse, HttpResponseBuilder,
+    http::{
+        StatusCode,
+        header::{CONTENT_TYPE, HeaderValue},
+    },
+};
 use serde::Serialize;
 
+use crate::correlation::current_request;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
 #[derive(Serialize)]
 struct JsonMessage {
     msg: String,
 }
 
+/// RFC 7807 problem details, extended with a machine-readable code and the correlation id.
+#[derive(Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<String>,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
+    /// Invalid fields of a rejected request.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
 pub trait HttpJsonMessageBody {
     fn json_message_body(self, msg: impl ToString) -> HttpResponse;
 }
 
 impl HttpJsonMessageBody for HttpResponseBuilder {
     fn json_message_body(mut self, msg: impl ToString) -> HttpResponse {
-        self.json(JsonMessage { msg: msg.to_string() })
+        self.json(JsonMessage {
+            msg: msg.to_string(),
+        })
+    }
+}
+
+/// Problem code of the errors that do not name one.
+fn status_code_name(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "validation",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::TOO_MANY_REQUESTS => "too_many_requests",
+        status if status.is_server_error() => "internal",
+        _ => "error",
     }
 }
 
+/// Turns the response into a problem, keeping its status and headers.
+fn into_problem(
+    mut response: HttpResponse,
+    code: &'static str,
+    detail: String,
+    errors: Vec<FieldError>,
+) -> HttpResponse {
+    let status = response.status();
+    let request = current_request();
+
+    let problem = Problem {
+        problem_type: format!("urn:agem:problem:{code}"),
+        title: status.canonical_reason().unwrap_or("Error"),
+        status: status.as_u16(),
+        detail,
+        instance: request.as_ref().map(|r| r.path.clone()),
+        code,
+        correlation_id: request.map(|r| r.correlation_id),
+        errors,
+    };
+
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    response
+        .set_body(serde_json::to_string(&problem).unwrap())
+        .map_into_boxed_body()
+}
+
 pub trait HttpErrorBody {
+    /// `application/problem+json` body with the status of the builder and `msg` as the detail.
     fn error_body(self, msg: impl ToString) -> HttpResponse;
+    /// Same as `error_body`, with a specific problem code.
+    fn problem_body(self, code: &'static str, msg: impl ToString) -> HttpResponse;
+    /// Problem listing the invalid fields of a request.
+    fn invalid_fields_body(self, msg: impl ToString, errors: Vec<FieldError>) -> HttpResponse;
 }
 
 impl HttpErrorBody for HttpResponseBuilder {
     fn error_body(mut self, msg: impl ToString) -> HttpResponse {
-        self.json(JsonMessage { msg: msg.to_string() })
+        let response = self.finish();
+        let code = status_code_name(response.status());
+        into_problem(response, code, msg.to_string(), Vec::new())
     }
-}
\ No newline at end of file
+
+    fn problem_body(mut self, code: &'static str, msg: impl ToString) -> HttpResponse {
+        into_problem(self.finish(), code, msg.to_string(), Vec::new())
+    }
+
+    fn invalid_fields_body(mut self, msg: impl ToString, errors: Vec<FieldError>) -> HttpResponse {
+        into_problem(self.finish(), "validation", msg.to_string(), errors)
+    }
+}
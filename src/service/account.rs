@@ -1,228 +1,178 @@
-use actix_web::rt::task::JoinHandle;
+use tracing::instrument;
 
-use crate::db::{
-    DatabaseConnection,
-    types::{DB_Account, DB_MonthlyFee, DB_Role, ID},
+use crate::{
+    db::{
+        DatabaseConnection,
+        types::{DB_Account, DB_AccountListing, ID},
+    },
+    error::{AppError, AppResult},
 };
 
-use super::{Account, MonthlyFee, Payments, Role};
-
-pub async fn create_account(db: DatabaseConnection, account: DB_Account) -> anyhow::Result<bool> {
-    match sqlx::query(
-        r"
-            INSERT INTO
-            accounts(
-                phone_number, name, lastname, email, hashed_password, password_set_ts
-            )
-            VALUES (
-                $1, $2, $3, $4, $5, $6
-            );
-        ",
-    )
-    .bind(&account.phone_number)
-    .bind(&account.name)
-    .bind(&account.lastname)
-    .bind(&account.email)
-    .bind(&account.hashed_password)
-    .bind(&account.password_set_ts)
-    .execute(&db)
-    .await
-    {
-        Ok(_) => Ok(true),
-        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Ok(false),
-        Err(err) => Err(err)?,
+use super::{Account, AccountCursor, AccountPage, AccountSummary, Payments, Role};
+
+pub enum AccountOrder {
+    Id,
+    Name,
+    Lastname,
+    Email,
+}
+
+pub struct AccountFilter {
+    /// Every term has to appear in the name, lastname, email or phone number.
+    pub search: Vec<String>,
+    pub role: Option<String>,
+    pub in_arrears: Option<bool>,
+}
+
+pub(crate) fn into_account(account: DB_Account) -> Account {
+    Account {
+        id: account.id,
+        phone_number: account.phone_number,
+        name: account.name,
+        lastname: account.lastname,
+        email: account.email,
+        hashed_password: account.hashed_password,
+        password_set_ts: account.password_set_ts as u64,
+        member_since: account.member_since as u64,
     }
 }
 
-pub async fn create_roles(db: DatabaseConnection, roles: Vec<String>) -> anyhow::Result<bool> {
-    match sqlx::query(
-        r"
-            INSERT INTO
-            roles(
-                role
-            )
-            SELECT * FROM UNNEST(
-                $1::text[]
-            );
-        ",
-    )
-    .bind(&roles)
-    .execute(&db)
-    .await
-    {
-        Ok(_) => Ok(true),
-        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Ok(false),
-        Err(err) => Err(err)?,
+/// Page of the listed accounts, fetched with one account more than `count` to tell whether
+/// there is a next page.
+pub(crate) fn into_account_page(
+    total: u64,
+    mut accounts: Vec<DB_AccountListing>,
+    count: i64,
+) -> AccountPage {
+    let next = match accounts.len() as i64 > count {
+        true => {
+            accounts.truncate(count as usize);
+            accounts.last().map(|a| AccountCursor {
+                sort_key: a.sort_key.clone(),
+                id: a.id,
+            })
+        }
+        false => None,
+    };
+
+    AccountPage {
+        total,
+        accounts: accounts
+            .into_iter()
+            .map(|a| AccountSummary {
+                id: a.id,
+                name: a.name,
+                lastname: a.lastname,
+                email: a.email,
+                phone_number: a.phone_number,
+                roles: a.roles,
+                in_arrears: a.in_arrears,
+            })
+            .collect(),
+        next,
     }
 }
 
+// Spans skip the arguments, which hold emails, phone numbers and password hashes.
+#[instrument(skip_all)]
+pub async fn create_account(db: DatabaseConnection, account: DB_Account) -> AppResult<()> {
+    db.create_account(account).await
+}
+
+/// Creates the account holding the `Admin` role while no account holds it, so that a new
+/// deployment can be administered. Returns `false` if an account already holds the role.
+#[instrument(skip_all)]
+pub async fn create_admin_account(db: DatabaseConnection, account: DB_Account) -> AppResult<bool> {
+    db.create_admin_account(account).await
+}
+
+#[instrument(skip_all)]
+pub async fn create_roles(db: DatabaseConnection, roles: Vec<String>) -> AppResult<()> {
+    db.create_roles(roles).await
+}
+
+#[instrument(skip_all, fields(account_id = account_id))]
 pub async fn add_roles_to_account(
     db: DatabaseConnection,
+    granted_by: &[Role],
     account_id: ID,
     role_ids: Vec<ID>,
-) -> anyhow::Result<bool> {
-    match sqlx::query(
-        r"
-            INSERT INTO
-            account_roles(
-                account_id, role_id
-            )
-            SELECT * FROM UNNEST(
-                $1::int[], $2::int[]
-            );
-        ",
-    )
-    .bind((0..role_ids.len()).map(|_| account_id).collect::<Vec<_>>())
-    .bind(&role_ids)
-    .execute(&db)
-    .await
-    {
-        Ok(_) => Ok(true),
-        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Ok(false),
-        Err(err) => Err(err)?,
-    }
+) -> AppResult<()> {
+    super::role::check_grantable_roles(db.clone(), granted_by, account_id, &role_ids).await?;
+
+    db.add_roles_to_account(account_id, role_ids).await
 }
 
+#[instrument(skip_all)]
 pub async fn fetch_account_by_email(
     db: DatabaseConnection,
     email: String,
-) -> anyhow::Result<Option<Account>> {
-    let account: Option<DB_Account> = match sqlx::query_as(
-        r"
-            SELECT * FROM accounts
-            WHERE email = $1;
-        ",
-    )
-    .bind(email)
-    .fetch_one(&db)
-    .await
-    {
-        Ok(a) => Some(a),
-        Err(sqlx::Error::RowNotFound) => None,
-        Err(err) => Err(err)?,
-    };
-
-    Ok(account.map(|a| Account {
-        id: a.id,
-        phone_number: a.phone_number,
-        name: a.name,
-        lastname: a.lastname,
-        email: a.email,
-        hashed_password: a.hashed_password,
-        password_set_ts: a.password_set_ts as u64,
-    }))
+) -> AppResult<Option<Account>> {
+    db.fetch_account_by_email(email).await
 }
 
+#[instrument(skip_all)]
 pub async fn fetch_account_by_phone_number(
     db: DatabaseConnection,
     phone_number: String,
-) -> anyhow::Result<Option<Account>> {
-    let account: Option<DB_Account> = match sqlx::query_as(
-        r"
-            SELECT * FROM accounts
-            WHERE phone_number = $1;
-        ",
-    )
-    .bind(phone_number)
-    .fetch_one(&db)
-    .await
-    {
-        Ok(a) => Some(a),
-        Err(sqlx::Error::RowNotFound) => None,
-        Err(err) => Err(err)?,
-    };
+) -> AppResult<Option<Account>> {
+    db.fetch_account_by_phone_number(phone_number).await
+}
+
+#[instrument(skip_all, fields(account_id = account_id))]
+pub async fn fetch_account_by_id(db: DatabaseConnection, account_id: ID) -> AppResult<Account> {
+    db.fetch_account_by_id(account_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Account not found."))
+}
+
+#[instrument(skip_all, fields(account_id = account_id))]
+pub async fn fetch_account_roles(db: DatabaseConnection, account_id: ID) -> AppResult<Vec<Role>> {
+    db.fetch_account_roles(account_id).await
+}
 
-    Ok(account.map(|a| Account {
-        id: a.id,
-        phone_number: a.phone_number,
-        name: a.name,
-        lastname: a.lastname,
-        email: a.email,
-        hashed_password: a.hashed_password,
-        password_set_ts: a.password_set_ts as u64,
-    }))
+#[instrument(skip_all, fields(account_id = account_id))]
+pub async fn fetch_account_payments(db: DatabaseConnection, account_id: ID) -> AppResult<Payments> {
+    db.fetch_account_payments(account_id).await
 }
 
-pub async fn fetch_account_roles(
+#[instrument(skip_all, fields(account_id = account_id))]
+pub async fn update_password(
     db: DatabaseConnection,
     account_id: ID,
-) -> anyhow::Result<Vec<Role>> {
-    let roles: Vec<DB_Role> = sqlx::query_as(
-        r"
-            SELECT
-                roles.id,
-                roles.role
-            FROM account_roles
-            JOIN roles ON roles.id = account_roles.role_id
-                AND account_roles.account_id = $1;
-        ",
-    )
-    .bind(account_id)
-    .fetch_all(&db)
-    .await?;
-
-    Ok(roles.into_iter().map(|r| Role { role: r.role }).collect())
-}
-
-pub async fn fetch_account_payments(
+    hashed_password: String,
+    password_set_ts: i64,
+) -> AppResult<()> {
+    db.update_password(account_id, hashed_password, password_set_ts)
+        .await
+}
+
+/// Fails with a conflict if the phone number or the email is already used by another account.
+#[instrument(skip_all, fields(account_id = account_id))]
+pub async fn update_account_profile(
     db: DatabaseConnection,
     account_id: ID,
-) -> anyhow::Result<Payments> {
-    let join_payment_rows: JoinHandle<Result<Vec<DB_MonthlyFee>, _>> = {
-        let db = db.clone();
-        actix_web::rt::spawn(async move {
-            sqlx::query_as(
-                r"
-                    SELECT
-                        monthly_fees.id,
-                        monthly_fees.year,
-                        monthly_fees.month
-                    FROM payments
-                    JOIN monthly_fees ON monthly_fees.id = payments.fee_id;
-                        AND payments.account_id = $1
-                ",
-            )
-            .bind(account_id)
-            .fetch_all(&db)
-            .await
-        })
-    };
-
-    let join_pre_payment_rows: JoinHandle<Result<Vec<DB_MonthlyFee>, _>> = {
-        let db = db.clone();
-        actix_web::rt::spawn(async move {
-            sqlx::query_as(
-                r"
-                    SELECT
-                        pre_payments.id,
-                        pre_payments.year,
-                        pre_payments.month
-                    FROM pre_payments
-                    WHERE pre_payments.account_id = $1;
-                ",
-            )
-            .bind(account_id)
-            .fetch_all(&db)
-            .await
-        })
-    };
+    name: String,
+    lastname: String,
+    phone_number: String,
+    email: String,
+) -> AppResult<()> {
+    db.update_account_profile(account_id, name, lastname, phone_number, email)
+        .await
+}
 
-    Ok(Payments {
-        made: join_payment_rows
-            .await??
-            .into_iter()
-            .map(|m| MonthlyFee {
-                year: m.year as u32,
-                month: m.month as u32,
-            })
-            .collect(),
-        precovered: join_pre_payment_rows
-            .await??
-            .into_iter()
-            .map(|m| MonthlyFee {
-                year: m.year as u32,
-                month: m.month as u32,
-            })
-            .collect(),
-    })
+/// Lists the accounts matching the filter. Pages continue either from `offset`
+/// or, when given, right after the `after` cursor.
+#[instrument(skip_all, fields(offset = offset, count = count))]
+pub async fn fetch_accounts(
+    db: DatabaseConnection,
+    filter: AccountFilter,
+    order: AccountOrder,
+    ascending: bool,
+    after: Option<AccountCursor>,
+    offset: i64,
+    count: i64,
+) -> AppResult<AccountPage> {
+    db.fetch_accounts(filter, order, ascending, after, offset, count)
+        .await
 }
@@ -0,0 +1,89 @@
+use crate::{
+    db::{
+        DatabaseConnection,
+        types::{DB_Arrears, DB_PeriodStatement, ID},
+    },
+    error::AppResult,
+    money::{Currency, Money},
+};
+
+use super::{Arrears, PeriodStatement, Statement};
+
+pub enum ArrearsOrder {
+    Amount,
+    MonthsOverdue,
+}
+
+pub(crate) fn into_period_statement(period: DB_PeriodStatement) -> AppResult<PeriodStatement> {
+    let currency = Currency::parse(&period.currency)?;
+    let outstanding = (period.due - period.paid - period.precovered).max(0);
+
+    Ok(PeriodStatement {
+        year: period.year as u32,
+        month: period.month as u32,
+        due: Money::new(period.due, currency),
+        paid: Money::new(period.paid, currency),
+        precovered: Money::new(period.precovered, currency),
+        outstanding: Money::new(outstanding, currency),
+        overdue: period.started && outstanding > 0,
+    })
+}
+
+pub(crate) fn into_arrears(arrears: DB_Arrears) -> AppResult<Arrears> {
+    Ok(Arrears {
+        account_id: arrears.account_id,
+        name: arrears.name,
+        lastname: arrears.lastname,
+        email: arrears.email,
+        outstanding: Money::from_db(arrears.outstanding, &arrears.currency)?,
+        months_overdue: arrears.months_overdue as u32,
+        since_year: arrears.since_year as u32,
+        since_month: arrears.since_month as u32,
+    })
+}
+
+/// Sums the outstanding amounts of the overdue periods per currency.
+fn overdue_balance(periods: &[PeriodStatement]) -> anyhow::Result<Vec<Money>> {
+    let mut balance: Vec<Money> = Vec::new();
+    for period in periods.iter().filter(|p| p.overdue) {
+        let total = match balance
+            .iter_mut()
+            .find(|m| m.currency == period.outstanding.currency)
+        {
+            Some(total) => total,
+            None => {
+                balance.push(Money::zero(period.outstanding.currency));
+                balance.last_mut().unwrap()
+            }
+        };
+        *total = total
+            .checked_add(period.outstanding)
+            .ok_or_else(|| anyhow::anyhow!("Balance overflow."))?;
+    }
+    balance.sort_by_key(|m| m.currency);
+
+    Ok(balance)
+}
+
+/// Statement of every fee period for an account since its membership started, covering also the
+/// periods that have not started yet. Only the started periods count towards the balance.
+pub async fn fetch_account_statement(
+    db: DatabaseConnection,
+    account_id: ID,
+) -> AppResult<Statement> {
+    let periods = db.fetch_period_statements(account_id).await?;
+    let balance = overdue_balance(&periods)?;
+
+    Ok(Statement { periods, balance })
+}
+
+/// Accounts with overdue periods, one entry per account and currency.
+pub async fn fetch_arrears(
+    db: DatabaseConnection,
+    order: ArrearsOrder,
+    ascending: bool,
+    offset: i64,
+    count: i64,
+) -> AppResult<Vec<Arrears>> {
+    db.fetch_arrears(order, ascending, offset, count).await
+}
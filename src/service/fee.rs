@@ -0,0 +1,84 @@
+use crate::{
+    db::{
+        DatabaseConnection,
+        types::{DB_MonthlyFee, ID},
+    },
+    error::{AppError, AppResult},
+    money::Money,
+};
+
+use super::{MonthlyFee, PrePaymentReport};
+
+pub(crate) fn into_monthly_fee(fee: DB_MonthlyFee) -> AppResult<MonthlyFee> {
+    Ok(MonthlyFee {
+        id: fee.id,
+        year: fee.year as u32,
+        month: fee.month as u32,
+        amount: Money::from_db(fee.amount, &fee.currency)?,
+    })
+}
+
+/// Creates the fee and applies the pre-payments of its period.
+/// Fails with a conflict if a fee for the same period already exists.
+pub async fn create_monthly_fee(
+    db: DatabaseConnection,
+    year: i32,
+    month: i32,
+    amount: Money,
+) -> AppResult<(MonthlyFee, PrePaymentReport)> {
+    db.create_monthly_fee(year, month, amount).await
+}
+
+/// Creates the fees of every month of the year, skipping the months that already exist,
+/// and applies the pre-payments of the new periods.
+/// Returns only the newly created fees.
+pub async fn create_monthly_fees_for_year(
+    db: DatabaseConnection,
+    year: i32,
+    amount: Money,
+) -> AppResult<(Vec<MonthlyFee>, PrePaymentReport)> {
+    db.create_monthly_fees_for_year(year, amount).await
+}
+
+pub async fn fetch_monthly_fees(
+    db: DatabaseConnection,
+    ascending: bool,
+    offset: i64,
+    count: i64,
+) -> AppResult<Vec<MonthlyFee>> {
+    db.fetch_monthly_fees(ascending, offset, count).await
+}
+
+pub async fn fetch_monthly_fee(db: DatabaseConnection, fee_id: ID) -> AppResult<MonthlyFee> {
+    db.fetch_monthly_fee(fee_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Fee not found."))
+}
+
+/// Changes the period and price of a fee. The price can not go below what an account already
+/// paid towards the fee.
+pub async fn update_monthly_fee(
+    db: DatabaseConnection,
+    fee_id: ID,
+    year: i32,
+    month: i32,
+    amount: Money,
+) -> AppResult<MonthlyFee> {
+    fetch_monthly_fee(db.clone(), fee_id).await?;
+
+    db.update_monthly_fee(fee_id, year, month, amount).await?;
+
+    Ok(MonthlyFee {
+        id: fee_id,
+        year: year as u32,
+        month: month as u32,
+        amount,
+    })
+}
+
+/// Fails with a conflict if payments were already recorded against the fee.
+pub async fn delete_monthly_fee(db: DatabaseConnection, fee_id: ID) -> AppResult<()> {
+    fetch_monthly_fee(db.clone(), fee_id).await?;
+
+    db.delete_monthly_fee(fee_id).await
+}
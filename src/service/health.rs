@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use crate::db::DatabaseConnection;
+
+pub enum Readiness {
+    Ready,
+    /// The database does not answer.
+    DatabaseUnavailable,
+    /// Versions of the migrations embedded in the server that the database has not applied.
+    PendingMigrations(Vec<i64>),
+}
+
+/// State of the database connection pool.
+pub struct PoolStats {
+    /// Open connections, idle or in use.
+    pub size: u32,
+    pub idle: usize,
+    pub max_size: u32,
+    /// Longest time a query waited for a connection since the stats were last taken.
+    pub acquire_wait: Duration,
+}
+
+pub async fn check_readiness(db: DatabaseConnection) -> Readiness {
+    db.check_readiness().await
+}
+
+/// `None` if the backend has no connection pool.
+pub async fn fetch_pool_stats(db: DatabaseConnection) -> Option<PoolStats> {
+    db.fetch_pool_stats().await
+}
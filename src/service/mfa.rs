@@ -0,0 +1,74 @@
+use crate::{
+    db::{DatabaseConnection, types::ID},
+    error::{AppError, AppResult},
+};
+
+use super::AccountTotp;
+
+/// Stores a new, unconfirmed secret, replacing any previous unconfirmed one.
+/// Fails with a conflict if the account already has a confirmed secret.
+pub async fn store_totp_secret(
+    db: DatabaseConnection,
+    account_id: ID,
+    secret: String,
+    now: i64,
+) -> AppResult<()> {
+    db.store_totp_secret(account_id, secret, now).await
+}
+
+/// `None` if the account never enrolled.
+pub async fn fetch_totp(db: DatabaseConnection, account_id: ID) -> AppResult<Option<AccountTotp>> {
+    db.fetch_totp(account_id).await
+}
+
+/// Confirmed secret of the account, fails with a validation error if there is none.
+pub async fn fetch_confirmed_totp(
+    db: DatabaseConnection,
+    account_id: ID,
+) -> AppResult<AccountTotp> {
+    match fetch_totp(db, account_id).await? {
+        Some(totp) if totp.confirmed => Ok(totp),
+        _ => Err(AppError::validation(
+            "Two-factor authentication is not enabled.",
+        )),
+    }
+}
+
+/// Records the time step of an accepted code. Returns `false` if the same or a later step
+/// was already used, i.e. the code is being replayed.
+pub async fn use_totp_step(db: DatabaseConnection, account_id: ID, step: i64) -> AppResult<bool> {
+    db.use_totp_step(account_id, step).await
+}
+
+/// Confirms the enrolled secret and replaces the recovery codes of the account.
+pub async fn confirm_totp(
+    db: DatabaseConnection,
+    account_id: ID,
+    step: i64,
+    recovery_code_hashes: Vec<String>,
+) -> AppResult<()> {
+    db.confirm_totp(account_id, step, recovery_code_hashes)
+        .await
+}
+
+pub async fn delete_totp(db: DatabaseConnection, account_id: ID) -> AppResult<()> {
+    db.delete_totp(account_id).await
+}
+
+pub async fn replace_recovery_codes(
+    db: DatabaseConnection,
+    account_id: ID,
+    code_hashes: Vec<String>,
+) -> AppResult<()> {
+    db.replace_recovery_codes(account_id, code_hashes).await
+}
+
+/// Marks an unused recovery code as used. Returns `false` if there is no such code.
+pub async fn use_recovery_code(
+    db: DatabaseConnection,
+    account_id: ID,
+    code_hash: String,
+    now: i64,
+) -> AppResult<bool> {
+    db.use_recovery_code(account_id, code_hash, now).await
+}
@@ -1,6 +1,15 @@
-use crate::db::types::ID;
+use uuid::Uuid;
+
+use crate::{db::types::ID, money::Money};
 
 pub mod account;
+pub mod balance;
+pub mod fee;
+pub mod health;
+pub mod mfa;
+pub mod payment;
+pub mod role;
+pub mod token;
 
 pub struct Account {
     pub id: ID,
@@ -10,24 +19,151 @@ pub struct Account {
     pub email: String,
     pub hashed_password: String,
     pub password_set_ts: u64,
+    /// Fees are charged from the period this falls into on.
+    pub member_since: u64,
     // pub roles: Vec<String>,
     // pub payments: Vec<MonthlyFee>
 }
 
+pub struct AccountSummary {
+    pub id: ID,
+    pub name: String,
+    pub lastname: String,
+    pub email: String,
+    pub phone_number: String,
+    pub roles: Vec<String>,
+    pub in_arrears: bool,
+}
+
+/// Position after the last account of a page, for keyset pagination.
+pub struct AccountCursor {
+    pub sort_key: String,
+    pub id: ID,
+}
+
+pub struct AccountPage {
+    /// Number of accounts matching the filter, over all pages.
+    pub total: u64,
+    pub accounts: Vec<AccountSummary>,
+    /// `None` on the last page.
+    pub next: Option<AccountCursor>,
+}
+
+#[derive(Clone)]
 pub struct Role {
-    // pub id: ID,
+    pub id: ID,
     pub role: String,
 }
 
 pub struct MonthlyFee {
-    // pub id: ID,
+    pub id: ID,
+    pub year: u32,
+    pub month: u32,
+    pub amount: Money,
+}
+
+/// A single, possibly partial, payment towards the fee of a period.
+pub struct Payment {
+    pub id: ID,
     pub year: u32,
     pub month: u32,
+    /// What was received, the reversals are not taken off.
+    pub amount: Money,
+    pub paid_at: u64,
+    pub reversals: Vec<Reversal>,
+}
+
+/// Part of a payment given back, in the currency of the payment.
+pub struct Reversal {
+    pub id: ID,
+    pub amount: Money,
+    /// Account that reversed the payment.
+    pub reversed_by: ID,
+    pub reversed_at: u64,
+}
+
+/// Payment for a period that had no fee yet, kept once it is applied to the fee.
+pub struct PrePayment {
+    pub payment: Payment,
+    /// Payment the pre-payment became once the fee of its period was created.
+    pub applied_payment_id: Option<ID>,
+    pub applied_at: Option<u64>,
 }
 
 pub struct Payments {
-    pub made: Vec<MonthlyFee>,
-    pub precovered: Vec<MonthlyFee>,
+    pub made: Vec<Payment>,
+    pub precovered: Vec<PrePayment>,
+}
+
+pub struct AccountPayment {
+    pub account_id: ID,
+    pub payment: Payment,
+}
+
+/// Result of turning pre-payments into payments once the fees of their periods exist.
+pub struct PrePaymentReport {
+    pub applied: Vec<AccountPayment>,
+    /// Pre-payments left for a period that has a fee, because the currency differs or
+    /// the amount is more than what is outstanding.
+    pub orphaned: Vec<AccountPayment>,
+}
+
+pub struct PeriodStatement {
+    pub year: u32,
+    pub month: u32,
+    pub due: Money,
+    pub paid: Money,
+    pub precovered: Money,
+    /// What is left of `due` after `paid` and `precovered`, never negative.
+    pub outstanding: Money,
+    /// The period has started and is not fully paid.
+    pub overdue: bool,
+}
+
+pub struct Statement {
+    pub periods: Vec<PeriodStatement>,
+    /// Sum of the outstanding amounts of the overdue periods, one entry per currency.
+    pub balance: Vec<Money>,
+}
+
+pub struct Arrears {
+    pub account_id: ID,
+    pub name: String,
+    pub lastname: String,
+    pub email: String,
+    pub outstanding: Money,
+    pub months_overdue: u32,
+    /// The oldest overdue period.
+    pub since_year: u32,
+    pub since_month: u32,
+}
+
+pub struct RefreshToken {
+    pub jti: Uuid,
+    pub family_id: Uuid,
+    pub account_id: ID,
+    pub token_hash: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+    pub rotated_at: Option<u64>,
+    pub revoked_at: Option<u64>,
+}
+
+pub struct AccountTotp {
+    pub account_id: ID,
+    pub secret: String,
+    pub confirmed: bool,
+    pub last_used_step: Option<u64>,
+}
+
+impl RefreshToken {
+    pub fn is_rotated(&self) -> bool {
+        self.rotated_at.is_some()
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
 }
 
 // pub struct UserRole {
@@ -38,4 +174,4 @@ pub struct Payments {
 // pub struct Payment {
 //     pub user_id: u32,
 //     pub fee_id: u32,
-// }
\ No newline at end of file
+// }
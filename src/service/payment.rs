@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use crate::{
+    db::{
+        DatabaseConnection,
+        types::{
+            DB_AccountPeriodPayment, DB_MonthlyFee, DB_PaymentReversal, DB_PeriodPayment,
+            DB_PrePayment, ID,
+        },
+    },
+    error::{AppError, AppResult},
+    money::{Currency, Money},
+};
+
+use super::{AccountPayment, Payment, Payments, PrePayment, PrePaymentReport, Reversal};
+
+pub(crate) const PRE_PAYMENT_APPLIED: &str =
+    "Pre-payment is applied, reverse the payment it became instead.";
+
+/// Payment of a `(year, month)` period. Without an amount the whole outstanding fee is paid.
+pub struct PeriodPayment {
+    pub year: i32,
+    pub month: i32,
+    pub amount: Option<Money>,
+}
+
+/// Table of a reversed payment, the ids of payments and pre-payments overlap.
+#[derive(Debug, Clone, Copy)]
+pub enum PaymentKind {
+    Payment,
+    PrePayment,
+}
+
+/// What to record for the paid periods, worked out by [`plan_payments`].
+pub(crate) struct PaymentPlan {
+    /// Amounts paid towards existing fees.
+    pub fee_payments: Vec<(ID, Money)>,
+    /// Amounts paid for `(year, month)` periods that have no fee yet.
+    pub pre_payments: Vec<(i32, i32, Money)>,
+}
+
+pub(crate) fn into_payment(payment: DB_PeriodPayment) -> AppResult<Payment> {
+    Ok(Payment {
+        id: payment.id,
+        year: payment.year as u32,
+        month: payment.month as u32,
+        amount: Money::from_db(payment.amount, &payment.currency)?,
+        paid_at: payment.paid_at as u64,
+        reversals: Vec::new(),
+    })
+}
+
+fn with_reversals<'a>(
+    payment: DB_PeriodPayment,
+    reversals: impl Iterator<Item = &'a DB_PaymentReversal>,
+) -> AppResult<Payment> {
+    let currency = Currency::parse(&payment.currency)?;
+    let reversals = reversals
+        .map(|r| Reversal {
+            id: r.id,
+            amount: Money::new(r.amount, currency),
+            reversed_by: r.reversed_by,
+            reversed_at: r.reversed_at as u64,
+        })
+        .collect();
+
+    Ok(Payment {
+        reversals,
+        ..into_payment(payment)?
+    })
+}
+
+/// Payments along with those of the reversals that belong to them.
+pub(crate) fn into_payments_with_reversals(
+    payments: Vec<DB_PeriodPayment>,
+    reversals: &[DB_PaymentReversal],
+) -> AppResult<Vec<Payment>> {
+    payments
+        .into_iter()
+        .map(|payment| {
+            let id = payment.id;
+            with_reversals(
+                payment,
+                reversals.iter().filter(|r| r.payment_id == Some(id)),
+            )
+        })
+        .collect()
+}
+
+/// Pre-payments along with those of the reversals that belong to them.
+pub(crate) fn into_pre_payments_with_reversals(
+    pre_payments: Vec<DB_PrePayment>,
+    reversals: &[DB_PaymentReversal],
+) -> AppResult<Vec<PrePayment>> {
+    pre_payments
+        .into_iter()
+        .map(|pre_payment| {
+            let id = pre_payment.payment.id;
+            Ok(PrePayment {
+                payment: with_reversals(
+                    pre_payment.payment,
+                    reversals.iter().filter(|r| r.pre_payment_id == Some(id)),
+                )?,
+                applied_payment_id: pre_payment.applied_payment_id,
+                applied_at: pre_payment.applied_at.map(|t| t as u64),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn into_account_payment(payment: DB_AccountPeriodPayment) -> AppResult<AccountPayment> {
+    Ok(AccountPayment {
+        account_id: payment.account_id,
+        payment: into_payment(payment.payment)?,
+    })
+}
+
+/// Decides how the payments of the periods are recorded, given the fees of the periods and what
+/// the account already paid towards each of them.
+///
+/// Periods with a fee are paid towards it, the amount defaulting to and being limited by what is
+/// outstanding. Periods without a fee are pre-paid, so their amount is required.
+/// Fails for the first period that can not be paid, so that nothing is recorded.
+pub(crate) fn plan_payments(
+    periods: Vec<PeriodPayment>,
+    fees: &[DB_MonthlyFee],
+    paid: &HashMap<ID, i64>,
+) -> AppResult<PaymentPlan> {
+    let mut fee_payments: Vec<(ID, Money)> = Vec::new();
+    let mut pre_payments: Vec<(i32, i32, Money)> = Vec::new();
+    for PeriodPayment {
+        year,
+        month,
+        amount,
+    } in periods
+    {
+        let Some(fee) = fees.iter().find(|f| f.year == year && f.month == month) else {
+            match amount {
+                Some(amount) => pre_payments.push((year, month, amount)),
+                None => {
+                    return Err(AppError::validation(format!(
+                        "Period {year}-{month:02} has no fee yet, the amount of the pre-payment is required."
+                    )));
+                }
+            }
+            continue;
+        };
+
+        let due = Money::from_db(fee.amount, &fee.currency)?;
+        let outstanding = Money::new(
+            due.minor_units - paid.get(&fee.id).copied().unwrap_or(0),
+            due.currency,
+        );
+        if !outstanding.is_positive() {
+            return Err(AppError::conflict(format!(
+                "Period {year}-{month:02} is already paid."
+            )));
+        }
+
+        let amount = amount.unwrap_or(outstanding);
+        if amount.currency != due.currency {
+            return Err(AppError::validation(format!(
+                "Fee of period {year}-{month:02} is in {}.",
+                due.currency
+            )));
+        }
+        if amount.minor_units > outstanding.minor_units {
+            return Err(AppError::conflict(format!(
+                "Only {outstanding} is outstanding for period {year}-{month:02}."
+            )));
+        }
+
+        fee_payments.push((fee.id, amount));
+    }
+
+    Ok(PaymentPlan {
+        fee_payments,
+        pre_payments,
+    })
+}
+
+/// Amount to reverse of a payment of which `left` is not reversed yet, by default all of it.
+pub(crate) fn reversal_amount(left: Money, amount: Option<Money>) -> AppResult<Money> {
+    if !left.is_positive() {
+        return Err(AppError::conflict("Payment is already reversed."));
+    }
+
+    let amount = amount.unwrap_or(left);
+    if amount.currency != left.currency {
+        return Err(AppError::validation(format!(
+            "Payment is in {}.",
+            left.currency
+        )));
+    }
+    if amount.minor_units > left.minor_units {
+        return Err(AppError::conflict(format!(
+            "Only {left} is left of the payment."
+        )));
+    }
+
+    Ok(amount)
+}
+
+/// Records the, possibly partial, payments of the given periods by an account.
+///
+/// Periods with an existing monthly fee become `payments` and can not exceed the outstanding
+/// amount of the fee, periods whose fee is not created yet become `pre_payments`.
+/// Nothing is recorded if any of the periods is refused.
+pub async fn record_payments(
+    db: DatabaseConnection,
+    account_id: ID,
+    periods: Vec<PeriodPayment>,
+) -> AppResult<Payments> {
+    db.record_payments(account_id, periods).await
+}
+
+/// Reverses `amount` of a payment or pre-payment of the account, all that is left of it without
+/// an amount. The reversal is recorded along with the account reversing it, the payment keeps the
+/// amount received. Applied pre-payments are reversed through the payment they became.
+pub async fn reverse_payment(
+    db: DatabaseConnection,
+    account_id: ID,
+    kind: PaymentKind,
+    payment_id: ID,
+    amount: Option<Money>,
+    reversed_by: ID,
+) -> AppResult<()> {
+    db.reverse_payment(account_id, kind, payment_id, amount, reversed_by)
+        .await
+}
+
+/// Applies the pre-payments of every period that has a fee. Safe to run repeatedly.
+pub async fn apply_pre_payments(db: DatabaseConnection) -> AppResult<PrePaymentReport> {
+    db.apply_pre_payments().await
+}
@@ -0,0 +1,158 @@
+use crate::{
+    auth::{ROLE_ADMIN, ROLE_TREASURER},
+    db::{
+        DatabaseConnection,
+        types::{DB_Role, ID},
+    },
+    error::{AppError, AppResult},
+};
+
+use super::Role;
+
+/// Roles the server itself checks for, they can not be renamed or deleted.
+pub const BUILT_IN_ROLES: [&str; 2] = [ROLE_ADMIN, ROLE_TREASURER];
+
+pub(crate) const ROLE_NOT_FOUND: &str = "Role not found.";
+
+pub(crate) const BUILT_IN_ROLE: &str = "Built-in roles can not be changed.";
+
+pub(crate) const LAST_ADMIN: &str = "The last admin can not lose the Admin role.";
+
+pub(crate) const ROLE_BEYOND_GRANTER: &str =
+    "Roles with permissions the granting account lacks can not be granted.";
+
+pub(crate) const PERMISSION_BEYOND_GRANTER: &str =
+    "Permissions the granting account lacks can not be granted.";
+
+pub(crate) fn into_role(role: DB_Role) -> Role {
+    Role {
+        id: role.id,
+        role: role.role,
+    }
+}
+
+pub async fn fetch_roles(db: DatabaseConnection) -> AppResult<Vec<Role>> {
+    db.fetch_roles().await
+}
+
+pub async fn fetch_role(db: DatabaseConnection, role_id: ID) -> AppResult<Role> {
+    db.fetch_role(role_id)
+        .await?
+        .ok_or_else(|| AppError::not_found(ROLE_NOT_FOUND))
+}
+
+pub async fn rename_role(db: DatabaseConnection, role_id: ID, role: String) -> AppResult<()> {
+    db.rename_role(role_id, role).await
+}
+
+/// Deletes the role and takes it away from every account holding it.
+pub async fn delete_role(db: DatabaseConnection, role_id: ID) -> AppResult<()> {
+    db.delete_role(role_id).await
+}
+
+/// Fails with not found if the account does not hold the role.
+pub async fn revoke_role_from_account(
+    db: DatabaseConnection,
+    account_id: ID,
+    role_id: ID,
+) -> AppResult<()> {
+    db.revoke_role_from_account(account_id, role_id).await
+}
+
+/// Fails with forbidden unless `granted_by`, the roles of the granting account, have every
+/// permission given by those of the roles the account does not hold yet. `Admin` is only
+/// granted by admins.
+pub(crate) async fn check_grantable_roles(
+    db: DatabaseConnection,
+    granted_by: &[Role],
+    account_id: ID,
+    role_ids: &[ID],
+) -> AppResult<()> {
+    if granted_by.iter().any(|r| r.role == ROLE_ADMIN) {
+        return Ok(());
+    }
+
+    let held = db.fetch_account_roles(account_id).await?;
+    let granter_permissions = fetch_permissions_of_roles(db.clone(), granted_by).await?;
+
+    for role_id in role_ids
+        .iter()
+        .filter(|id| !held.iter().any(|r| r.id == **id))
+    {
+        let role = fetch_role(db.clone(), *role_id).await?;
+        if role.role == ROLE_ADMIN {
+            return Err(AppError::forbidden(ROLE_BEYOND_GRANTER));
+        }
+
+        let permissions = fetch_permissions_of_roles(db.clone(), &[role]).await?;
+        if !permissions.iter().all(|p| granter_permissions.contains(p)) {
+            return Err(AppError::forbidden(ROLE_BEYOND_GRANTER));
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets exactly the given roles on the account in one transaction.
+/// See [`check_grantable_roles`] for the roles `granted_by` can grant.
+pub async fn replace_account_roles(
+    db: DatabaseConnection,
+    granted_by: &[Role],
+    account_id: ID,
+    role_ids: Vec<ID>,
+) -> AppResult<()> {
+    check_grantable_roles(db.clone(), granted_by, account_id, &role_ids).await?;
+
+    db.replace_account_roles(account_id, role_ids).await
+}
+
+pub async fn fetch_permissions(db: DatabaseConnection) -> AppResult<Vec<String>> {
+    db.fetch_permissions().await
+}
+
+pub async fn fetch_role_permissions(db: DatabaseConnection, role_id: ID) -> AppResult<Vec<String>> {
+    db.fetch_role_permissions(role_id).await
+}
+
+/// Permissions granted by the roles, every permission for `Admin`.
+pub async fn fetch_permissions_of_roles(
+    db: DatabaseConnection,
+    roles: &[Role],
+) -> AppResult<Vec<String>> {
+    if roles.iter().any(|r| r.role == ROLE_ADMIN) {
+        return db.fetch_permissions().await;
+    }
+
+    db.fetch_permissions_of_role_ids(roles.iter().map(|r| r.id).collect())
+        .await
+}
+
+/// Grants exactly the given permissions to the role. Those of `Admin` can not be changed.
+///
+/// Permissions the role does not have yet are only granted if `granted_by`, the roles of the
+/// granting account, have them too.
+pub async fn replace_role_permissions(
+    db: DatabaseConnection,
+    granted_by: &[Role],
+    role_id: ID,
+    permissions: Vec<String>,
+) -> AppResult<()> {
+    let role = fetch_role(db.clone(), role_id).await?;
+    if role.role == ROLE_ADMIN {
+        return Err(AppError::conflict(BUILT_IN_ROLE));
+    }
+
+    if !granted_by.iter().any(|r| r.role == ROLE_ADMIN) {
+        let held = fetch_role_permissions(db.clone(), role_id).await?;
+        let granter_permissions = fetch_permissions_of_roles(db.clone(), granted_by).await?;
+
+        if permissions
+            .iter()
+            .any(|p| !held.contains(p) && !granter_permissions.contains(p))
+        {
+            return Err(AppError::forbidden(PERMISSION_BEYOND_GRANTER));
+        }
+    }
+
+    db.replace_role_permissions(role_id, permissions).await
+}
@@ -0,0 +1,74 @@
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        DatabaseConnection,
+        types::{DB_RefreshToken, ID},
+    },
+    error::{AppError, AppResult},
+};
+
+use super::RefreshToken;
+
+pub async fn store_refresh_token(db: DatabaseConnection, token: DB_RefreshToken) -> AppResult<()> {
+    db.store_refresh_token(token).await
+}
+
+/// Fails as unauthorized if the token was never issued or is already deleted.
+pub async fn fetch_refresh_token(db: DatabaseConnection, jti: Uuid) -> AppResult<RefreshToken> {
+    db.fetch_refresh_token(jti)
+        .await?
+        .ok_or_else(|| AppError::unauthorized("Invalid token."))
+}
+
+/// Marks the token as used. Returns `false` if it was already rotated or revoked,
+/// which means the token is being reused and its family has to be revoked.
+pub async fn rotate_refresh_token(db: DatabaseConnection, jti: Uuid, now: i64) -> AppResult<bool> {
+    db.rotate_refresh_token(jti, now).await
+}
+
+pub async fn revoke_refresh_token_family(
+    db: DatabaseConnection,
+    family_id: Uuid,
+    now: i64,
+) -> AppResult<u64> {
+    db.revoke_refresh_token_family(family_id, now).await
+}
+
+pub async fn revoke_account_refresh_tokens(
+    db: DatabaseConnection,
+    account_id: ID,
+    now: i64,
+) -> AppResult<u64> {
+    db.revoke_account_refresh_tokens(account_id, now).await
+}
+
+pub async fn delete_expired_refresh_tokens(
+    db: DatabaseConnection,
+    account_id: ID,
+    now: i64,
+) -> AppResult<u64> {
+    db.delete_expired_refresh_tokens(account_id, now).await
+}
+
+pub async fn store_password_reset_token(
+    db: DatabaseConnection,
+    token_hash: String,
+    account_id: ID,
+    expires_at: i64,
+) -> AppResult<()> {
+    db.store_password_reset_token(token_hash, account_id, expires_at)
+        .await
+}
+
+/// Marks an unused, unexpired reset token as used and returns the account it was issued for.
+/// Fails as unauthorized if there is no such token.
+pub async fn consume_password_reset_token(
+    db: DatabaseConnection,
+    token_hash: String,
+    now: i64,
+) -> AppResult<ID> {
+    db.consume_password_reset_token(token_hash, now)
+        .await?
+        .ok_or_else(|| AppError::unauthorized("Invalid token."))
+}
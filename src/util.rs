@@ -34,3 +34,10 @@ impl Header for BearerToken {
         }
     }
 }
+
+pub fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
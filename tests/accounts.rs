@@ -0,0 +1,405 @@
+mod common;
+
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use agem_server::{
+    db::{DatabaseConnection, memory::MemoryRepository, types::DB_Account},
+    service,
+    util::unix_timestamp,
+};
+use common::{
+    access_token, admin_token, create_account, get, new_account, post, put, send, service,
+};
+use serde_json::json;
+
+const MEMBER_PASSWORD: &str = "Member-password-1";
+
+#[actix_web::test]
+async fn created_account_can_log_in() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+
+    let (status, body) = send(&service, post("/api/v1/account", &admin, new_account(1))).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    let token = access_token(&service, "member1@example.com", MEMBER_PASSWORD).await;
+    let (status, body) = send(&service, get("/api/v1/me", &token)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["email"], "member1@example.com");
+}
+
+#[actix_web::test]
+async fn email_is_stored_trimmed_in_lowercase() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+
+    let mut account = new_account(1);
+    account["email"] = json!(" Member1@Example.COM ");
+    let (status, body) = send(&service, post("/api/v1/account", &admin, account)).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    let token = access_token(&service, "MEMBER1@example.com", MEMBER_PASSWORD).await;
+    let (status, body) = send(&service, get("/api/v1/me", &token)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["email"], "member1@example.com");
+
+    // the same email in another case is taken
+    let mut account = new_account(2);
+    account["email"] = json!("member1@EXAMPLE.com");
+    let (status, _) = send(&service, post("/api/v1/account", &admin, account)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn duplicate_phone_number_is_a_conflict() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    create_account(&service, &admin, 1).await;
+
+    let mut account = new_account(2);
+    account["phone_number"] = new_account(1)["phone_number"].clone();
+    let (status, body) = send(&service, post("/api/v1/account", &admin, account)).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{body}");
+    assert_eq!(body["code"], "conflict");
+
+    let mut account = new_account(2);
+    account["email"] = new_account(1)["email"].clone();
+    let (status, _) = send(&service, post("/api/v1/account", &admin, account)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn invalid_account_lists_the_fields() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+
+    let mut account = new_account(1);
+    account["phone_number"] = json!("12");
+    account["email"] = json!("not an email");
+    let (status, body) = send(&service, post("/api/v1/account", &admin, account)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert_eq!(body["code"], "validation");
+
+    let mut fields: Vec<&str> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    fields.sort();
+    assert_eq!(fields, ["email", "phone_number"]);
+}
+
+#[actix_web::test]
+async fn members_can_not_manage_accounts() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    create_account(&service, &admin, 1).await;
+    let member = access_token(&service, "member1@example.com", MEMBER_PASSWORD).await;
+
+    let (status, body) = send(&service, post("/api/v1/account", &member, new_account(2))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "forbidden");
+
+    let (status, _) = send(&service, get("/api/v1/accounts?sort=1&count=10", &member)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn assigned_role_grants_its_permissions() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    let account_id = create_account(&service, &admin, 1).await;
+
+    let (status, roles) = send(&service, get("/api/v1/roles", &admin)).await;
+    assert_eq!(status, StatusCode::OK, "{roles}");
+    let treasurer = roles
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["role"] == "Treasurer")
+        .unwrap()["id"]
+        .clone();
+
+    let (status, body) = send(
+        &service,
+        put(
+            &format!("/api/v1/account/{account_id}/roles"),
+            &admin,
+            json!({ "role_ids": [treasurer] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let (status, body) = send(
+        &service,
+        get(&format!("/api/v1/account/{account_id}/roles"), &admin),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body[0]["role"], "Treasurer");
+
+    // permissions are resolved when the token is issued
+    let member = access_token(&service, "member1@example.com", MEMBER_PASSWORD).await;
+    let (_, claims) = send(&service, get("/auth/me", &member)).await;
+    assert_eq!(claims["role"], json!(["Treasurer"]));
+    assert!(
+        claims["permissions"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("payments.write"))
+    );
+
+    let (status, body) = send(&service, get("/api/v1/accounts?sort=1&count=10", &member)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["total"], 3);
+}
+
+#[actix_web::test]
+async fn unknown_role_is_not_assigned() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    let account_id = create_account(&service, &admin, 1).await;
+
+    let (status, _) = send(
+        &service,
+        put(
+            &format!("/api/v1/account/{account_id}/roles"),
+            &admin,
+            json!({ "role_ids": [9999] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(
+        &service,
+        put(
+            "/api/v1/account/9999/roles",
+            &admin,
+            json!({ "role_ids": [] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn roles_beyond_the_granting_account_are_not_granted() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    let clerk_id = create_account(&service, &admin, 1).await;
+    let account_id = create_account(&service, &admin, 2).await;
+
+    send(&service, post("/api/v1/roles", &admin, json!(["Clerk"]))).await;
+    let (_, roles) = send(&service, get("/api/v1/roles", &admin)).await;
+    let role_id = |role: &str| {
+        roles
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["role"] == role)
+            .unwrap()["id"]
+            .clone()
+    };
+    let clerk = role_id("Clerk");
+    let (status, body) = send(
+        &service,
+        put(
+            &format!("/api/v1/roles/{clerk}/permissions"),
+            &admin,
+            json!({ "permissions": ["roles.read", "roles.write"] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (status, body) = send(
+        &service,
+        put(
+            &format!("/api/v1/account/{clerk_id}/roles"),
+            &admin,
+            json!({ "role_ids": [clerk] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let token = access_token(&service, "member1@example.com", MEMBER_PASSWORD).await;
+
+    for role in [role_id("Admin"), role_id("Treasurer")] {
+        let (status, body) = send(
+            &service,
+            put(
+                &format!("/api/v1/account/{account_id}/roles"),
+                &token,
+                json!({ "role_ids": [role] }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{body}");
+        let (status, body) = send(
+            &service,
+            post(
+                "/api/v1/account/roles",
+                &token,
+                json!({ "account_id": account_id, "role_ids": [role] }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{body}");
+    }
+
+    // nor can the role be given more than its granter has
+    let (status, body) = send(
+        &service,
+        put(
+            &format!("/api/v1/roles/{clerk}/permissions"),
+            &token,
+            json!({ "permissions": ["roles.read", "roles.write", "payments.write"] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{body}");
+
+    let (status, body) = send(
+        &service,
+        put(
+            &format!("/api/v1/account/{account_id}/roles"),
+            &token,
+            json!({ "role_ids": [clerk] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+#[actix_web::test]
+async fn last_admin_keeps_the_admin_role() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+
+    let (_, me) = send(&service, get("/api/v1/me", &admin)).await;
+    let admin_id = me["id"].as_i64().unwrap();
+
+    let (status, body) = send(
+        &service,
+        put(
+            &format!("/api/v1/account/{admin_id}/roles"),
+            &admin,
+            json!({ "role_ids": [] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{body}");
+}
+
+#[actix_web::test]
+async fn blank_role_is_not_created() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+
+    let (status, body) = send(
+        &service,
+        post("/api/v1/roles", &admin, json!(["Auditor", " "])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert_eq!(body["code"], "validation");
+
+    let (_, roles) = send(&service, get("/api/v1/roles", &admin)).await;
+    assert!(
+        !roles
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|r| r["role"] == "Auditor")
+    );
+
+    let (status, body) = send(
+        &service,
+        post("/api/v1/roles", &admin, json!([" Auditor "])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let (_, roles) = send(&service, get("/api/v1/roles", &admin)).await;
+    assert!(
+        roles
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|r| r["role"] == "Auditor")
+    );
+}
+
+#[actix_web::test]
+async fn role_can_not_take_the_name_of_another() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+
+    send(&service, post("/api/v1/roles", &admin, json!(["Auditor"]))).await;
+    let (_, roles) = send(&service, get("/api/v1/roles", &admin)).await;
+    let auditor = roles
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["role"] == "Auditor")
+        .unwrap()["id"]
+        .clone();
+
+    let (status, body) = send(
+        &service,
+        put(
+            &format!("/api/v1/roles/{auditor}"),
+            &admin,
+            json!({ "role": "Treasurer" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{body}");
+    assert_eq!(body["detail"], "Duplicate role.");
+}
+
+fn first_admin(email: &str) -> DB_Account {
+    DB_Account {
+        id: 0,
+        phone_number: String::new(),
+        name: "Admin".to_string(),
+        lastname: String::new(),
+        email: email.to_string(),
+        hashed_password: String::new(),
+        password_set_ts: unix_timestamp() as i64,
+        member_since: unix_timestamp() as i64,
+    }
+}
+
+#[actix_web::test]
+async fn first_admin_is_only_created_while_there_is_none() {
+    let db: DatabaseConnection = Arc::new(MemoryRepository::new());
+
+    let created =
+        service::account::create_admin_account(db.clone(), first_admin("first@example.com"))
+            .await
+            .unwrap();
+    assert!(created);
+    let account =
+        service::account::fetch_account_by_email(db.clone(), "first@example.com".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+    let roles = service::account::fetch_account_roles(db.clone(), account.id)
+        .await
+        .unwrap();
+    assert_eq!(roles[0].role, "Admin");
+
+    // later starts leave the accounts as they are
+    let created =
+        service::account::create_admin_account(db.clone(), first_admin("second@example.com"))
+            .await
+            .unwrap();
+    assert!(!created);
+    let account = service::account::fetch_account_by_email(db, "second@example.com".to_string())
+        .await
+        .unwrap();
+    assert!(account.is_none());
+}
@@ -0,0 +1,267 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use agem_server::app::app;
+use agem_server::{auth::Claims, util::unix_timestamp};
+use common::{
+    ADMIN_EMAIL, ADMIN_PASSWORD, MEMBER_EMAIL, MEMBER_PASSWORD, admin_token, enroll_admin,
+    enroll_mfa, enrollment_token, expired_access_token, get, jwt_keys, login, refresh, send,
+    service, state_with_outbox, totp_code, verify_mfa,
+};
+use uuid::Uuid;
+
+#[actix_web::test]
+async fn login_issues_tokens() {
+    let service = service().await;
+
+    let (status, body) = login(&service, MEMBER_EMAIL, MEMBER_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body["access_token"].is_string());
+    assert!(body["refresh_token"].is_string());
+
+    let token = body["access_token"].as_str().unwrap();
+    let (status, body) = send(&service, get("/auth/me", token)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["sub"], MEMBER_EMAIL);
+    assert_eq!(body["role"], serde_json::json!([]));
+}
+
+#[actix_web::test]
+async fn login_with_wrong_password_is_rejected() {
+    let service = service().await;
+
+    let (status, body) = login(&service, ADMIN_EMAIL, "Wrong-password-1").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+
+    let (status, _) = login(&service, "nobody@example.com", ADMIN_PASSWORD).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn login_without_password_lists_the_field() {
+    let service = service().await;
+
+    let (status, body) = login(&service, ADMIN_EMAIL, "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "validation");
+    assert_eq!(body["errors"][0]["field"], "password");
+}
+
+#[actix_web::test]
+async fn refresh_rotates_the_refresh_token() {
+    let service = service().await;
+    let (_, tokens) = login(&service, MEMBER_EMAIL, MEMBER_PASSWORD).await;
+    let first = tokens["refresh_token"].as_str().unwrap();
+
+    let (status, body) = refresh(&service, first).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let second = body["refresh_token"].as_str().unwrap().to_string();
+    assert_ne!(first, second);
+
+    let (status, body) = send(
+        &service,
+        get("/auth/me", body["access_token"].as_str().unwrap()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+#[actix_web::test]
+async fn refresh_works_right_after_a_password_change() {
+    let service = service().await;
+    let (_, tokens) = login(&service, MEMBER_EMAIL, MEMBER_PASSWORD).await;
+    let (status, body) = send(
+        &service,
+        test::TestRequest::post()
+            .uri("/auth/password")
+            .insert_header((
+                "Authorization",
+                format!("Bearer {}", tokens["access_token"].as_str().unwrap()),
+            ))
+            .set_form([
+                ("old_password", MEMBER_PASSWORD),
+                ("new_password", "Changed-password-2"),
+            ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    // logging in within the second the password was set
+    let (status, tokens) = login(&service, MEMBER_EMAIL, "Changed-password-2").await;
+    assert_eq!(status, StatusCode::OK, "{tokens}");
+    let (status, body) = refresh(&service, tokens["refresh_token"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+#[actix_web::test]
+async fn reused_refresh_token_revokes_the_family() {
+    let service = service().await;
+    let (_, tokens) = login(&service, MEMBER_EMAIL, MEMBER_PASSWORD).await;
+    let first = tokens["refresh_token"].as_str().unwrap();
+
+    let (_, body) = refresh(&service, first).await;
+    let second = body["refresh_token"].as_str().unwrap().to_string();
+
+    let (status, body) = refresh(&service, first).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+    assert_eq!(body["detail"], "Refresh token reuse detected.");
+
+    // the legitimate holder is logged out as well
+    let (status, _) = refresh(&service, &second).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn expired_access_token_is_rejected() {
+    let service = service().await;
+
+    let token = expired_access_token(ADMIN_EMAIL);
+    let (status, body) = send(&service, get("/api/v1/roles", &token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+
+    let (status, _) = send(&service, get("/auth/me", &token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn expired_refresh_token_is_rejected() {
+    let service = service().await;
+
+    let now = unix_timestamp() as usize;
+    let claims = Claims::for_refresh_token(
+        now - 2 * 60 * 60,
+        60 * 60,
+        ADMIN_EMAIL.to_string(),
+        Uuid::new_v4().to_string(),
+    );
+    let token = jwt_keys().encode(&claims).unwrap();
+
+    let (status, body) = refresh(&service, &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["detail"], "ExpiredSignature");
+}
+
+#[actix_web::test]
+async fn access_token_is_not_a_refresh_token() {
+    let service = service().await;
+    let token = admin_token(&service).await;
+
+    let (status, _) = refresh(&service, &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn api_requires_a_bearer_token() {
+    let service = service().await;
+
+    let (status, body) = send(&service, test::TestRequest::get().uri("/api/v1/roles")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["detail"], "Missing bearer token.");
+}
+
+#[actix_web::test]
+async fn admin_enrolls_in_two_factor_authentication_at_login() {
+    let (state, outbox) = state_with_outbox();
+    let service = test::init_service(app(state)).await;
+
+    let (status, body) = login(&service, ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["mfa_required"], true);
+    assert_eq!(body["enrollment_required"], true);
+    assert!(body["access_token"].is_null());
+    assert!(body["secret"].is_null());
+    let mfa_token = body["mfa_token"].as_str().unwrap();
+
+    // the password alone does not yield a secret, the token mailed at login is needed too
+    let (status, _) = enroll_mfa(&service, mfa_token, mfa_token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = enroll_mfa(&service, mfa_token, &enrollment_token(MEMBER_EMAIL)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let enrollment_token = outbox.last_token(ADMIN_EMAIL).unwrap();
+    let (status, body) = enroll_mfa(&service, mfa_token, &enrollment_token).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let code = totp_code(body["secret"].as_str().unwrap());
+
+    let (status, body) = verify_mfa(&service, mfa_token, &code).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["recovery_codes"].as_array().unwrap().len(), 10);
+
+    // the enrollment is confirmed, later logins ask for a code without a new secret
+    let (status, body) = login(&service, ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["mfa_required"], true);
+    assert_eq!(body["enrollment_required"], false);
+    let mfa_token = body["mfa_token"].as_str().unwrap();
+    let (status, _) = enroll_mfa(&service, mfa_token, &enrollment_token).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn failed_codes_lock_the_account_despite_the_password() {
+    let service = service().await;
+
+    let (mfa_token, secret) = enroll_admin(&service).await;
+    let code: u32 = totp_code(&secret).parse().unwrap();
+    let wrong_code = format!("{:06}", (code + 500_000) % 1_000_000);
+
+    for _ in 0..6 {
+        // a correct password does not clear the failures of the second factor
+        let (status, body) = login(&service, ADMIN_EMAIL, ADMIN_PASSWORD).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, _) = verify_mfa(&service, &mfa_token, &wrong_code).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    let response = test::call_service(
+        &service,
+        test::TestRequest::post()
+            .uri("/auth/mfa/verify")
+            .set_form([("mfa_token", mfa_token.as_str()), ("code", &wrong_code)])
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response
+        .headers()
+        .get("Retry-After")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 30);
+}
+
+#[actix_web::test]
+async fn password_reset_does_not_tell_whether_the_account_exists() {
+    let service = service().await;
+
+    let forgot = |email: &str| {
+        test::TestRequest::post()
+            .uri("/auth/password/forgot")
+            .set_form([("email", email)])
+    };
+    let (status, known) = send(&service, forgot(MEMBER_EMAIL)).await;
+    assert_eq!(status, StatusCode::OK, "{known}");
+    let (status, unknown) = send(&service, forgot("nobody@example.com")).await;
+    assert_eq!(status, StatusCode::OK, "{unknown}");
+    assert_eq!(known, unknown);
+}
+
+#[actix_web::test]
+async fn unknown_password_reset_token_is_unauthorized() {
+    let service = service().await;
+
+    let (status, body) = send(
+        &service,
+        test::TestRequest::post()
+            .uri("/auth/password/reset")
+            .set_form([("token", "unknown"), ("new_password", "Changed-password-2")]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+}
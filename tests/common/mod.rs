@@ -0,0 +1,376 @@
+//! Boots the whole `App` on an in-memory repository, one fresh store per test.
+
+#![allow(dead_code)]
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use actix_http::Request;
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test, web,
+};
+use agem_server::{
+    app::{AppState, app},
+    auth::{
+        ACCESS_TOKEN_EXPIRES_IN, Claims, MFA_PENDING_TOKEN_EXPIRES_IN,
+        PASSWORD_RESET_TOKEN_EXPIRES_IN, REFRESH_TOKEN_EXPIRES_IN, ROLE_ADMIN, TokenLifetimes,
+        keys::{JwtKeyConfig, JwtKeys, JwtKeysConfig},
+        throttle::LoginThrottle,
+    },
+    config::CorsConfig,
+    db::{memory::MemoryRepository, types::DB_Account},
+    mail::{Mail, MailSender},
+    metrics::Metrics,
+    password::PasswordHasher,
+    util::unix_timestamp,
+};
+use async_trait::async_trait;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use jsonwebtoken::Algorithm;
+use serde_json::{Value, json};
+use sha1::Sha1;
+
+pub const ADMIN_EMAIL: &str = "admin@example.com";
+pub const ADMIN_PASSWORD: &str = "Admin-password-1";
+/// Account without roles, which logs in with its password alone.
+pub const MEMBER_EMAIL: &str = "member@example.com";
+pub const MEMBER_PASSWORD: &str = "Member-password-1";
+const JWT_SECRET: &str = "integration-test-secret-integration-test-secret";
+/// Lowest cost bcrypt accepts, hashing at the default cost would make every login slow.
+const BCRYPT_COST: u32 = 4;
+
+/// Keeps the mails sent, for the tests that read the tokens in them.
+#[derive(Default)]
+pub struct Outbox(Mutex<Vec<Mail>>);
+
+impl Outbox {
+    /// Last line of the last mail sent to `to`, where the tokens are.
+    pub fn last_token(&self, to: &str) -> Option<String> {
+        let mails = self.0.lock().unwrap();
+        let mail = mails.iter().rev().find(|m| m.to == to)?;
+
+        mail.body.lines().last().map(str::to_string)
+    }
+}
+
+#[async_trait]
+impl MailSender for Outbox {
+    async fn send(&self, mail: Mail) -> anyhow::Result<()> {
+        self.0.lock().unwrap().push(mail);
+        Ok(())
+    }
+}
+
+pub fn jwt_keys() -> JwtKeys {
+    JwtKeys::from_config(
+        JwtKeysConfig {
+            signing_key_id: "test".to_string(),
+            keys: vec![JwtKeyConfig {
+                kid: "test".to_string(),
+                algorithm: Algorithm::HS256,
+                secret: Some(JWT_SECRET.to_string()),
+                private_key_file: None,
+                public_key_file: None,
+            }],
+        },
+        Path::new("."),
+    )
+    .unwrap()
+}
+
+/// State of a server whose only accounts are the admin and a member.
+pub fn state() -> AppState {
+    state_with_outbox().0
+}
+
+/// [`state`] along with the outbox its mails go to.
+pub fn state_with_outbox() -> (AppState, Arc<Outbox>) {
+    let hasher = PasswordHasher::new(BCRYPT_COST);
+
+    let repository = MemoryRepository::new();
+    repository
+        .seed_account(
+            DB_Account {
+                id: 0,
+                phone_number: "+905550000000".to_string(),
+                name: "Admin".to_string(),
+                lastname: "Admin".to_string(),
+                email: ADMIN_EMAIL.to_string(),
+                hashed_password: hasher.hash_password(ADMIN_PASSWORD),
+                password_set_ts: unix_timestamp() as i64,
+                member_since: 0,
+            },
+            &[ROLE_ADMIN],
+        )
+        .unwrap();
+    repository
+        .seed_account(
+            DB_Account {
+                id: 0,
+                phone_number: "+905550000001".to_string(),
+                name: "Member".to_string(),
+                lastname: "Member".to_string(),
+                email: MEMBER_EMAIL.to_string(),
+                hashed_password: hasher.hash_password(MEMBER_PASSWORD),
+                password_set_ts: unix_timestamp() as i64,
+                member_since: 0,
+            },
+            &[],
+        )
+        .unwrap();
+
+    let outbox = Arc::new(Outbox::default());
+
+    let state = AppState {
+        db: web::Data::new(Arc::new(repository)),
+        jwt_keys: web::Data::new(jwt_keys()),
+        token_lifetimes: web::Data::new(TokenLifetimes {
+            access: ACCESS_TOKEN_EXPIRES_IN,
+            refresh: REFRESH_TOKEN_EXPIRES_IN,
+            mfa_pending: MFA_PENDING_TOKEN_EXPIRES_IN,
+            password_reset: PASSWORD_RESET_TOKEN_EXPIRES_IN,
+        }),
+        password_hasher: web::Data::new(hasher),
+        mail_sender: web::Data::from(outbox.clone() as Arc<dyn MailSender>),
+        login_throttle: web::Data::new(LoginThrottle::default()),
+        metrics: web::Data::new(Metrics::new().unwrap()),
+        cors: CorsConfig {
+            allowed_origins: Vec::new(),
+            max_age_secs: 0,
+        },
+    };
+
+    (state, outbox)
+}
+
+pub async fn service()
+-> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    test::init_service(app(state())).await
+}
+
+/// Status and JSON body of the response, `Value::Null` when the body is empty.
+pub async fn send<S, B>(service: &S, request: test::TestRequest) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(service, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    let body = match body.is_empty() {
+        true => Value::Null,
+        false => serde_json::from_slice(&body).unwrap(),
+    };
+
+    (status, body)
+}
+
+pub async fn login<S, B>(service: &S, email: &str, password: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    send(
+        service,
+        test::TestRequest::post()
+            .uri("/auth/connect")
+            .set_form([("email", email), ("password", password)]),
+    )
+    .await
+}
+
+pub async fn refresh<S, B>(service: &S, refresh_token: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    send(
+        service,
+        test::TestRequest::post()
+            .uri("/auth/refresh")
+            .set_form([("refresh_token", refresh_token)]),
+    )
+    .await
+}
+
+/// Access token of the account, which has to log in successfully.
+pub async fn access_token<S, B>(service: &S, email: &str, password: &str) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, body) = login(service, email, password).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+/// Current RFC 6238 code of a base32 secret, computed apart from the server.
+pub fn totp_code(secret: &str) -> String {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    let mut mac = Hmac::<Sha1>::new_from_slice(&secret).unwrap();
+    mac.update(&(unix_timestamp() / 30).to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[19] & 0x0f) as usize;
+    let value = u32::from_be_bytes(digest[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    format!("{:06}", value % 1_000_000)
+}
+
+pub async fn verify_mfa<S, B>(service: &S, mfa_token: &str, code: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    send(
+        service,
+        test::TestRequest::post()
+            .uri("/auth/mfa/verify")
+            .set_form([("mfa_token", mfa_token), ("code", code)]),
+    )
+    .await
+}
+
+pub async fn enroll_mfa<S, B>(
+    service: &S,
+    mfa_token: &str,
+    enrollment_token: &str,
+) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    send(
+        service,
+        test::TestRequest::post().uri("/auth/mfa/enroll").set_form([
+            ("mfa_token", mfa_token),
+            ("enrollment_token", enrollment_token),
+        ]),
+    )
+    .await
+}
+
+/// Enrollment token signed with the test keys, standing in for the one mailed at login.
+pub fn enrollment_token(email: &str) -> String {
+    let claims = Claims::for_totp_enrollment_token(
+        unix_timestamp() as usize,
+        MFA_PENDING_TOKEN_EXPIRES_IN,
+        email.to_string(),
+    );
+
+    jwt_keys().encode(&claims).unwrap()
+}
+
+/// `mfa_token` and TOTP secret of the first login of the admin, which has to enroll.
+pub async fn enroll_admin<S, B>(service: &S) -> (String, String)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, body) = login(service, ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["enrollment_required"], true, "{body}");
+    let mfa_token = body["mfa_token"].as_str().unwrap().to_string();
+
+    let (status, body) = enroll_mfa(service, &mfa_token, &enrollment_token(ADMIN_EMAIL)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    (mfa_token, body["secret"].as_str().unwrap().to_string())
+}
+
+/// Access token of the admin, which enrolls in two-factor authentication on its first login.
+///
+/// A code is only accepted once, so the admin can only log in once per service.
+pub async fn admin_token<S, B>(service: &S) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (mfa_token, secret) = enroll_admin(service).await;
+
+    let (status, body) = verify_mfa(service, &mfa_token, &totp_code(&secret)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+pub fn get(uri: &str, token: &str) -> test::TestRequest {
+    test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {token}")))
+}
+
+pub fn post(uri: &str, token: &str, body: Value) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {token}")))
+        .set_json(body)
+}
+
+pub fn put(uri: &str, token: &str, body: Value) -> test::TestRequest {
+    test::TestRequest::put()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {token}")))
+        .set_json(body)
+}
+
+/// Request body of a new account, unique per `n`.
+pub fn new_account(n: u32) -> Value {
+    json!({
+        "name": "Ayşe",
+        "lastname": "Yılmaz",
+        "email": format!("member{n}@example.com"),
+        "phone_number": format!("+9055512345{n:02}"),
+        "password": "Member-password-1",
+        // 2020-01-01, members are charged the fees the tests create for 2020
+        "member_since": 1577836800,
+    })
+}
+
+/// Creates the account as the admin and returns its id.
+pub async fn create_account<S, B>(service: &S, admin_token: &str, n: u32) -> i64
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, body) = send(
+        service,
+        post("/api/v1/account", admin_token, new_account(n)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    let email = format!("member{n}@example.com");
+    let (status, body) = send(
+        service,
+        get(
+            &format!("/api/v1/accounts?q={email}&sort=1&count=1"),
+            admin_token,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    body["accounts"][0]["id"].as_i64().unwrap()
+}
+
+/// Access token signed with the test keys that expired an hour ago.
+pub fn expired_access_token(email: &str) -> String {
+    let now = unix_timestamp() as usize;
+    let claims = Claims::for_access_token(
+        now - 2 * 60 * 60,
+        60 * 60,
+        email.to_string(),
+        vec![ROLE_ADMIN.to_string()],
+        Vec::new(),
+    );
+
+    jwt_keys().encode(&claims).unwrap()
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use common::{MEMBER_EMAIL, MEMBER_PASSWORD, login, send, service};
+
+#[actix_web::test]
+async fn probes_need_no_token() {
+    let service = service().await;
+
+    let (status, body) = send(&service, test::TestRequest::get().uri("/healthz")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["status"], "ok");
+
+    let (status, body) = send(&service, test::TestRequest::get().uri("/readyz")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["status"], "ready");
+}
+
+#[actix_web::test]
+async fn version_names_the_build() {
+    let service = service().await;
+
+    let (status, body) = send(&service, test::TestRequest::get().uri("/version")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(body["git_commit"].is_string());
+    assert!(body["built_at"].as_u64().unwrap() > 0);
+}
+
+#[actix_web::test]
+async fn metrics_count_requests_and_logins() {
+    let service = service().await;
+
+    let (status, _) = login(&service, MEMBER_EMAIL, MEMBER_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = login(&service, MEMBER_EMAIL, "Wrong-password-1").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let response = test::call_service(
+        &service,
+        test::TestRequest::get().uri("/metrics").to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = test::read_body(response).await;
+    let body = std::str::from_utf8(&body).unwrap();
+
+    assert!(body.contains(
+        r#"agem_http_request_duration_seconds_count{method="POST",route="/auth/connect",status="200"} 1"#
+    ));
+    assert!(body.contains(r#"agem_auth_tokens_issued_total{grant="password"} 1"#));
+    assert!(body.contains(r#"agem_auth_failed_logins_total{reason="invalid_credentials"} 1"#));
+    // the memory backend has no connection pool
+    assert!(!body.contains("agem_db_pool_"));
+}
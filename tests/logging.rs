@@ -0,0 +1,120 @@
+mod common;
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::http::StatusCode;
+use common::{MEMBER_EMAIL, MEMBER_PASSWORD, get, login, refresh, send, service};
+use serde_json::Value;
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Events written by the subscriber of the test, one JSON object per line.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Logs {
+    type Writer = Logs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+impl Logs {
+    /// Captures every event of the current thread, which runs the service of the test.
+    fn capture() -> (Logs, DefaultGuard) {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .flatten_event(true)
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(logs.clone())
+            .finish();
+
+        (logs.clone(), tracing::subscriber::set_default(subscriber))
+    }
+
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+
+    /// Completion events of the requests, with the fields of their span.
+    fn requests(&self) -> Vec<Value> {
+        self.text()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|event| event["message"] == "request completed")
+            .map(|event| event["span"].clone())
+            .collect()
+    }
+}
+
+#[actix_web::test]
+async fn requests_are_logged_with_their_subject_and_correlation_id() {
+    let service = service().await;
+    let (logs, _guard) = Logs::capture();
+
+    let (status, body) = login(&service, MEMBER_EMAIL, MEMBER_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let token = body["access_token"].as_str().unwrap();
+    let (status, body) = send(
+        &service,
+        get("/auth/me", token).insert_header(("X-Correlation-Id", "log-test-1")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let requests = logs.requests();
+    assert_eq!(requests.len(), 2, "{}", logs.text());
+
+    assert_eq!(requests[0]["method"], "POST");
+    assert_eq!(requests[0]["path"], "/auth/connect");
+    assert_eq!(requests[0]["status"], 200);
+    assert!(requests[0]["subject"].is_null());
+
+    assert_eq!(requests[1]["method"], "GET");
+    assert_eq!(requests[1]["path"], "/auth/me");
+    assert_eq!(requests[1]["status"], 200);
+    assert_eq!(requests[1]["subject"], MEMBER_EMAIL);
+    assert_eq!(requests[1]["correlation_id"], "log-test-1");
+    assert!(requests[1]["latency_ms"].is_u64());
+}
+
+#[actix_web::test]
+async fn passwords_and_tokens_are_never_logged() {
+    let service = service().await;
+    let (logs, _guard) = Logs::capture();
+
+    let (status, body) = login(&service, MEMBER_EMAIL, MEMBER_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let refresh_token = body["refresh_token"].as_str().unwrap().to_string();
+    let (status, body) = refresh(&service, &refresh_token).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let access_token = body["access_token"].as_str().unwrap().to_string();
+    // a token in the query string stays out of the logged path
+    send(
+        &service,
+        get(&format!("/auth/me?token={access_token}"), &access_token),
+    )
+    .await;
+
+    let text = logs.text();
+    assert_eq!(logs.requests().len(), 3, "{text}");
+    assert!(!text.contains(MEMBER_PASSWORD), "{text}");
+    assert!(!text.contains(&refresh_token), "{text}");
+    assert!(!text.contains(&access_token), "{text}");
+}
@@ -0,0 +1,416 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use common::{admin_token, create_account, get, new_account, post, send, service};
+use serde_json::{Value, json};
+
+fn try_amount(amount: &str) -> Value {
+    json!({ "amount": amount, "currency": "TRY" })
+}
+
+fn payment(account_id: i64, periods: Value) -> Value {
+    json!({ "account_id": account_id, "periods": periods })
+}
+
+#[actix_web::test]
+async fn payments_cover_the_outstanding_fee() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    let account_id = create_account(&service, &admin, 1).await;
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/monthly_fees",
+            &admin,
+            json!({ "year": 2020, "month": 1, "amount": try_amount("150.00") }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    // partial payment first, the rest defaults to what is outstanding
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(
+                account_id,
+                json!([{ "year": 2020, "month": 1, "amount": try_amount("50.00") }]),
+            ),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    assert_eq!(body["made"][0]["amount"], try_amount("50.00"));
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(account_id, json!([{ "year": 2020, "month": 1 }])),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    assert_eq!(body["made"][0]["amount"], try_amount("100.00"));
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(account_id, json!([{ "year": 2020, "month": 1 }])),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["detail"], "Period 2020-01 is already paid.");
+
+    let (status, body) = send(
+        &service,
+        get(&format!("/api/v1/account/{account_id}/statement"), &admin),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["periods"][0]["outstanding"], try_amount("0.00"));
+    assert_eq!(body["balance"], json!([]));
+}
+
+#[actix_web::test]
+async fn payment_can_not_exceed_the_fee() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    let account_id = create_account(&service, &admin, 1).await;
+    send(
+        &service,
+        post(
+            "/api/v1/monthly_fees",
+            &admin,
+            json!({ "year": 2020, "month": 1, "amount": try_amount("150.00") }),
+        ),
+    )
+    .await;
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(
+                account_id,
+                json!([{ "year": 2020, "month": 1, "amount": try_amount("150.01") }]),
+            ),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{body}");
+
+    let (status, _) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(
+                account_id,
+                json!([{ "year": 2020, "month": 1, "amount": { "amount": "10.00", "currency": "EUR" } }]),
+            ),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // nothing was recorded by the refused payments
+    let (status, body) = send(
+        &service,
+        get(&format!("/api/v1/account/{account_id}/payments"), &admin),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["made"], json!([]));
+}
+
+#[actix_web::test]
+async fn pre_payment_is_applied_when_the_fee_is_created() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    let account_id = create_account(&service, &admin, 1).await;
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(account_id, json!([{ "year": 2020, "month": 2 }])),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(
+                account_id,
+                json!([{ "year": 2020, "month": 2, "amount": try_amount("40.00") }]),
+            ),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    assert_eq!(body["made"], json!([]));
+    assert_eq!(body["precovered"][0]["amount"], try_amount("40.00"));
+    let pre_payment_id = body["precovered"][0]["id"].clone();
+
+    // only what is left of a pre-payment is applied
+    let reverse = |amount: Value| {
+        post(
+            "/api/v1/payments/reverse",
+            &admin,
+            json!({ "account_id": account_id, "pre_payment_id": pre_payment_id, "amount": amount }),
+        )
+    };
+    let (status, body) = send(&service, reverse(try_amount("10.00"))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/monthly_fees/year",
+            &admin,
+            json!({ "year": 2020, "amount": try_amount("100.00") }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    assert_eq!(body["fees"].as_array().unwrap().len(), 12);
+    assert_eq!(body["pre_payments"]["applied"][0]["account_id"], account_id);
+    assert_eq!(
+        body["pre_payments"]["applied"][0]["amount"],
+        try_amount("30.00")
+    );
+    assert_eq!(body["pre_payments"]["orphaned"], json!([]));
+
+    // the applied pre-payment stays listed with the payment it became
+    let (_, body) = send(
+        &service,
+        get(&format!("/api/v1/account/{account_id}/payments"), &admin),
+    )
+    .await;
+    assert_eq!(body["precovered"][0]["id"], pre_payment_id);
+    assert_eq!(body["precovered"][0]["amount"], try_amount("40.00"));
+    assert_eq!(
+        body["precovered"][0]["reversals"][0]["amount"],
+        try_amount("10.00")
+    );
+    assert_eq!(
+        body["precovered"][0]["applied_payment_id"],
+        body["made"][0]["id"]
+    );
+    let (status, body) = send(&service, reverse(try_amount("10.00"))).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{body}");
+
+    let (_, body) = send(
+        &service,
+        get(&format!("/api/v1/account/{account_id}/statement"), &admin),
+    )
+    .await;
+    assert_eq!(body["periods"][1]["paid"], try_amount("30.00"));
+    assert_eq!(body["periods"][1]["precovered"], try_amount("0.00"));
+    assert_eq!(body["periods"][1]["outstanding"], try_amount("70.00"));
+    assert_eq!(body["balance"], json!([try_amount("1170.00")]));
+}
+
+#[actix_web::test]
+async fn reversed_payment_is_outstanding_again() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    let account_id = create_account(&service, &admin, 1).await;
+    send(
+        &service,
+        post(
+            "/api/v1/monthly_fees",
+            &admin,
+            json!({ "year": 2020, "month": 1, "amount": try_amount("150.00") }),
+        ),
+    )
+    .await;
+    let (_, body) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(
+                account_id,
+                json!([
+                    { "year": 2020, "month": 1 },
+                    { "year": 2020, "month": 2, "amount": try_amount("150.00") },
+                ]),
+            ),
+        ),
+    )
+    .await;
+    let payment_id = body["made"][0]["id"].clone();
+    let pre_payment_id = body["precovered"][0]["id"].clone();
+
+    // part of the payment first, then what is left of it
+    let reverse = |amount: Option<Value>| {
+        let mut request = json!({ "account_id": account_id, "payment_id": payment_id });
+        if let Some(amount) = amount {
+            request["amount"] = amount;
+        }
+        post("/api/v1/payments/reverse", &admin, request)
+    };
+    let (status, body) = send(&service, reverse(Some(try_amount("200.00")))).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{body}");
+    let (status, body) = send(&service, reverse(Some(try_amount("50.00")))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (_, body) = send(
+        &service,
+        get(&format!("/api/v1/account/{account_id}/payments"), &admin),
+    )
+    .await;
+    assert_eq!(body["made"][0]["amount"], try_amount("150.00"));
+    assert_eq!(
+        body["made"][0]["reversals"][0]["amount"],
+        try_amount("50.00")
+    );
+    let (status, body) = send(&service, reverse(None)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (status, _) = send(&service, reverse(None)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // the payment stays listed with every reversal, the pre-payment of the other period is kept
+    let (_, me) = send(&service, get("/api/v1/me", &admin)).await;
+    let (_, body) = send(
+        &service,
+        get(&format!("/api/v1/account/{account_id}/payments"), &admin),
+    )
+    .await;
+    let reversals = body["made"][0]["reversals"].as_array().unwrap();
+    assert_eq!(reversals.len(), 2);
+    assert_eq!(reversals[1]["amount"], try_amount("100.00"));
+    assert!(reversals.iter().all(|r| r["reversed_by"] == me["id"]));
+    assert_eq!(body["precovered"][0]["id"], pre_payment_id);
+
+    // the offset defaults to the first page
+    let (status, body) = send(&service, get("/api/v1/arrears?sort=1&count=10", &admin)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let arrears = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["account_id"] == account_id)
+        .unwrap();
+    assert_eq!(arrears["outstanding"], try_amount("150.00"));
+}
+
+#[actix_web::test]
+async fn fees_before_the_membership_are_not_charged() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    let mut account = new_account(1);
+    // 2020-03-15
+    account["member_since"] = json!(1584230400);
+    let (status, body) = send(&service, post("/api/v1/account", &admin, account)).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let (_, body) = send(
+        &service,
+        get(
+            "/api/v1/accounts?q=member1@example.com&sort=1&count=1",
+            &admin,
+        ),
+    )
+    .await;
+    let account_id = body["accounts"][0]["id"].clone();
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/monthly_fees/year",
+            &admin,
+            json!({ "year": 2020, "amount": try_amount("100.00") }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    let (status, body) = send(
+        &service,
+        get(&format!("/api/v1/account/{account_id}/statement"), &admin),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let periods = body["periods"].as_array().unwrap();
+    assert_eq!(periods.len(), 10);
+    assert_eq!(periods[0]["month"], 3);
+    assert_eq!(body["balance"], json!([try_amount("1000.00")]));
+
+    let (_, body) = send(
+        &service,
+        get("/api/v1/arrears?sort=1&offset=0&count=10", &admin),
+    )
+    .await;
+    let arrears = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["account_id"] == account_id)
+        .unwrap();
+    assert_eq!(arrears["months_overdue"], 10);
+    assert_eq!(arrears["since"], json!({ "year": 2020, "month": 3 }));
+}
+
+#[actix_web::test]
+async fn payment_of_unknown_account_is_not_found() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+
+    let (status, body) = send(
+        &service,
+        post(
+            "/api/v1/payments",
+            &admin,
+            payment(9999, json!([{ "year": 2020, "month": 1 }])),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
+
+#[actix_web::test]
+async fn lists_page_without_a_query_string() {
+    let service = service().await;
+    let admin = admin_token(&service).await;
+    for year in 2020..2029 {
+        let (status, body) = send(
+            &service,
+            post(
+                "/api/v1/monthly_fees/year",
+                &admin,
+                json!({ "year": year, "amount": try_amount("100.00") }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+    }
+
+    let (status, body) = send(&service, get("/api/v1/monthly_fees", &admin)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let fees = body.as_array().unwrap();
+    assert_eq!(fees.len(), 20);
+    assert_eq!(fees[0]["year"], 2020);
+    assert_eq!(fees[0]["month"], 1);
+
+    // 108 fees exist, a page holds at most 100
+    let (_, body) = send(&service, get("/api/v1/monthly_fees?count=1000", &admin)).await;
+    assert_eq!(body.as_array().unwrap().len(), 100);
+
+    let (status, body) = send(&service, get("/api/v1/arrears", &admin)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}